@@ -0,0 +1,72 @@
+//! The agent's settings: `agent.toml` in the working directory, or the file named by
+//! `PAAS_AGENT_CONFIG`, with env vars taking precedence. The file is optional.
+
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address paasd sends process requests to, e.g. `127.0.0.1:8001`. `PAAS_AGENT_LISTEN`
+    pub listen: String,
+    /// Where logs, status changes and run results are reported. `PAASD_URL`
+    pub paasd_url: String,
+    /// Seconds a request to paasd may take. `PAAS_AGENT_PAASD_TIMEOUT`
+    pub paasd_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: "127.0.0.1:8001".to_string(),
+            paasd_url: paas_client::DEFAULT_BASE_URL.to_string(),
+            paasd_timeout: 60,
+        }
+    }
+}
+
+impl Config {
+    pub fn paasd_timeout(&self) -> Duration {
+        Duration::from_secs(self.paasd_timeout)
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Read the config file and env vars. Called once at startup, before `config()` is used.
+pub fn load() -> Result<&'static Config, String> {
+    let mut config = match env::var("PAAS_AGENT_CONFIG") {
+        Ok(path) => read(Path::new(&path))?,
+        Err(_) if Path::new("agent.toml").exists() => read(Path::new("agent.toml"))?,
+        Err(_) => Config::default(),
+    };
+
+    override_with(&mut config.listen, "PAAS_AGENT_LISTEN")?;
+    override_with(&mut config.paasd_url, "PAASD_URL")?;
+    override_with(&mut config.paasd_timeout, "PAAS_AGENT_PAASD_TIMEOUT")?;
+
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The settings `load` read at startup
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("config::load runs at startup")
+}
+
+fn read(path: &Path) -> Result<Config, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    toml::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+/// Replace a setting with the value of `var`, if it is set
+fn override_with<T: FromStr>(setting: &mut T, var: &str) -> Result<(), String> {
+    if let Ok(value) = env::var(var) {
+        *setting = value.parse().map_err(|_| format!("Invalid {}: '{}'", var, value))?;
+    }
+    Ok(())
+}
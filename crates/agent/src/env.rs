@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+
+use shared::{Application, ServiceDiscovery, link_env_name};
+use shared::interpolate::{referenced_apps, resolve_env};
+
+/// `${app:<name>.<attribute>}` of a deployed app
+fn app_attribute(app: &Application, attribute: &str) -> Option<String> {
+    match attribute {
+        "url" => Some(format!("http://127.0.0.1:{}", app.port)),
+        "host" => Some("127.0.0.1".to_string()),
+        "port" => Some(app.port.to_string()),
+        "name" => Some(app.name.clone()),
+        "id" => app.id.map(|id| id.to_string()),
+        _ => None,
+    }
+}
+
+/// The apps referenced by name. Names paasd doesn't know are left out.
+async fn fetch_apps(names: impl IntoIterator<Item = &str>) -> Result<Vec<Application>, String> {
+    let mut apps = Vec::new();
+    for name in names {
+        match crate::paasd().app(name).await {
+            Ok(details) => apps.push(details.app),
+            Err(e) if e.is_not_found() => {}
+            Err(e) => return Err(format!("Failed to look up app '{}': {}", name, e)),
+        }
+    }
+    Ok(apps)
+}
+
+/// The address of a linked app, looked up through paasd's discovery API
+async fn discover(name: &str) -> Result<ServiceDiscovery, String> {
+    crate::paasd()
+        .discover(name)
+        .await
+        .map_err(|e| format!("Failed to look up linked app '{}': {}", name, e))
+}
+
+/// The environment a process of `app` runs with: `<NAME>_URL` for its links, its env vars
+/// with `${...}` references resolved, then its secrets, then the platform's own vars (`PORT`, `PAAS_*`), which take precedence
+pub async fn app_env(app: &Application, secrets: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    let mut platform = BTreeMap::from([
+        ("PORT".to_string(), app.port.to_string()),
+        ("PAAS_APP_NAME".to_string(), app.name.clone()),
+    ]);
+    if let Some(app_id) = app.id {
+        platform.insert("PAAS_APP_ID".to_string(), app_id.to_string());
+    }
+    if let Some(release_id) = app.release_id {
+        platform.insert("PAAS_RELEASE".to_string(), release_id.to_string());
+    }
+
+    // Other apps are looked up now, so their current port is used
+    let mut vars = app.env_map();
+    for name in app.link_names() {
+        // A missing link shouldn't keep the app down; paasd restarts it once the link moves
+        match discover(&name).await {
+            Ok(service) => {
+                vars.entry(link_env_name(&name)).or_insert(service.url);
+            }
+            Err(e) => eprintln!("{}: {}", app.name, e),
+        }
+    }
+    let apps = fetch_apps(referenced_apps(&vars)).await?;
+    let lookup = |name: &str, attribute: &str| {
+        apps.iter()
+            .find(|a| a.name == name)
+            .and_then(|a| app_attribute(a, attribute))
+    };
+
+    let mut env = resolve_env(&vars, secrets, &platform, &lookup)?;
+    env.extend(secrets.clone());
+    env.extend(platform);
+    Ok(env)
+}
@@ -1,10 +1,27 @@
 use actix_web::{App, HttpResponse, HttpServer, Responder, web};
-use reqwest::Client;
-use shared::{Application, NewAppLog};
+use paas_client::Client;
+use shared::error;
+use shared::{
+    ApiError, AppProcess, AppRun, AppStatus, Application, BUILD_PROCESS, BuildResult, DEFAULT_BUILD_TIMEOUT_SECS,
+    ErrorCode, FinishRun, NewAppLog, PatchApplication, PatchProcess, RUN_PROCESS, RunBuild, RunProcess, StartRun,
+    WEB_PROCESS,
+};
+use env::app_env;
+use ports::{can_inspect_ports, listening_ports};
+use release::{prepare_release, remove_releases};
+use std::collections::BTreeMap;
 use std::process::Stdio;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicI32, AtomicU8, Ordering};
+use std::time::Instant;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 
+mod config;
+mod env;
+mod ports;
+mod release;
+
 #[cfg(windows)]
 mod job_object {
     use windows::Win32::Foundation::HANDLE;
@@ -48,6 +65,9 @@ mod job_object {
 
 const MAX_RETRIES: u32 = 3;
 
+/// Decrypted secrets received from paasd. Kept in memory only, for restarts.
+type Secrets = BTreeMap<String, String>;
+
 fn detect_port(line: &str) -> Option<i32> {
     let line_lower = line.to_lowercase();
 
@@ -80,24 +100,26 @@ fn detect_port(line: &str) -> Option<i32> {
         if let Some(idx) = line.find(prefix) {
             let after = &line[idx + prefix.len()..];
             let port_str: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(port) = port_str.parse::<i32>() {
-                if port > 1024 && port < 65536 {
-                    return Some(port);
-                }
+            if let Ok(port) = port_str.parse::<i32>()
+                && port > 1024
+                && port < 65536
+            {
+                return Some(port);
             }
         }
     }
 
     // Pattern 2: "listening on :8080" or ":8080" (Go style)
-    if line_lower.contains("listening") || line_lower.contains("serving") {
-        if let Some(colon_pos) = line.rfind(':') {
-            let after = &line[colon_pos + 1..];
-            let port_str: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
-            if let Ok(port) = port_str.parse::<i32>() {
-                if port > 1024 && port < 65536 {
-                    return Some(port);
-                }
-            }
+    if (line_lower.contains("listening") || line_lower.contains("serving"))
+        && let Some(colon_pos) = line.rfind(':')
+    {
+        let after = &line[colon_pos + 1..];
+        let port_str: String = after.chars().take_while(|c| c.is_ascii_digit()).collect();
+        if let Ok(port) = port_str.parse::<i32>()
+            && port > 1024
+            && port < 65536
+        {
+            return Some(port);
         }
     }
 
@@ -105,14 +127,15 @@ fn detect_port(line: &str) -> Option<i32> {
     if line_lower.contains("port") {
         let words: Vec<&str> = line.split_whitespace().collect();
         for (i, word) in words.iter().enumerate() {
-            if word.to_lowercase() == "port" {
-                if let Some(next) = words.get(i + 1) {
-                    let port_str: String = next.chars().take_while(|c| c.is_ascii_digit()).collect();
-                    if let Ok(port) = port_str.parse::<i32>() {
-                        if port > 1024 && port < 65536 {
-                            return Some(port);
-                        }
-                    }
+            if word.to_lowercase() == "port"
+                && let Some(next) = words.get(i + 1)
+            {
+                let port_str: String = next.chars().take_while(|c| c.is_ascii_digit()).collect();
+                if let Ok(port) = port_str.parse::<i32>()
+                    && port > 1024
+                    && port < 65536
+                {
+                    return Some(port);
                 }
             }
         }
@@ -121,164 +144,259 @@ fn detect_port(line: &str) -> Option<i32> {
     None
 }
 
-async fn update_status(app_id: uuid::Uuid, status: &str) {
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}", app_id);
-    let _ = client
-        .patch(&url)
-        .json(&serde_json::json!({ "status": status }))
-        .send()
-        .await;
+/// The paasd API. Every log line and state change is reported as it happens, so requests
+/// aren't retried: a paasd outage shouldn't hold up the processes producing them.
+fn paasd() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        let config = config::config();
+        Client::builder()
+            .base_url(&config.paasd_url)
+            .timeout(config.paasd_timeout())
+            .retries(0)
+            .build()
+    })
+}
+
+async fn update_status(process: &AppProcess, status: AppStatus) {
+    let patch = PatchProcess {
+        status: Some(status),
+        ..Default::default()
+    };
+    let _ = paasd().patch_process(process.app_id, process.id, &patch).await;
+}
+
+/// Whether paasd still wants this process instance running with the given PID.
+/// A STOPPED status, a removed instance (scaled down) or a replaced PID all mean no.
+async fn should_restart(process: &AppProcess, pid: u32) -> bool {
+    match paasd().process(process.app_id, process.id).await {
+        Ok(current) => {
+            let stopped = matches!(current.status, AppStatus::STOPPED);
+            let replaced = current.pid.is_some_and(|p| p != pid as i32);
+            !stopped && !replaced
+        }
+        Err(e) if e.is_not_found() => false,
+        Err(_) => true,
+    }
 }
 
 async fn send_log(log: NewAppLog) {
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}/logs", log.app_id);
-    if let Err(e) = client.post(&url).json(&log).send().await {
+    if let Err(e) = paasd().send_log(&log).await {
         eprintln!("Failed to send log to paasd: {}", e);
     }
 }
 
-async fn spawn_app(app: Application, attempt: u32) {
-    let app_id = app.id.unwrap();
-    let parts: Vec<&str> = app.command.split_whitespace().collect();
-    if parts.is_empty() {
-        eprintln!("Empty command");
+/// Record the port the app's web process actually listens on
+async fn report_port(app_id: uuid::Uuid, port: i32) {
+    let patch = PatchApplication {
+        port: Some(port),
+        port_detected: true,
+        ..Default::default()
+    };
+    if let Err(e) = paasd().patch_app(app_id, &patch).await {
+        eprintln!("Failed to report port: {}", e);
+    }
+}
+
+/// How long to look for the web process's listening socket before relying on its log output
+const PORT_PROBE_SECS: u32 = 30;
+
+/// Port detection states of a web process
+const PORT_PROBING: u8 = 0;
+const PORT_CONFIRMED: u8 = 1;
+const PORT_FROM_LOGS: u8 = 2;
+
+/// Watch the process tree's sockets until it listens on a port. If it never does
+/// within `PORT_PROBE_SECS`, fall back to the port seen in its logs (if any).
+async fn probe_port(
+    app_id: uuid::Uuid,
+    expected: i32,
+    pid: u32,
+    state: Arc<AtomicU8>,
+    logged_port: Arc<AtomicI32>,
+) {
+    for _ in 0..PORT_PROBE_SECS {
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        let ports = tokio::task::spawn_blocking(move || listening_ports(pid))
+            .await
+            .unwrap_or_default();
+        let Some(&first) = ports.first() else {
+            continue;
+        };
+
+        state.store(PORT_CONFIRMED, Ordering::SeqCst);
+        if ports.iter().any(|&p| p as i32 == expected) {
+            println!("Confirmed app listening on port {}", expected);
+        } else {
+            println!("App is listening on port {} instead of PORT={}", first, expected);
+            report_port(app_id, first as i32).await;
+        }
+        return;
+    }
+
+    println!("No listening socket found for PID {}, using ports from its logs", pid);
+    state.store(PORT_FROM_LOGS, Ordering::SeqCst);
+    let port = logged_port.load(Ordering::SeqCst);
+    if port > 0 && port != expected {
+        report_port(app_id, port).await;
+    }
+}
+
+/// Handle a port mentioned in a log line, which is only trusted when `/proc` gave no answer
+async fn port_from_log(app_id: uuid::Uuid, line: &str, state: &AtomicU8, logged_port: &AtomicI32) {
+    let Some(port) = detect_port(line) else {
         return;
+    };
+    match state.load(Ordering::SeqCst) {
+        PORT_PROBING => logged_port.store(port, Ordering::SeqCst),
+        PORT_FROM_LOGS => {
+            println!("Detected app running on port {}", port);
+            report_port(app_id, port).await;
+        }
+        _ => {}
     }
+}
 
-    // On Windows, npm/npx/yarn are .cmd files and need special handling
-    let raw_program = parts[0].to_string();
+/// Run a command line through the platform shell so one-off commands can use pipes and `&&`
+fn shell_command(command: &str) -> Command {
     #[cfg(target_os = "windows")]
-    let program = match raw_program.as_str() {
-        "npm" => "npm.cmd".to_string(),
-        "npx" => "npx.cmd".to_string(),
-        "yarn" => "yarn.cmd".to_string(),
-        "pnpm" => "pnpm.cmd".to_string(),
-        _ => raw_program,
+    let cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
     };
     #[cfg(not(target_os = "windows"))]
-    let program = raw_program;
+    let cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    cmd
+}
 
-    let args: Vec<String> = parts[1..].iter().map(|s| s.to_string()).collect();
+async fn spawn_process(app: Application, process: AppProcess, secrets: Secrets, attempt: u32) {
+    let app_id = app.id.unwrap();
+    let label = format!("{}.{}", process.process_type, process.instance);
+    if process.command.trim().is_empty() {
+        eprintln!("Empty command");
+        return;
+    }
 
-    let mut cmd = Command::new(&program);
-    cmd.args(&args)
-        .current_dir(&app.working_dir)
+    // Through the shell like runs and builds, so quoting, `&&` and `$PORT` work
+    let mut cmd = shell_command(&process.command);
+    cmd.current_dir(&app.working_dir)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    // Own process group, so stopping or signalling the app reaches the command and not just the shell
+    #[cfg(unix)]
+    cmd.process_group(0);
 
-    if let Some(env_obj) = &app.env_vars {
-        if let Some(map) = env_obj.as_object() {
-            for (key, val) in map {
-                if let Some(val_str) = val.as_str() {
-                    cmd.env(key, val_str);
-                }
-            }
+    match app_env(&app, &secrets).await {
+        Ok(env) => cmd.envs(env),
+        Err(e) => {
+            eprintln!("Failed to start process {}: {}", label, e);
+            send_log(NewAppLog {
+                app_id,
+                stream: "stderr".to_string(),
+                message: format!("[PaaS] {}", e),
+                process_type: Some(process.process_type.clone()),
+                run_id: None,
+            })
+            .await;
+            update_status(&process, AppStatus::CRASHED).await;
+            return;
         }
-    }
+    };
 
-    let mut process = match cmd.spawn() {
+    let mut child = match cmd.spawn() {
         Ok(p) => p,
         Err(e) => {
-            eprintln!("Failed to execute process: {}", e);
-            update_status(app_id, "CRASHED").await;
+            eprintln!("Failed to execute process {}: {}", label, e);
+            update_status(&process, AppStatus::CRASHED).await;
             return;
         }
     };
 
-    let pid = process.id().unwrap_or(0);
-    println!("Application started with PID: {} (attempt {}/{})", pid, attempt, MAX_RETRIES);
+    let pid = child.id().unwrap_or(0);
+    println!(
+        "Process {} started with PID: {} (attempt {}/{})",
+        label, pid, attempt, MAX_RETRIES
+    );
 
     // Assign to Job Object so child processes die when agent stops (Windows only)
     #[cfg(windows)]
     job_object::assign_process_to_job(pid);
 
     // Send PID and update status to RUNNING
-    let client_pid = Client::new();
-    let patch_url = format!("http://127.0.0.1:8080/apps/{}", app_id);
-    let patch_body = serde_json::json!({ "pid": pid as i32, "status": "RUNNING" });
-    if let Err(e) = client_pid.patch(&patch_url).json(&patch_body).send().await {
+    let patch = PatchProcess {
+        status: Some(AppStatus::RUNNING),
+        pid: Some(pid as i32),
+        restarted: attempt > 1,
+    };
+    if let Err(e) = paasd().patch_process(app_id, process.id, &patch).await {
         eprintln!("Failed to update PID and status: {}", e);
     }
 
-    let stdout = process.stdout.take();
-    let stderr = process.stderr.take();
-    let app_id_stdout = app_id;
-    let app_id_stderr = app_id;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
 
-    if let Some(stdout) = stdout {
-        tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
-            while let Ok(Some(line)) = lines.next_line().await {
-                println!("[stdout] {}", line);
-                if let Some(port) = detect_port(&line) {
-                    println!("Detected app running on port {}", port);
-                    let client = Client::new();
-                    let url = format!("http://127.0.0.1:8080/apps/{}", app_id_stdout);
-                    let _ = client
-                        .patch(&url)
-                        .json(&serde_json::json!({ "port": port }))
-                        .send()
-                        .await;
-                }
-                send_log(NewAppLog {
-                    app_id: app_id_stdout,
-                    stream: "stdout".to_string(),
-                    message: line,
-                })
-                .await;
-            }
-        });
+    // Only the web process owns the app's port
+    let detect_ports = process.process_type == WEB_PROCESS;
+    let port_state = Arc::new(AtomicU8::new(if can_inspect_ports() {
+        PORT_PROBING
+    } else {
+        PORT_FROM_LOGS
+    }));
+    let logged_port = Arc::new(AtomicI32::new(0));
+    if detect_ports && can_inspect_ports() {
+        tokio::spawn(probe_port(
+            app_id,
+            app.port,
+            pid,
+            port_state.clone(),
+            logged_port.clone(),
+        ));
     }
 
-    if let Some(stderr) = stderr {
+    for (reader, stream) in [
+        (stdout.map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>), "stdout"),
+        (stderr.map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>), "stderr"),
+    ] {
+        let Some(reader) = reader else {
+            continue;
+        };
+        let process_type = process.process_type.clone();
+        let port_state = port_state.clone();
+        let logged_port = logged_port.clone();
         tokio::spawn(async move {
-            let reader = BufReader::new(stderr);
-            let mut lines = reader.lines();
+            let mut lines = BufReader::new(reader).lines();
             while let Ok(Some(line)) = lines.next_line().await {
-                println!("[stderr] {}", line);
-                if let Some(port) = detect_port(&line) {
-                    println!("Detected app running on port {}", port);
-                    let client = Client::new();
-                    let url = format!("http://127.0.0.1:8080/apps/{}", app_id_stderr);
-                    let _ = client
-                        .patch(&url)
-                        .json(&serde_json::json!({ "port": port }))
-                        .send()
-                        .await;
+                println!("[{}] {}", stream, line);
+                if detect_ports {
+                    port_from_log(app_id, &line, &port_state, &logged_port).await;
                 }
                 send_log(NewAppLog {
-                    app_id: app_id_stderr,
-                    stream: "stderr".to_string(),
+                    app_id,
+                    stream: stream.to_string(),
                     message: line,
+                    process_type: Some(process_type.clone()),
+                    run_id: None,
                 })
                 .await;
             }
         });
     }
 
-    let status = process.wait().await;
+    let status = child.wait().await;
 
     match status {
         Ok(exit_status) if exit_status.success() => {
-            println!("Application exited cleanly.");
-            update_status(app_id, "STOPPED").await;
+            println!("Process {} exited cleanly.", label);
+            update_status(&process, AppStatus::STOPPED).await;
         }
         _ => {
-            // Check if the app was intentionally stopped before restarting
-            let client = Client::new();
-            let url = format!("http://127.0.0.1:8080/apps/{}", app_id);
-            let is_intentionally_stopped = if let Ok(res) = client.get(&url).send().await {
-                if let Ok(body) = res.json::<serde_json::Value>().await {
-                    body["status"].as_str().unwrap_or("") == "STOPPED"
-                } else {
-                    false
-                }
-            } else {
-                false
-            };
+            // Check if the process was intentionally stopped before restarting
+            let is_intentionally_stopped = !should_restart(&process, pid).await;
 
             if is_intentionally_stopped {
                 println!("Process was intentionally stopped. Not restarting.");
@@ -287,7 +405,8 @@ async fn spawn_app(app: Application, attempt: u32) {
 
             if attempt < MAX_RETRIES {
                 println!(
-                    "Application crashed! Restarting... (attempt {}/{})",
+                    "Process {} crashed! Restarting... (attempt {}/{})",
+                    label,
                     attempt + 1,
                     MAX_RETRIES
                 );
@@ -295,51 +414,349 @@ async fn spawn_app(app: Application, attempt: u32) {
                     app_id,
                     stream: "stderr".to_string(),
                     message: format!(
-                        "[PaaS] App crashed. Restarting... (attempt {}/{})",
+                        "[PaaS] {} crashed. Restarting... (attempt {}/{})",
+                        label,
                         attempt + 1,
                         MAX_RETRIES
                     ),
+                    process_type: Some(process.process_type.clone()),
+                    run_id: None,
                 })
                 .await;
                 tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-                Box::pin(spawn_app(app, attempt + 1)).await;
+                Box::pin(spawn_process(app, process, secrets, attempt + 1)).await;
             } else {
                 eprintln!(
-                    "Application crashed after {} attempts. Marking as CRASHED.",
-                    MAX_RETRIES
+                    "Process {} crashed after {} attempts. Marking as CRASHED.",
+                    label, MAX_RETRIES
                 );
                 send_log(NewAppLog {
                     app_id,
                     stream: "stderr".to_string(),
                     message: format!(
-                        "[PaaS] App crashed after {} attempts. Giving up.",
-                        MAX_RETRIES
+                        "[PaaS] {} crashed after {} attempts. Giving up.",
+                        label, MAX_RETRIES
                     ),
+                    process_type: Some(process.process_type.clone()),
+                    run_id: None,
                 })
                 .await;
-                update_status(app_id, "CRASHED").await;
+                update_status(&process, AppStatus::CRASHED).await;
             }
         }
     }
 }
 
-async fn run_program(app: web::Json<Application>) -> impl Responder {
-    let app = app.into_inner();
-    println!("Starting application: {}", app.name);
+async fn run_program(run: web::Json<RunProcess>) -> impl Responder {
+    let RunProcess {
+        mut app,
+        process,
+        secrets,
+    } = run.into_inner();
+    if let Err(e) = prepare_release(&mut app).await {
+        eprintln!("{}", e);
+        send_log(NewAppLog {
+            app_id: process.app_id,
+            stream: "stderr".to_string(),
+            message: format!("[PaaS] {}", e),
+            process_type: Some(process.process_type.clone()),
+            run_id: None,
+        })
+        .await;
+        return ApiError::new(ErrorCode::Internal, e).response();
+    }
+    println!(
+        "Starting application: {} ({}.{})",
+        app.name, process.process_type, process.instance
+    );
     println!("Working directory: {}", app.working_dir);
-    println!("Command: {}", app.command);
+    println!("Command: {}", process.command);
 
     tokio::spawn(async move {
-        spawn_app(app, 1).await;
+        spawn_process(app, process, secrets, 1).await;
     });
 
     HttpResponse::Ok().finish()
 }
 
+/// Execute a one-off command in the app's environment, streaming its output
+/// to paasd under the run's ID and reporting the exit code when done.
+async fn execute_run(app: Application, run: AppRun, secrets: Secrets) {
+    let mut cmd = shell_command(&run.command);
+    cmd.current_dir(&app.working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let started = Instant::now();
+    match app_env(&app, &secrets).await {
+        Ok(env) => cmd.envs(env),
+        Err(e) => {
+            eprintln!("Failed to execute run {}: {}", run.id, e);
+            send_log(NewAppLog {
+                app_id: run.app_id,
+                stream: "stderr".to_string(),
+                message: format!("[PaaS] {}", e),
+                process_type: Some(RUN_PROCESS.to_string()),
+                run_id: Some(run.id),
+            })
+            .await;
+            report_run(&run, 127, started).await;
+            return;
+        }
+    };
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to execute run {}: {}", run.id, e);
+            send_log(NewAppLog {
+                app_id: run.app_id,
+                stream: "stderr".to_string(),
+                message: format!("[PaaS] Failed to start command: {}", e),
+                process_type: Some(RUN_PROCESS.to_string()),
+                run_id: Some(run.id),
+            })
+            .await;
+            report_run(&run, 127, started).await;
+            return;
+        }
+    };
+
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(forward_run_output(BufReader::new(stdout), run.clone(), "stdout")));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(forward_run_output(BufReader::new(stderr), run.clone(), "stderr")));
+    }
+
+    let exit_code = match child.wait().await {
+        Ok(status) => status.code().unwrap_or(-1),
+        Err(e) => {
+            eprintln!("Failed to wait for run {}: {}", run.id, e);
+            -1
+        }
+    };
+
+    // Make sure all output has reached paasd before the run is marked finished
+    for reader in readers {
+        let _ = reader.await;
+    }
+
+    println!("Run {} exited with code {}", run.id, exit_code);
+    report_run(&run, exit_code, started).await;
+}
+
+async fn forward_run_output<R>(reader: BufReader<R>, run: AppRun, stream: &'static str)
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut lines = reader.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        println!("[run {}] [{}] {}", run.id, stream, line);
+        send_log(NewAppLog {
+            app_id: run.app_id,
+            stream: stream.to_string(),
+            message: line,
+            process_type: Some(RUN_PROCESS.to_string()),
+            run_id: Some(run.id),
+        })
+        .await;
+    }
+}
+
+async fn report_run(run: &AppRun, exit_code: i32, started: Instant) {
+    let finish = FinishRun {
+        exit_code,
+        duration_ms: started.elapsed().as_millis() as i64,
+    };
+    if let Err(e) = paasd().finish_run(run.app_id, run.id, &finish).await {
+        eprintln!("Failed to report result of run {}: {}", run.id, e);
+    }
+}
+
+async fn start_run(run: web::Json<StartRun>) -> impl Responder {
+    let StartRun {
+        mut app,
+        run,
+        secrets,
+    } = run.into_inner();
+    if let Err(e) = prepare_release(&mut app).await {
+        eprintln!("{}", e);
+        return ApiError::new(ErrorCode::Internal, e).response();
+    }
+    println!("Starting one-off run for {}: {}", app.name, run.command);
+    println!("Working directory: {}", app.working_dir);
+
+    tokio::spawn(async move {
+        execute_run(app, run, secrets).await;
+    });
+
+    HttpResponse::Ok().finish()
+}
+
+async fn send_build_log(app_id: uuid::Uuid, stream: &str, message: String) {
+    send_log(NewAppLog {
+        app_id,
+        stream: stream.to_string(),
+        message,
+        process_type: Some(BUILD_PROCESS.to_string()),
+        run_id: None,
+    })
+    .await;
+}
+
+async fn forward_build_output<R>(reader: BufReader<R>, app_id: uuid::Uuid, stream: &'static str)
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut lines = reader.lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        println!("[build] [{}] {}", stream, line);
+        send_build_log(app_id, stream, line).await;
+    }
+}
+
+/// Run the app's build command to completion (or until it times out), streaming its
+/// output to paasd tagged as build output. paasd waits for the result before starting the app.
+async fn build_program(build: web::Json<RunBuild>) -> impl Responder {
+    let RunBuild { mut app, secrets } = build.into_inner();
+    let app_id = match app.id {
+        Some(id) => id,
+        None => return ApiError::invalid_request("Missing app id").response(),
+    };
+    let build = match app.build_command.as_deref().map(str::trim) {
+        Some(build) if !build.is_empty() => build.to_string(),
+        _ => return ApiError::invalid_request("App has no build command").response(),
+    };
+    let timeout_secs = app.build_timeout.unwrap_or(DEFAULT_BUILD_TIMEOUT_SECS).max(1) as u64;
+
+    if let Err(e) = prepare_release(&mut app).await {
+        eprintln!("{}", e);
+        send_build_log(app_id, "stderr", format!("[PaaS] {}. Deploy aborted.", e)).await;
+        return HttpResponse::Ok().json(BuildResult {
+            success: false,
+            exit_code: None,
+            timed_out: false,
+            duration_ms: 0,
+        });
+    }
+
+    println!("Building application: {}", app.name);
+    println!("Working directory: {}", app.working_dir);
+    println!("Build command: {}", build);
+    send_build_log(app_id, "stdout", format!("[PaaS] Building with `{}`", build)).await;
+
+    let mut cmd = shell_command(&build);
+    cmd.current_dir(&app.working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Own process group so a timeout kills the whole build, not just the shell
+    #[cfg(unix)]
+    cmd.process_group(0);
+    match app_env(&app, &secrets).await {
+        Ok(env) => cmd.envs(env),
+        Err(e) => {
+            eprintln!("{}", e);
+            send_build_log(app_id, "stderr", format!("[PaaS] {}. Deploy aborted.", e)).await;
+            return HttpResponse::Ok().json(BuildResult {
+                success: false,
+                exit_code: None,
+                timed_out: false,
+                duration_ms: 0,
+            });
+        }
+    };
+
+    let started = Instant::now();
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("Failed to start build: {}", e);
+            send_build_log(app_id, "stderr", format!("[PaaS] Failed to start build: {}", e)).await;
+            return HttpResponse::Ok().json(BuildResult {
+                success: false,
+                exit_code: None,
+                timed_out: false,
+                duration_ms: 0,
+            });
+        }
+    };
+
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(forward_build_output(BufReader::new(stdout), app_id, "stdout")));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(forward_build_output(BufReader::new(stderr), app_id, "stderr")));
+    }
+
+    let waited = tokio::time::timeout(tokio::time::Duration::from_secs(timeout_secs), child.wait()).await;
+    let (exit_code, timed_out) = match waited {
+        Ok(Ok(status)) => (status.code(), false),
+        Ok(Err(e)) => {
+            eprintln!("Failed to wait for build: {}", e);
+            (None, false)
+        }
+        Err(_) => {
+            if let Some(pid) = child.id() {
+                kill_process_tree(pid);
+            }
+            let _ = child.kill().await;
+            (None, true)
+        }
+    };
+
+    for reader in readers {
+        let _ = reader.await;
+    }
+
+    let duration_ms = started.elapsed().as_millis() as i64;
+    let success = !timed_out && exit_code == Some(0);
+    let (stream, summary) = if success {
+        ("stdout", format!("[PaaS] Build succeeded in {:.1}s", duration_ms as f64 / 1000.0))
+    } else if timed_out {
+        ("stderr", format!("[PaaS] Build timed out after {}s. Deploy aborted.", timeout_secs))
+    } else {
+        (
+            "stderr",
+            format!(
+                "[PaaS] Build failed with exit code {}. Deploy aborted.",
+                exit_code.map(|c| c.to_string()).unwrap_or("unknown".into())
+            ),
+        )
+    };
+    println!("{}", summary);
+    send_build_log(app_id, stream, summary).await;
+
+    HttpResponse::Ok().json(BuildResult {
+        success,
+        exit_code,
+        timed_out,
+        duration_ms,
+    })
+}
+
+/// Kill a process and everything it spawned
+fn kill_process_tree(pid: u32) {
+    #[cfg(target_os = "windows")]
+    let _ = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/F", "/T"])
+        .output();
+
+    // The process leads its own group, so signal the whole group
+    #[cfg(not(target_os = "windows"))]
+    let _ = std::process::Command::new("kill")
+        .args(["-9", &format!("-{}", pid)])
+        .output();
+}
+
 async fn stop_program(body: web::Json<serde_json::Value>) -> impl Responder {
     let pid = match body.get("pid").and_then(|p| p.as_i64()) {
         Some(p) => p as u32,
-        None => return HttpResponse::BadRequest().body("Missing pid"),
+        None => return ApiError::invalid_request("Missing pid").response(),
     };
 
     println!("Killing process with PID: {}", pid);
@@ -349,10 +766,17 @@ async fn stop_program(body: web::Json<serde_json::Value>) -> impl Responder {
         .args(["/PID", &pid.to_string(), "/F", "/T"])
         .output();
 
+    // App processes lead their own group; processes started before they did don't
     #[cfg(not(target_os = "windows"))]
-    let result = std::process::Command::new("kill")
-        .args(["-9", &pid.to_string()])
-        .output();
+    let result = match std::process::Command::new("kill")
+        .args(["-9", "--", &format!("-{}", pid)])
+        .output()
+    {
+        Ok(output) if !output.status.success() => {
+            std::process::Command::new("kill").args(["-9", &pid.to_string()]).output()
+        }
+        result => result,
+    };
 
     match result {
         Ok(_) => {
@@ -361,7 +785,70 @@ async fn stop_program(body: web::Json<serde_json::Value>) -> impl Responder {
         }
         Err(e) => {
             eprintln!("Failed to kill process {}: {}", pid, e);
-            HttpResponse::InternalServerError().finish()
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Send a signal (e.g. `HUP`) to a process so it can reload without restarting
+async fn signal_program(body: web::Json<serde_json::Value>) -> impl Responder {
+    let pid = match body.get("pid").and_then(|p| p.as_i64()) {
+        Some(p) => p as u32,
+        None => return ApiError::invalid_request("Missing pid").response(),
+    };
+    let signal = body.get("signal").and_then(|s| s.as_str()).unwrap_or("HUP");
+    if !signal.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return ApiError::invalid_request("Invalid signal").response();
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        eprintln!("Cannot send SIG{} to process {}: signals aren't supported on Windows", signal, pid);
+        ApiError::new(ErrorCode::NotImplemented, "Signals aren't supported on Windows").response()
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        println!("Sending SIG{} to process {}", signal, pid);
+        // The whole group, so the command gets it when it runs under a shell
+        let group = std::process::Command::new("kill")
+            .args([&format!("-{}", signal), "--", &format!("-{}", pid)])
+            .output();
+        let result = match group {
+            Ok(output) if !output.status.success() => std::process::Command::new("kill")
+                .args([&format!("-{}", signal), &pid.to_string()])
+                .output(),
+            result => result,
+        };
+        match result {
+            Ok(output) if output.status.success() => HttpResponse::Ok().finish(),
+            Ok(output) => {
+                eprintln!(
+                    "Failed to signal process {}: {}",
+                    pid,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                ApiError::internal().response()
+            }
+            Err(e) => {
+                eprintln!("Failed to signal process {}: {}", pid, e);
+                ApiError::internal().response()
+            }
+        }
+    }
+}
+
+/// Remove an app's extracted releases once it has been destroyed
+async fn delete_releases(path: web::Path<uuid::Uuid>) -> impl Responder {
+    let app_id = path.into_inner();
+    match remove_releases(app_id) {
+        Ok(_) => {
+            println!("Removed releases of {}", app_id);
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ApiError::internal().response()
         }
     }
 }
@@ -392,15 +879,26 @@ async fn check_status(path: web::Path<u32>) -> impl Responder {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let addr = ("127.0.0.1", 8001);
-    println!("app is bound to http://{}:{}", addr.0, addr.1);
+    let config = config::load().unwrap_or_else(|e| {
+        eprintln!("Startup: {}", e);
+        std::process::exit(1);
+    });
+    println!("app is bound to http://{}", config.listen);
     HttpServer::new(move || {
         App::new()
+            .wrap(error::RequestId)
+            .app_data(error::json_config())
+            .app_data(error::path_config())
+            .default_service(web::to(error::no_route))
             .route("/run", web::post().to(run_program))
+            .route("/runs", web::post().to(start_run))
+            .route("/build", web::post().to(build_program))
             .route("/stop", web::post().to(stop_program))
+            .route("/signal", web::post().to(signal_program))
+            .route("/releases/{app_id}", web::delete().to(delete_releases))
             .route("/status/{pid}", web::get().to(check_status))
     })
-    .bind(addr)?
+    .bind(config.listen.as_str())?
     .run()
     .await
 }
@@ -0,0 +1,109 @@
+#[cfg(target_os = "linux")]
+use std::collections::{HashMap, HashSet};
+
+/// Ports in LISTEN state owned by `root_pid` or any of its descendants, lowest first.
+/// Always empty on platforms without `/proc`.
+#[cfg(target_os = "linux")]
+pub fn listening_ports(root_pid: u32) -> Vec<u16> {
+    let inodes: HashSet<u64> = process_tree(root_pid)
+        .into_iter()
+        .flat_map(socket_inodes)
+        .collect();
+    if inodes.is_empty() {
+        return Vec::new();
+    }
+
+    let mut ports: Vec<u16> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|table| parse_listening(&table))
+        .filter(|(_, inode)| inodes.contains(inode))
+        .map(|(port, _)| port)
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listening_ports(_root_pid: u32) -> Vec<u16> {
+    Vec::new()
+}
+
+/// Whether ports can be read from `/proc` on this platform
+pub fn can_inspect_ports() -> bool {
+    cfg!(target_os = "linux")
+}
+
+/// `root_pid` and all of its descendants
+#[cfg(target_os = "linux")]
+fn process_tree(root_pid: u32) -> Vec<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<u32>().ok()) else {
+                continue;
+            };
+            if let Some(ppid) = parent_pid(pid) {
+                children.entry(ppid).or_default().push(pid);
+            }
+        }
+    }
+
+    let mut tree = vec![root_pid];
+    let mut i = 0;
+    while i < tree.len() {
+        if let Some(kids) = children.get(&tree[i]) {
+            tree.extend(kids);
+        }
+        i += 1;
+    }
+    tree
+}
+
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name is in parentheses and may contain spaces: "pid (comm) state ppid ..."
+    let rest = &stat[stat.rfind(')')? + 1..];
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Inodes of the sockets a process has open
+#[cfg(target_os = "linux")]
+fn socket_inodes(pid: u32) -> Vec<u64> {
+    let Ok(fds) = std::fs::read_dir(format!("/proc/{}/fd", pid)) else {
+        return Vec::new();
+    };
+    fds.flatten()
+        .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+        .filter_map(|target| {
+            let target = target.to_string_lossy().to_string();
+            target
+                .strip_prefix("socket:[")?
+                .strip_suffix(']')?
+                .parse()
+                .ok()
+        })
+        .collect()
+}
+
+/// (local port, inode) of every LISTEN socket in a `/proc/net/tcp{,6}` table
+#[cfg(target_os = "linux")]
+fn parse_listening(table: &str) -> Vec<(u16, u64)> {
+    const TCP_LISTEN: &str = "0A";
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(3) != Some(&TCP_LISTEN) {
+                return None;
+            }
+            let (_, port) = fields.get(1)?.rsplit_once(':')?;
+            let port = u16::from_str_radix(port, 16).ok()?;
+            let inode = fields.get(9)?.parse().ok()?;
+            Some((port, inode))
+        })
+        .collect()
+}
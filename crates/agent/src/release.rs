@@ -0,0 +1,72 @@
+use std::path::{Path, PathBuf};
+
+use shared::Application;
+use uuid::Uuid;
+
+/// Root directory releases are extracted into, one directory per app and release
+fn releases_root() -> PathBuf {
+    std::env::var("PAAS_RELEASES_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| std::env::temp_dir().join("paas").join("releases"))
+}
+
+/// Point the app's working directory at its release, downloading and extracting
+/// the release first if this agent hasn't done so yet. Apps without a release
+/// keep their `working_dir`.
+pub async fn prepare_release(app: &mut Application) -> Result<(), String> {
+    let (Some(app_id), Some(release_id)) = (app.id, app.release_id) else {
+        return Ok(());
+    };
+
+    let dir = releases_root().join(app_id.to_string()).join(release_id.to_string());
+    if !dir.exists() {
+        let source = download_source(app_id, release_id).await?;
+        let target = dir.clone();
+        tokio::task::spawn_blocking(move || extract_source(&source, &target))
+            .await
+            .map_err(|e| format!("Extracting release failed: {}", e))??;
+        println!("Extracted release {} of {} into {}", release_id, app.name, dir.display());
+    }
+
+    app.working_dir = dir.to_string_lossy().to_string();
+    Ok(())
+}
+
+/// Delete every release of the app this agent extracted
+pub fn remove_releases(app_id: Uuid) -> Result<(), String> {
+    let dir = releases_root().join(app_id.to_string());
+    if !dir.exists() {
+        return Ok(());
+    }
+    std::fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove {}: {}", dir.display(), e))
+}
+
+async fn download_source(app_id: Uuid, release_id: Uuid) -> Result<Vec<u8>, String> {
+    crate::paasd()
+        .release_source(app_id, release_id)
+        .await
+        .map_err(|e| format!("Failed to download release {}: {}", release_id, e))
+}
+
+/// Unpack into a scratch directory and move it into place, so a release
+/// directory only ever exists fully extracted
+fn extract_source(source: &[u8], dir: &Path) -> Result<(), String> {
+    let parent = dir.parent().expect("release dir has a parent");
+    std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+
+    let scratch = parent.join(format!(".extract-{}", Uuid::new_v4()));
+    let result = tar::Archive::new(flate2::read::GzDecoder::new(source))
+        .unpack(&scratch)
+        .map_err(|e| format!("Invalid source tarball: {}", e))
+        .and_then(|_| match std::fs::rename(&scratch, dir) {
+            Ok(_) => Ok(()),
+            // Another process of the same release got there first
+            Err(_) if dir.exists() => Ok(()),
+            Err(e) => Err(format!("Failed to move release into place: {}", e)),
+        });
+
+    if scratch.exists() {
+        let _ = std::fs::remove_dir_all(&scratch);
+    }
+    result
+}
@@ -0,0 +1,93 @@
+//! `/apps` and `/apps/{app_id}`
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use serde::Serialize;
+use shared::{
+    AppDetails, AppLiveStatus, AppSummary, Application, CreatedApp, DeletedApp, PatchApplication, RedeployApp,
+    Redeployed,
+};
+
+use crate::{Client, Error, segment};
+
+/// Query of `GET /apps`. Fields left unset use the server's defaults.
+#[derive(Serialize, Debug, Default, Clone)]
+pub struct ListApps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Comma-separated `key=value` pairs an app must all have
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// `name`, `status`, `port`, `created`, `deployed`, `started` or `restarts`; `-` for descending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// List destroyed apps that can still be restored instead
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+/// A page of `GET /apps`
+#[derive(Debug, Clone)]
+pub struct AppPage {
+    pub apps: Vec<AppSummary>,
+    /// Apps matching the filters across all pages
+    pub total: i64,
+}
+
+impl Client {
+    /// Register an app without starting it. A port of 0 asks paasd to allocate one.
+    pub async fn create_app(&self, app: &Application) -> Result<CreatedApp, Error> {
+        self.json(self.request(Method::POST, "/apps").json(app)).await
+    }
+
+    pub async fn list_apps(&self, query: &ListApps) -> Result<AppPage, Error> {
+        let res = self.send(self.request(Method::GET, "/apps").query(query)).await?;
+        let total = res
+            .headers()
+            .get("X-Total-Count")
+            .and_then(|t| t.to_str().ok())
+            .and_then(|t| t.parse().ok())
+            .unwrap_or(0);
+        let apps = res.json().await.map_err(Error::Decode)?;
+        Ok(AppPage { apps, total })
+    }
+
+    pub async fn app(&self, app: impl Display) -> Result<AppDetails, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}", segment(app)))).await
+    }
+
+    /// The app with the status of its web process according to the agent
+    pub async fn app_status(&self, app: impl Display) -> Result<AppLiveStatus, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}/status", segment(app)))).await
+    }
+
+    /// Update the app's record. Setting the status to STOPPED also stops its processes.
+    pub async fn patch_app(&self, app: impl Display, patch: &PatchApplication) -> Result<(), Error> {
+        self.empty(self.request(Method::PATCH, &format!("/apps/{}", segment(app))).json(patch))
+            .await
+    }
+
+    /// Apply config changes and start the app again, building first if it has a build
+    pub async fn redeploy(&self, app: impl Display, redeploy: &RedeployApp) -> Result<Redeployed, Error> {
+        self.json(self.request(Method::POST, &format!("/apps/{}/redeploy", segment(app))).json(redeploy))
+            .await
+    }
+
+    /// Stop and destroy the app. Unless purged, it can be restored until `purge_after`.
+    pub async fn delete_app(&self, app: impl Display, purge: bool) -> Result<DeletedApp, Error> {
+        let req = self
+            .request(Method::DELETE, &format!("/apps/{}", segment(app)))
+            .query(&[("purge", purge)]);
+        self.json(req).await
+    }
+
+    /// Bring back a destroyed app, stopped
+    pub async fn restore_app(&self, app: impl Display) -> Result<Application, Error> {
+        self.json(self.request(Method::POST, &format!("/apps/{}/restore", segment(app)))).await
+    }
+}
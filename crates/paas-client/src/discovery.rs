@@ -0,0 +1,13 @@
+//! `/discovery/{app_name}`
+
+use reqwest::Method;
+use shared::ServiceDiscovery;
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    /// Where to reach an app and its running web instances
+    pub async fn discover(&self, app_name: &str) -> Result<ServiceDiscovery, Error> {
+        self.json(self.request(Method::GET, &format!("/discovery/{}", segment(app_name)))).await
+    }
+}
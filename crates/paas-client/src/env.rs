@@ -0,0 +1,51 @@
+//! `/apps/{app_id}/env`. Every change is recorded as a release and, with `restart`,
+//! applied right away instead of when the app's processes next start.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use reqwest::Method;
+use shared::EnvUpdate;
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    pub async fn env(&self, app: impl Display) -> Result<BTreeMap<String, String>, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}/env", segment(app)))).await
+    }
+
+    /// Replace all of the app's env vars
+    pub async fn replace_env(
+        &self,
+        app: impl Display,
+        env: &BTreeMap<String, String>,
+        restart: bool,
+    ) -> Result<EnvUpdate, Error> {
+        let req = self
+            .request(Method::PUT, &format!("/apps/{}/env", segment(app)))
+            .query(&[("restart", restart)])
+            .json(env);
+        self.json(req).await
+    }
+
+    /// Set the given env vars, removing those whose value is None
+    pub async fn patch_env(
+        &self,
+        app: impl Display,
+        changes: &BTreeMap<String, Option<String>>,
+        restart: bool,
+    ) -> Result<EnvUpdate, Error> {
+        let req = self
+            .request(Method::PATCH, &format!("/apps/{}/env", segment(app)))
+            .query(&[("restart", restart)])
+            .json(changes);
+        self.json(req).await
+    }
+
+    pub async fn remove_env(&self, app: impl Display, keys: &[String], restart: bool) -> Result<EnvUpdate, Error> {
+        let req = self
+            .request(Method::DELETE, &format!("/apps/{}/env", segment(app)))
+            .query(&[("restart", restart.to_string()), ("keys", keys.join(","))]);
+        self.json(req).await
+    }
+}
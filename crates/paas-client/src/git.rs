@@ -0,0 +1,28 @@
+//! `/apps/{app_id}/git`
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use reqwest::{Method, Response};
+use shared::{GitPush, GitRepo};
+
+use crate::{Client, Error, segment};
+
+/// A git deploy answers once the pushed commit is built and started
+const DEPLOY_TIMEOUT: Duration = Duration::from_secs(3600);
+
+impl Client {
+    /// Create or repair the app's repository on the paasd host
+    pub async fn git_repo(&self, app: impl Display) -> Result<GitRepo, Error> {
+        self.json(self.request(Method::POST, &format!("/apps/{}/git", segment(app)))).await
+    }
+
+    /// Deploy a pushed commit. The response streams plain-text progress; read it with `Response::chunk`.
+    pub async fn git_deploy(&self, app: impl Display, push: &GitPush) -> Result<Response, Error> {
+        let req = self
+            .request(Method::POST, &format!("/apps/{}/git/deploy", segment(app)))
+            .timeout(DEPLOY_TIMEOUT)
+            .json(push);
+        self.send(req).await
+    }
+}
@@ -0,0 +1,268 @@
+//! Typed async client for the paasd API, used by the `paas` CLI and the agent.
+//!
+//! Methods take the app as an id or a name, like the `/apps/{app_id}/...` routes do.
+//! Failed requests come back as `Error::Api` carrying the server's `ApiError`.
+
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use shared::{ApiError, ErrorCode};
+
+mod apps;
+mod discovery;
+mod env;
+mod git;
+mod logs;
+mod processes;
+mod releases;
+mod runs;
+mod schedules;
+mod secrets;
+
+pub use apps::{AppPage, ListApps};
+
+/// Where paasd listens unless configured otherwise
+pub const DEFAULT_BASE_URL: &str = "http://127.0.0.1:8080";
+
+/// Header paasd tags every response with, and quotes in error bodies
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug)]
+pub enum Error {
+    /// paasd couldn't be reached, or didn't answer in time
+    Connect(reqwest::Error),
+    /// paasd answered with an error
+    Api(ApiError),
+    /// The response isn't what the endpoint returns, e.g. from an incompatible server
+    Decode(reqwest::Error),
+}
+
+impl Error {
+    /// The error paasd answered with
+    pub fn api(&self) -> Option<&ApiError> {
+        match self {
+            Error::Api(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.api().is_some_and(|e| e.code == ErrorCode::NotFound)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Connect(e) if e.is_timeout() => f.write_str("The server didn't answer in time"),
+            Error::Connect(e) => match e.url() {
+                Some(url) => write!(f, "Cannot connect to server at {}", url.origin().ascii_serialization()),
+                None => f.write_str("Cannot connect to server"),
+            },
+            Error::Api(error) => f.write_str(&error.message),
+            Error::Decode(e) => write!(f, "Unexpected response from server: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Connect(e) | Error::Decode(e) => Some(e),
+            Error::Api(error) => Some(error),
+        }
+    }
+}
+
+/// A paasd client. Cloning is cheap; clones share their connection pool.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::new()
+    }
+}
+
+impl Client {
+    /// A client for paasd on `DEFAULT_BASE_URL` with the default timeouts and retries
+    pub fn new() -> Self {
+        ClientBuilder::new().build()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let req = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    /// Send a request, turning error responses into `Error::Api`. Requests that never
+    /// reached paasd are retried; idempotent ones also after a timeout or a 502/503/504.
+    async fn send(&self, req: RequestBuilder) -> Result<Response, Error> {
+        let mut req = req.build().map_err(Error::Connect)?;
+        let idempotent = req.method().is_idempotent();
+        let mut attempt = 0;
+        loop {
+            let retry = if attempt < self.retries { req.try_clone() } else { None };
+            let result = self.http.execute(req).await;
+            let retryable = match &result {
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+                Ok(res) => {
+                    idempotent
+                        && matches!(
+                            res.status(),
+                            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                        )
+                }
+            };
+            match retry {
+                Some(next) if retryable => {
+                    tokio::time::sleep(self.retry_delay * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                    req = next;
+                }
+                _ => {
+                    let res = result.map_err(Error::Connect)?;
+                    if !res.status().is_success() {
+                        return Err(Error::Api(read_error(res).await));
+                    }
+                    return Ok(res);
+                }
+            }
+        }
+    }
+
+    async fn json<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, Error> {
+        self.send(req).await?.json().await.map_err(Error::Decode)
+    }
+
+    /// For endpoints whose successful responses carry nothing worth reading
+    async fn empty(&self, req: RequestBuilder) -> Result<(), Error> {
+        self.send(req).await.map(drop)
+    }
+}
+
+/// The `ApiError` of a failed response. Bodies from older servers or proxies that
+/// aren't one are turned into one from the status and text.
+async fn read_error(res: Response) -> ApiError {
+    let status = res.status();
+    let request_id = res
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .map(str::to_string);
+    let body = res.text().await.unwrap_or_default();
+
+    let mut error = serde_json::from_str::<ApiError>(&body).unwrap_or_else(|_| {
+        let message = match body.trim() {
+            "" => status.canonical_reason().unwrap_or("Request failed").to_string(),
+            text => text.to_string(),
+        };
+        ApiError::new(ErrorCode::from_status(status.as_u16()), message)
+    });
+    error.request_id = error.request_id.or(request_id);
+    error
+}
+
+/// An app id or name, process type or secret name as a path segment
+fn segment(value: impl fmt::Display) -> String {
+    urlencoding::encode(&value.to_string()).into_owned()
+}
+
+#[derive(Clone, Debug)]
+pub struct ClientBuilder {
+    base_url: String,
+    token: Option<String>,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retries: u32,
+    retry_delay: Duration,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder::new()
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        ClientBuilder {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            token: None,
+            timeout: Duration::from_secs(60),
+            connect_timeout: Duration::from_secs(5),
+            retries: 2,
+            retry_delay: Duration::from_millis(250),
+        }
+    }
+
+    /// paasd's address, e.g. `http://10.0.0.5:8080`
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Sent as a bearer token with every request
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Total time a request may take. Source uploads and git deploys allow longer.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// How often a failed request is sent again; 0 disables retries
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait before the first retry, doubled for each one after
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Panics like `reqwest::Client::new` if the HTTP client can't be initialized
+    pub fn build(self) -> Client {
+        let http = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .build()
+            .expect("failed to initialize the HTTP client");
+        Client {
+            http,
+            base_url: self.base_url,
+            token: self.token,
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+        }
+    }
+}
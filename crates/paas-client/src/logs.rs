@@ -0,0 +1,44 @@
+//! `/apps/{app_id}/logs`
+
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use shared::{AppLog, NewAppLog};
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    /// The last `limit` log lines, oldest first, of one process type or all of them
+    pub async fn logs(&self, app: impl Display, limit: i64, process: Option<&str>) -> Result<Vec<AppLog>, Error> {
+        let mut req = self
+            .request(Method::GET, &format!("/apps/{}/logs", segment(app)))
+            .query(&[("limit", limit)]);
+        if let Some(process) = process {
+            req = req.query(&[("process", process)]);
+        }
+        self.json(req).await
+    }
+
+    /// Log lines written after `since`, for following the logs
+    pub async fn logs_since(
+        &self,
+        app: impl Display,
+        since: DateTime<Utc>,
+        process: Option<&str>,
+    ) -> Result<Vec<AppLog>, Error> {
+        let mut req = self
+            .request(Method::GET, &format!("/apps/{}/logs", segment(app)))
+            .query(&[("since", since.to_rfc3339())]);
+        if let Some(process) = process {
+            req = req.query(&[("process", process)]);
+        }
+        self.json(req).await
+    }
+
+    /// Store a line of output, as the agent does for every process it runs
+    pub async fn send_log(&self, log: &NewAppLog) -> Result<(), Error> {
+        let path = format!("/apps/{}/logs", log.app_id);
+        self.empty(self.request(Method::POST, &path).json(log)).await
+    }
+}
@@ -0,0 +1,37 @@
+//! `/apps/{app_id}/processes`
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use shared::{AppProcess, PatchProcess, ScaleProcess, ScaledProcess};
+use uuid::Uuid;
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    pub async fn processes(&self, app: impl Display) -> Result<Vec<AppProcess>, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}/processes", segment(app)))).await
+    }
+
+    pub async fn process(&self, app: impl Display, process_id: Uuid) -> Result<AppProcess, Error> {
+        let path = format!("/apps/{}/processes/{}", segment(app), process_id);
+        self.json(self.request(Method::GET, &path)).await
+    }
+
+    /// Record a process instance's status or PID, as the agent does
+    pub async fn patch_process(&self, app: impl Display, process_id: Uuid, patch: &PatchProcess) -> Result<(), Error> {
+        let path = format!("/apps/{}/processes/{}", segment(app), process_id);
+        self.empty(self.request(Method::PATCH, &path).json(patch)).await
+    }
+
+    /// Run `scale` instances of a process type
+    pub async fn scale(&self, app: impl Display, process_type: &str, scale: i32) -> Result<ScaledProcess, Error> {
+        let path = format!("/apps/{}/processes/{}/scale", segment(app), segment(process_type));
+        self.json(self.request(Method::POST, &path).json(&ScaleProcess { scale })).await
+    }
+
+    pub async fn restart_processes(&self, app: impl Display, process_type: &str) -> Result<(), Error> {
+        let path = format!("/apps/{}/processes/{}/restart", segment(app), segment(process_type));
+        self.empty(self.request(Method::POST, &path)).await
+    }
+}
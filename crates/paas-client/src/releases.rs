@@ -0,0 +1,55 @@
+//! `/apps/{app_id}/releases`
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use reqwest::Method;
+use shared::{GitMetadata, PromoteRelease, Release};
+use uuid::Uuid;
+
+use crate::{Client, Error, segment};
+
+/// Uploads and downloads of source tarballs may take longer than other requests
+const SOURCE_TIMEOUT: Duration = Duration::from_secs(600);
+
+impl Client {
+    /// Upload a gzipped source tarball as a new release. It isn't started until a redeploy switches to it.
+    pub async fn upload_release(
+        &self,
+        app: impl Display,
+        source: Vec<u8>,
+        git: Option<&GitMetadata>,
+    ) -> Result<Release, Error> {
+        let mut req = self
+            .request(Method::POST, &format!("/apps/{}/releases", segment(app)))
+            .header("Content-Type", "application/gzip")
+            .timeout(SOURCE_TIMEOUT)
+            .body(source);
+        if let Some(git) = git {
+            req = req.query(git);
+        }
+        self.json(req).await
+    }
+
+    /// The app's releases, newest first
+    pub async fn releases(&self, app: impl Display, limit: Option<i64>) -> Result<Vec<Release>, Error> {
+        let mut req = self.request(Method::GET, &format!("/apps/{}/releases", segment(app)));
+        if let Some(limit) = limit {
+            req = req.query(&[("limit", limit)]);
+        }
+        self.json(req).await
+    }
+
+    /// Copy another app's release to this app
+    pub async fn promote_release(&self, app: impl Display, promote: &PromoteRelease) -> Result<Release, Error> {
+        let path = format!("/apps/{}/releases/promote", segment(app));
+        self.json(self.request(Method::POST, &path).json(promote)).await
+    }
+
+    /// The gzipped source tarball of a release
+    pub async fn release_source(&self, app: impl Display, release_id: Uuid) -> Result<Vec<u8>, Error> {
+        let path = format!("/apps/{}/releases/{}/source", segment(app), release_id);
+        let res = self.send(self.request(Method::GET, &path).timeout(SOURCE_TIMEOUT)).await?;
+        res.bytes().await.map(|b| b.to_vec()).map_err(Error::Decode)
+    }
+}
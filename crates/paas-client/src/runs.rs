@@ -0,0 +1,45 @@
+//! `/apps/{app_id}/runs`
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use shared::{AppLog, AppRun, FinishRun, NewRun};
+use uuid::Uuid;
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    /// Start a one-off command in the app's environment
+    pub async fn start_run(&self, app: impl Display, command: &str) -> Result<AppRun, Error> {
+        let path = format!("/apps/{}/runs", segment(app));
+        let body = NewRun {
+            command: command.to_string(),
+        };
+        self.json(self.request(Method::POST, &path).json(&body)).await
+    }
+
+    pub async fn runs(&self, app: impl Display, limit: Option<i64>) -> Result<Vec<AppRun>, Error> {
+        let mut req = self.request(Method::GET, &format!("/apps/{}/runs", segment(app)));
+        if let Some(limit) = limit {
+            req = req.query(&[("limit", limit)]);
+        }
+        self.json(req).await
+    }
+
+    pub async fn run(&self, app: impl Display, run_id: Uuid) -> Result<AppRun, Error> {
+        let path = format!("/apps/{}/runs/{}", segment(app), run_id);
+        self.json(self.request(Method::GET, &path)).await
+    }
+
+    /// Record how a run ended, as the agent does
+    pub async fn finish_run(&self, app: impl Display, run_id: Uuid, finish: &FinishRun) -> Result<(), Error> {
+        let path = format!("/apps/{}/runs/{}", segment(app), run_id);
+        self.empty(self.request(Method::PATCH, &path).json(finish)).await
+    }
+
+    /// The run's output after log line `after`; 0 for all of it
+    pub async fn run_logs(&self, app: impl Display, run_id: Uuid, after: i64) -> Result<Vec<AppLog>, Error> {
+        let path = format!("/apps/{}/runs/{}/logs", segment(app), run_id);
+        self.json(self.request(Method::GET, &path).query(&[("after", after)])).await
+    }
+}
@@ -0,0 +1,41 @@
+//! `/apps/{app_id}/schedules`
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use shared::{AppRun, NewSchedule, Schedule};
+use uuid::Uuid;
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    pub async fn schedules(&self, app: impl Display) -> Result<Vec<Schedule>, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}/schedules", segment(app)))).await
+    }
+
+    /// Replace the app's cron schedules
+    pub async fn set_schedules(&self, app: impl Display, schedules: &[NewSchedule]) -> Result<Vec<Schedule>, Error> {
+        let path = format!("/apps/{}/schedules", segment(app));
+        self.json(self.request(Method::PUT, &path).json(schedules)).await
+    }
+
+    /// Start a schedule's command now, outside its schedule
+    pub async fn run_schedule(&self, app: impl Display, schedule_id: Uuid) -> Result<AppRun, Error> {
+        let path = format!("/apps/{}/schedules/{}/run", segment(app), schedule_id);
+        self.json(self.request(Method::POST, &path)).await
+    }
+
+    /// The schedule's past runs, newest first
+    pub async fn schedule_runs(
+        &self,
+        app: impl Display,
+        schedule_id: Uuid,
+        limit: Option<i64>,
+    ) -> Result<Vec<AppRun>, Error> {
+        let mut req = self.request(Method::GET, &format!("/apps/{}/schedules/{}/runs", segment(app), schedule_id));
+        if let Some(limit) = limit {
+            req = req.query(&[("limit", limit)]);
+        }
+        self.json(req).await
+    }
+}
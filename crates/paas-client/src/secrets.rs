@@ -0,0 +1,27 @@
+//! `/apps/{app_id}/secrets`. Values can be set but never read back.
+
+use std::fmt::Display;
+
+use reqwest::Method;
+use shared::{SecretInfo, SetSecret};
+
+use crate::{Client, Error, segment};
+
+impl Client {
+    pub async fn secrets(&self, app: impl Display) -> Result<Vec<SecretInfo>, Error> {
+        self.json(self.request(Method::GET, &format!("/apps/{}/secrets", segment(app)))).await
+    }
+
+    pub async fn set_secret(&self, app: impl Display, name: &str, value: &str) -> Result<SecretInfo, Error> {
+        let path = format!("/apps/{}/secrets/{}", segment(app), segment(name));
+        let body = SetSecret {
+            value: value.to_string(),
+        };
+        self.json(self.request(Method::PUT, &path).json(&body)).await
+    }
+
+    pub async fn remove_secret(&self, app: impl Display, name: &str) -> Result<(), Error> {
+        let path = format!("/apps/{}/secrets/{}", segment(app), segment(name));
+        self.empty(self.request(Method::DELETE, &path)).await
+    }
+}
@@ -2,6 +2,18 @@ use clap::{Parser, Subcommand};
 
 #[derive(Debug, Parser)]
 pub struct Cli {
+    /// Use an [environments.<name>] section of paas.toml, e.g. staging
+    #[arg(long = "env", global = true, value_name = "ENVIRONMENT")]
+    pub environment: Option<String>,
+    /// Act on the deployed app with this name instead of the one in paas.toml
+    #[arg(long, global = true, value_name = "NAME", conflicts_with = "environment")]
+    pub app: Option<String>,
+    /// Print the result as JSON, and errors as `{"error": ...}`
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Talk to the server of this context instead of the current one
+    #[arg(long, global = true, value_name = "NAME")]
+    pub context: Option<String>,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -9,25 +21,231 @@ pub struct Cli {
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     Init,
-    Deploy,
-    Redeploy,
+    Deploy {
+        /// Deploy this commit, branch or tag from git history instead of the working tree
+        #[arg(long = "ref")]
+        git_ref: Option<String>,
+    },
+    Redeploy {
+        /// Deploy this commit, branch or tag from git history instead of the working tree
+        #[arg(long = "ref")]
+        git_ref: Option<String>,
+    },
     Status,
+    /// List all deployed apps
+    Apps {
+        /// Only apps with this status, e.g. crashed
+        #[arg(long)]
+        status: Option<String>,
+        /// Only apps with this label, e.g. team=payments. Repeat to require several.
+        #[arg(long = "label", value_name = "KEY=VALUE")]
+        labels: Vec<String>,
+        #[arg(long, default_value = "name", value_parser = ["name", "status", "port", "created", "deployed", "started", "restarts"])]
+        sort: String,
+        /// Sort in descending order
+        #[arg(long)]
+        desc: bool,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+        #[arg(long, default_value_t = 0)]
+        offset: i64,
+        /// List destroyed apps that can still be restored
+        #[arg(long)]
+        deleted: bool,
+        #[command(subcommand)]
+        action: Option<AppsAction>,
+    },
+    /// List deployed source releases
+    Releases,
+    /// Deploy the release running in one environment to another, e.g. `paas promote staging production`
+    Promote { from: String, to: String },
+    /// Add a git remote that deploys on `git push`
+    #[command(name = "git:remote")]
+    GitRemote {
+        /// Name of the remote to add
+        #[arg(short, long, default_value = "paas")]
+        remote: String,
+        /// Use an ssh:// URL on the server's host instead of a local file path
+        #[arg(long)]
+        ssh: bool,
+    },
     Logs {
         #[arg(short, long)]
         follow: bool,
+        /// Only show logs from this process type (e.g. worker)
+        #[arg(short, long)]
+        process: Option<String>,
+    },
+    /// List, scale and restart process types
+    Ps {
+        #[command(subcommand)]
+        action: Option<PsAction>,
     },
     Stop,
+    /// Stop the app and delete it from the server
+    Destroy {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Delete immediately and for good instead of keeping it restorable
+        #[arg(long)]
+        purge: bool,
+    },
+    /// Run a one-off command (e.g. migrations) with the app's env and working directory
+    Run {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
     Env {
         #[command(subcommand)]
         action: EnvAction,
     },
+    /// Manage encrypted secrets, exposed to the app as env vars but never stored in paas.toml
+    Secrets {
+        #[command(subcommand)]
+        action: SecretsAction,
+    },
+    /// Manage scheduled jobs declared with [[cron]] in paas.toml
+    Cron {
+        #[command(subcommand)]
+        action: CronAction,
+    },
+    /// Deploy the apps of paas-stack.toml together, in dependency order
+    Stack {
+        #[command(subcommand)]
+        action: StackAction,
+    },
+    /// Switch between paasd servers, e.g. a local one, staging and production
+    Context {
+        #[command(subcommand)]
+        action: ContextAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AppsAction {
+    /// Bring back a destroyed app before it's purged
+    Restore { name: String },
+    /// Permanently delete a destroyed app now, freeing its name
+    Purge {
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
 pub enum EnvAction {
+    Set {
+        key_value: String,
+        /// Set the var on the server instead of in paas.toml
+        #[arg(long)]
+        remote: bool,
+        /// Restart the app so the change applies now (with --remote or --app)
+        #[arg(long)]
+        restart: bool,
+    },
+    List,
+    Remove {
+        key: String,
+        /// Remove the var on the server instead of from paas.toml
+        #[arg(long)]
+        remote: bool,
+        /// Restart the app so the change applies now (with --remote or --app)
+        #[arg(long)]
+        restart: bool,
+    },
+    /// Download the server's env vars into .env
+    Pull {
+        /// Write them to the [env] section of paas.toml instead
+        #[arg(long)]
+        toml: bool,
+    },
+    /// Add the vars of a .env file to the [env] section of paas.toml
+    Import {
+        #[arg(default_value = ".env")]
+        file: String,
+    },
+    /// Print the [env] section of paas.toml in .env format
+    Export,
+    /// Replace the server's env vars with the [env] section of paas.toml
+    Push {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Restart the app so the change applies now
+        #[arg(long)]
+        restart: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretsAction {
+    /// `KEY=VALUE`, or `KEY` alone to read the value from stdin
     Set { key_value: String },
     List,
-    Remove { key: String },
+    Rm { key: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PsAction {
+    /// Set instance counts, e.g. `paas ps scale worker=2`
+    Scale { assignments: Vec<String> },
+    /// Restart one process type, or all of them
+    Restart { process_type: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CronAction {
+    List,
+    /// Trigger a job immediately
+    RunNow { name: String },
+    /// Show recent runs of a job
+    History { name: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum StackAction {
+    /// Deploy every app, waiting for each one's dependencies to be healthy
+    Up,
+    /// Stop every app in reverse dependency order
+    Down,
+    Status,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ContextAction {
+    /// Save a server under a name, or update it
+    Add {
+        name: String,
+        /// paasd's URL, e.g. http://10.0.0.5:8080
+        #[arg(long)]
+        server: String,
+        /// Sent as a bearer token with every request
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Make commands talk to this context's server
+    Use { name: String },
+    List,
+}
+
+impl Commands {
+    /// Whether the command works on a deployed app without paas.toml, so `--app` applies
+    pub fn accepts_app(&self) -> bool {
+        match self {
+            Commands::Init
+            | Commands::Apps { .. }
+            | Commands::Deploy { .. }
+            | Commands::Promote { .. }
+            | Commands::GitRemote { .. }
+            | Commands::Stack { .. }
+            | Commands::Context { .. } => false,
+            Commands::Env { action } => !matches!(action, EnvAction::Import { .. } | EnvAction::Export),
+            _ => true,
+        }
+    }
 }
 
 pub fn parse_cli() -> Cli {
@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use paas_client::ListApps;
+use shared::AppSummary;
+
+use crate::config::{PaasConfig, client, save_app_id, selected_environment};
+use crate::errors::{Failure, fail, failed};
+use crate::output::{ask, json, print_json, say};
+
+/// `3d4h`, `2h15m`, `5m` or `40s`
+fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds().max(0);
+    let (days, hours, mins) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{}d{}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h{}m", hours, mins)
+    } else if mins > 0 {
+        format!("{}m", mins)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+fn ago(time: Option<DateTime<Utc>>) -> String {
+    match time {
+        Some(time) => format!("{} ago", format_duration(Utc::now() - time)),
+        None => "-".to_string(),
+    }
+}
+
+/// Uptime of an app whose web process is alive
+fn uptime(app: &AppSummary) -> String {
+    match app.stats.started_at {
+        Some(started) if app.live_status == "RUNNING" => format_duration(Utc::now() - started),
+        _ => "-".to_string(),
+    }
+}
+
+pub struct AppListOptions {
+    pub status: Option<String>,
+    pub labels: Vec<String>,
+    pub sort: String,
+    pub desc: bool,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+pub async fn list_apps(options: AppListOptions) -> anyhow::Result<()> {
+    let sort = if options.desc {
+        format!("-{}", options.sort)
+    } else {
+        options.sort
+    };
+    let query = ListApps {
+        status: options.status,
+        label: (!options.labels.is_empty()).then(|| options.labels.join(",")),
+        sort: Some(sort),
+        limit: Some(options.limit),
+        offset: Some(options.offset),
+        deleted: false,
+    };
+
+    let page = client().list_apps(&query).await.map_err(|e| failed("Failed to list apps", e))?;
+    let (apps, total) = (page.apps, page.total);
+
+    if json() {
+        return print_json(&apps);
+    }
+
+    if apps.is_empty() {
+        println!("No apps found.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:<9} {:<9} {:<6} {:<8} {:<8} DEPLOYED",
+        "NAME", "STATUS", "LIVE", "PORT", "UPTIME", "RESTARTS"
+    );
+    for summary in &apps {
+        let status = serde_json::to_value(&summary.app.status).unwrap_or_default();
+        println!(
+            "{:<24} {:<9} {:<9} {:<6} {:<8} {:<8} {}",
+            summary.app.name,
+            status.as_str().unwrap_or("UNKNOWN"),
+            summary.live_status,
+            summary.app.port,
+            uptime(summary),
+            summary.stats.restart_count,
+            ago(summary.stats.deployed_at),
+        );
+    }
+
+    let shown_to = options.offset + apps.len() as i64;
+    if options.offset > 0 || shown_to < total {
+        println!();
+        println!("Showing {}-{} of {} apps", options.offset + 1, shown_to, total);
+        if shown_to < total {
+            println!("Use --offset {} for the next page.", shown_to);
+        }
+    }
+    Ok(())
+}
+
+/// List destroyed apps that can still be restored
+pub async fn list_deleted_apps() -> anyhow::Result<()> {
+    let query = ListApps {
+        sort: Some("name".to_string()),
+        limit: Some(500),
+        deleted: true,
+        ..Default::default()
+    };
+    let apps = client().list_apps(&query).await.map_err(|e| failed("Failed to list apps", e))?.apps;
+
+    if json() {
+        return print_json(&apps);
+    }
+    if apps.is_empty() {
+        println!("No destroyed apps.");
+        return Ok(());
+    }
+
+    println!("{:<24} {:<6} DESTROYED", "NAME", "PORT");
+    for summary in &apps {
+        println!(
+            "{:<24} {:<6} {}",
+            summary.app.name,
+            summary.app.port,
+            ago(summary.stats.deleted_at)
+        );
+    }
+    println!();
+    println!("Restore one with `paas apps restore <name>`.");
+    Ok(())
+}
+
+/// Bring back a destroyed app, stopped, and record its id in paas.toml if this is its project
+pub async fn restore_app(name: String) -> anyhow::Result<()> {
+    let app = match client().restore_app(&name).await {
+        Ok(app) => app,
+        Err(e) if e.is_not_found() => {
+            return Err(fail(
+                Failure::NotDeployed,
+                format!("No destroyed app named '{}'. It may have been purged already.", name),
+            ));
+        }
+        Err(e) => return Err(failed(&format!("Failed to restore {}", name), e)),
+    };
+    say!("Restored {} on port {}.", app.name, app.port);
+
+    let filename = Path::new("paas.toml");
+    if let Some(app_id) = app.id
+        && filename.exists()
+        && let Ok(config) = PaasConfig::parse(&std::fs::read_to_string(filename)?)
+        && config.name == app.name
+        && config.id.is_none()
+    {
+        save_app_id(filename, selected_environment(), app_id)?;
+        say!("Saved its id to paas.toml.");
+    }
+    say!("It's stopped; start it with `paas redeploy --app {}`.", app.name);
+    if json() {
+        print_json(&app)?;
+    }
+    Ok(())
+}
+
+/// Permanently delete a destroyed app before its restore window ends, freeing its name
+pub async fn purge_app(name: String, yes: bool) -> anyhow::Result<()> {
+    match client().app(&name).await {
+        Ok(_) => {
+            return Err(fail(
+                Failure::Error,
+                format!("{} isn't destroyed. Use `paas destroy --app {} --purge` to delete it.", name, name),
+            ));
+        }
+        Err(e) if e.is_not_found() => {}
+        Err(e) => return Err(failed(&format!("Failed to purge {}", name), e)),
+    }
+
+    if !yes {
+        let answer = ask(&format!("Permanently delete {}? It can't be restored afterwards. [y/N] ", name))?;
+        if !matches!(answer.as_str(), "y" | "Y" | "yes") {
+            return Err(fail(Failure::Error, "Aborted."));
+        }
+    }
+
+    let deleted = client()
+        .delete_app(&name, true)
+        .await
+        .map_err(|e| failed(&format!("Failed to purge {}", name), e))?;
+    if json() {
+        return print_json(&deleted);
+    }
+    println!("Purged {}.", name);
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! `paas context`: named paasd servers in `~/.config/paas/config.toml`
+
+use anyhow::Ok;
+
+use crate::config::{ContextConfig, UserConfig};
+use crate::errors::{Failure, fail};
+use crate::output::{json, print_json};
+
+pub fn add_context(name: String, server: String, token: Option<String>) -> anyhow::Result<()> {
+    let url = reqwest::Url::parse(&server).map_err(|e| anyhow::anyhow!("Invalid server URL '{}': {}", server, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        anyhow::bail!("Invalid server URL '{}': expected http:// or https://", server);
+    }
+
+    let mut user_config = UserConfig::load()?;
+    let context = ContextConfig {
+        server: server.trim_end_matches('/').to_string(),
+        token,
+    };
+    let updated = user_config.contexts.insert(name.clone(), context.clone()).is_some();
+    let path = user_config.save()?;
+
+    if json() {
+        return print_json(&serde_json::json!({ "name": name, "server": context.server, "updated": updated }));
+    }
+    if updated {
+        println!("Updated context '{}' in {}", name, path.display());
+    } else {
+        println!("Added context '{}' to {}", name, path.display());
+    }
+    if user_config.current_context.as_deref() != Some(name.as_str()) {
+        println!("Switch to it with `paas context use {}`, or pass --context {}.", name, name);
+    }
+    Ok(())
+}
+
+pub fn use_context(name: String) -> anyhow::Result<()> {
+    let mut user_config = UserConfig::load()?;
+    let Some(context) = user_config.contexts.get(&name).cloned() else {
+        return Err(fail(
+            Failure::Error,
+            format!("No context named '{}'. Add it with `paas context add {} --server <url>`.", name, name),
+        ));
+    };
+    user_config.current_context = Some(name.clone());
+    user_config.save()?;
+    if json() {
+        return print_json(&serde_json::json!({ "name": name, "server": context.server }));
+    }
+    println!("Switched to context '{}' ({})", name, context.server);
+    Ok(())
+}
+
+/// List the contexts, marking the one commands use with `*`
+pub fn list_contexts(selected: Option<String>) -> anyhow::Result<()> {
+    let user_config = UserConfig::load()?;
+    let contexts = user_config.contexts.clone();
+    let active = user_config.select(selected).ok().and_then(|server| server.context);
+
+    if json() {
+        let contexts: Vec<_> = contexts
+            .iter()
+            .map(|(name, context)| {
+                serde_json::json!({
+                    "name": name,
+                    "server": context.server,
+                    "token": context.token.is_some(),
+                    "active": active.as_deref() == Some(name.as_str()),
+                })
+            })
+            .collect();
+        return print_json(&contexts);
+    }
+    if contexts.is_empty() {
+        println!("No contexts. Add one with `paas context add <name> --server <url>`.");
+        return Ok(());
+    }
+    println!("  {:<16} {:<40} TOKEN", "NAME", "SERVER");
+    for (name, context) in &contexts {
+        let marker = if active.as_deref() == Some(name.as_str()) { "*" } else { " " };
+        let token = if context.token.is_some() { "yes" } else { "no" };
+        println!("{} {:<16} {:<40} {}", marker, name, context.server, token);
+    }
+    if active.is_none()
+        && let Some(server) = std::env::var("PAAS_SERVER").ok().filter(|s| !s.is_empty())
+    {
+        println!();
+        println!("PAAS_SERVER is set, so commands use {} instead.", server);
+    }
+    Ok(())
+}
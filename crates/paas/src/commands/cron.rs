@@ -0,0 +1,112 @@
+use shared::{RunStatus, Schedule};
+use uuid::Uuid;
+
+use crate::config::{client, target_app_id};
+use crate::errors::failed;
+use crate::output::{json, print_json};
+
+async fn fetch_schedules(app_id: Uuid) -> anyhow::Result<Vec<Schedule>> {
+    client()
+        .schedules(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch cron jobs", e))
+}
+
+async fn find_schedule(app_id: Uuid, name: &str) -> anyhow::Result<Schedule> {
+    fetch_schedules(app_id)
+        .await?
+        .into_iter()
+        .find(|s| s.name == name || s.id.to_string() == name)
+        .ok_or_else(|| anyhow::anyhow!("No cron job named '{}'. See `paas cron list`.", name))
+}
+
+fn format_time(time: Option<chrono::DateTime<chrono::Utc>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or("-".into())
+}
+
+pub async fn list_schedules() -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let schedules = fetch_schedules(app_id).await?;
+    if json() {
+        return print_json(&schedules);
+    }
+    if schedules.is_empty() {
+        println!("No cron jobs. Add a [[cron]] section to paas.toml and redeploy.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<16} {:<20} {:<20} {:<10} COMMAND",
+        "NAME", "SCHEDULE", "LAST RUN (UTC)", "NEXT RUN (UTC)", "LAST EXIT"
+    );
+    for schedule in &schedules {
+        let last_exit = match (&schedule.last_status, schedule.last_exit_code) {
+            (Some(RunStatus::RUNNING), _) => "running".to_string(),
+            (_, Some(code)) => code.to_string(),
+            _ => "-".to_string(),
+        };
+        println!(
+            "{:<20} {:<16} {:<20} {:<20} {:<10} {}",
+            schedule.name,
+            schedule.schedule,
+            format_time(schedule.last_run_at),
+            format_time(schedule.next_run_at),
+            last_exit,
+            schedule.command,
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn run_schedule_now(name: String) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let schedule = find_schedule(app_id, &name).await?;
+    let run = client()
+        .run_schedule(app_id, schedule.id)
+        .await
+        .map_err(|e| failed(&format!("Failed to run '{}'", schedule.name), e))?;
+    if json() {
+        return print_json(&run);
+    }
+    println!("Started '{}' (run {})", schedule.name, run.id);
+    println!("Check the result with `paas cron history {}`", schedule.name);
+
+    Ok(())
+}
+
+pub async fn schedule_history(name: String) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let schedule = find_schedule(app_id, &name).await?;
+    let runs = client()
+        .schedule_runs(app_id, schedule.id, None)
+        .await
+        .map_err(|e| failed(&format!("Failed to fetch the history of '{}'", schedule.name), e))?;
+    if json() {
+        return print_json(&runs);
+    }
+    if runs.is_empty() {
+        println!("'{}' has not run yet.", schedule.name);
+        return Ok(());
+    }
+
+    println!("{:<20} {:<10} {:<6} {:<10} RUN ID", "STARTED (UTC)", "STATUS", "EXIT", "DURATION");
+    for run in &runs {
+        println!(
+            "{:<20} {:<10} {:<6} {:<10} {}",
+            format_time(Some(run.created_at)),
+            format!("{:?}", run.status),
+            run.exit_code.map(|c| c.to_string()).unwrap_or("-".into()),
+            run.duration_ms
+                .map(|ms| format!("{:.1}s", ms as f64 / 1000.0))
+                .unwrap_or("-".into()),
+            run.id,
+        );
+    }
+
+    Ok(())
+}
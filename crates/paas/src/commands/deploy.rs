@@ -1,121 +1,163 @@
-use std::{
-    fs::{self},
-    io::Write,
-    path::Path,
-};
+use std::{collections::BTreeMap, path::Path};
 
-use reqwest::Client;
-use serde::Deserialize;
-use shared::Application;
+use anyhow::Context;
+use shared::{Application, RedeployApp, WEB_PROCESS};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
-pub struct PaasConfig {
-    pub name: String,
-    pub runtime: String,
-    pub command: String,
-    pub port: Option<i32>,
-    pub id: Option<Uuid>,
-    pub env: Option<std::collections::HashMap<String, String>>,
+use crate::commands::logs::follow_build_logs;
+use crate::commands::ps::apply_replicas;
+use crate::commands::redeploy::report_started;
+use crate::commands::releases::describe_release;
+use crate::config::{
+    PaasConfig, client, require_paas_toml, save_app_id, selected_environment, sync_schedules, with_environment,
+};
+use crate::errors::{Failure, fail, failed_as};
+use crate::output::{json, print_json, say};
+use crate::source::{prepare_source, upload_source};
+
+/// Register the app with paasd without starting it and return its id
+pub async fn create_app(config: &PaasConfig, formation: &BTreeMap<String, String>) -> anyhow::Result<Uuid> {
+    let fixed_port = config.fixed_port();
+    let request_payload = Application {
+        name: config.name.clone(),
+        command: formation[WEB_PROCESS].clone(),
+        // 0: paasd allocates a free port
+        port: fixed_port.unwrap_or(0),
+        status: shared::AppStatus::PENDING,
+        id: None,
+        // Empty: the app runs from the uploaded source, not from a directory on the host
+        working_dir: String::new(),
+        pid: None,
+        env_vars: config
+            .env
+            .as_ref()
+            .map(|e| serde_json::to_value(e).unwrap_or(serde_json::json!({}))),
+        processes: Some(serde_json::to_value(formation)?),
+        build_command: config.build.clone(),
+        build_timeout: config.build_timeout,
+        release_id: None,
+        links: config.links.as_ref().map(|l| serde_json::json!(l)),
+        on_link_change: config.on_link_change.clone(),
+        labels: config.labels.as_ref().map(|l| serde_json::json!(l)),
+    };
+
+    let created = client()
+        .create_app(&request_payload)
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Deployment failed", e))?;
+    if fixed_port.is_none() {
+        say!("Assigned port {}", created.port);
+    }
+    Ok(created.id)
 }
 
-pub async fn deploy_project() -> anyhow::Result<()> {
-    let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        println!("Initialize the project first. use 'paas init' for that.");
-        return Ok(());
+/// Package `dir`, register the app and start its first release. Returns the new app's id
+/// once its build succeeded and its processes were started. The id is saved to `dir`'s paas.toml
+/// as soon as the app is registered, so a failed deploy is retried with `paas redeploy`.
+pub async fn deploy_new(dir: &Path, app_data: &PaasConfig, git_ref: Option<&str>) -> anyhow::Result<Uuid> {
+    let formation = app_data
+        .process_formation_in(dir)
+        .map_err(|e| fail(Failure::DeployFailed, format!("{:#}", e)))?;
+    let command = formation[WEB_PROCESS].clone();
+    let schedules = app_data.schedules();
+
+    say!(
+        "Deploying: {} ({}) using {}",
+        app_data.name, app_data.runtime, command
+    );
+    for (process_type, process_command) in formation.iter().filter(|(t, _)| *t != WEB_PROCESS) {
+        say!("  {}: {}", process_type, process_command);
+    }
+    if let Some(build) = &app_data.build {
+        say!("  build: {}", build);
+    }
+
+    let (archive, git) =
+        prepare_source(dir, git_ref).map_err(|e| fail(Failure::DeployFailed, format!("{:#}", e)))?;
+    say!(
+        "Packaged {} files ({:.1} KB)",
+        archive.files,
+        archive.bytes.len() as f64 / 1024.0
+    );
+
+    let deploy_started = chrono::Utc::now();
+
+    let application_id = create_app(app_data, &formation).await?;
+    save_app_id(&dir.join("paas.toml"), selected_environment(), application_id)?;
+    let release = upload_source(application_id, &archive, git.as_ref()).await?;
+    say!("Uploaded release {}", describe_release(&release));
+
+    let redeploy = RedeployApp {
+        release_id: Some(release.id),
+        ..Default::default()
+    };
+    let deployed = client()
+        .redeploy(application_id, &redeploy)
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Deployment failed", e))?;
+
+    say!("Project Successfully deployed");
+
+    if deployed.building {
+        say!("Building...");
+        if !follow_build_logs(&application_id, deploy_started).await? {
+            return Err(fail(
+                Failure::DeployFailed,
+                format!("Build failed, deploy aborted. Fix the build and run `{}`.", with_environment("paas redeploy")),
+            ));
+        }
     }
 
+    if let Some(replicas) = &app_data.replicas {
+        apply_replicas(application_id, replicas).await;
+    }
+    if !schedules.is_empty() {
+        match sync_schedules(application_id, &schedules).await {
+            Result::Ok(_) => say!("Registered {} cron job(s)", schedules.len()),
+            Result::Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(application_id)
+}
+
+pub async fn deploy_project(git_ref: Option<String>) -> anyhow::Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+
     // let _file = File::open(filename)?;
 
     //read the wholefile into string
     let content = std::fs::read_to_string(filename)?;
 
     //map/ deserialize it directly into out struct
-    let app_data: PaasConfig = match toml::from_str(&content) {
-        Result::Ok(data) => data,
-        Result::Err(e) => {
-            eprintln!("Failed to parse paas.toml: {}", e);
-            eprintln!("Check for duplicate keys in your [env] section.");
-            return Ok(());
-        }
-    };
+    let app_data = PaasConfig::parse(&content).map_err(|e| {
+        fail(
+            Failure::Error,
+            format!("Failed to parse paas.toml: {}\nCheck for duplicate keys in your [env] section.", e),
+        )
+    })?;
 
     if let Some(existing_id) = app_data.id {
-        println!("Project already deployed (id: {}).", existing_id);
-        println!("  - To restart it, use `paas redeploy`.");
-        println!("  - To stop it, use `paas stop`.");
-        println!("  - To deploy as a brand new app, remove the `id` line from paas.toml.");
-        return Ok(());
+        return Err(fail(
+            Failure::Error,
+            format!(
+                "Project already deployed (id: {}).\n  - To restart it, use `{}`.\n  - To stop it, use `{}`.\n  \
+                 - To deploy as a brand new app, remove the `id` line from paas.toml.",
+                existing_id,
+                with_environment("paas redeploy"),
+                with_environment("paas stop")
+            ),
+        ));
     }
 
-    println!("Deploying: {} using {}", app_data.name, app_data.command);
+    let application_id = deploy_new(&std::env::current_dir()?, &app_data, git_ref.as_deref()).await?;
 
-    let current_dir = std::env::current_dir()?
-        .to_string_lossy()
-        .to_string();
+    let status = report_started(application_id)
+        .await
+        .with_context(|| format!("Note: Once fixed, start it again with `{}`.", with_environment("paas redeploy")))?;
 
-    let request_payload = Application {
-        name: app_data.name,
-        command: app_data.command,
-        port: app_data.port.unwrap_or(3000),
-        status: shared::AppStatus::PENDING,
-        id: None,
-        working_dir: current_dir,
-        pid: None,
-        env_vars: app_data.env.map(|e| serde_json::to_value(e).unwrap_or(serde_json::json!({}))),
-    };
-
-    let client = Client::new();
-    let url = "http://127.0.0.1:8080/apps";
-
-    let res = client.post(url).json(&request_payload).send().await?;
-
-    if res.status().is_success() {
-        let body: serde_json::Value = res.json().await?;
-        let application_id: Uuid = body["id"].as_str().unwrap_or_default().parse()?;
-
-        println!("Project Successfully deployed");
-        println!("Starting application...");
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-        let client2 = Client::new();
-        let status_url = format!("http://127.0.0.1:8080/apps/{}/status", application_id);
-        if let Ok(status_res) = client2.get(&status_url).send().await {
-            if let Ok(status_body) = status_res.json::<serde_json::Value>().await {
-                let status = status_body["status"].as_str().unwrap_or("UNKNOWN");
-                if status == "STOPPED" || status == "CRASHED" {
-                    eprintln!("Application failed to start! Check logs with `paas logs`");
-                    eprintln!("Note: App ID not saved to paas.toml since it failed to start.");
-                } else {
-                    // Only write id to paas.toml if app actually started
-                    // Insert id BEFORE the [env] section to avoid it being parsed as an env var
-                    let content = fs::read_to_string("paas.toml")?;
-                    let new_content = if content.contains("[env]") {
-                        content.replace("[env]", &format!("id = \"{}\"\n\n[env]", application_id))
-                    } else {
-                        format!("{}\nid = \"{}\"\n", content, application_id)
-                    };
-                    fs::write("paas.toml", new_content)?;
-
-                    let port = status_body["port"].as_i64().unwrap_or(0);
-                    if port > 0 {
-                        println!("Application is running on port {}", port);
-                        println!("Local: http://localhost:{}", port);
-                    } else {
-                        println!("Application is running. Port not yet detected.");
-                        println!("Check `paas logs` for the actual port.");
-                    }
-                }
-            }
-        }
-    } else if res.status() == reqwest::StatusCode::CONFLICT {
-        let body = res.text().await.unwrap_or_default();
-        eprintln!("Deployment failed: {}", body);
-        eprintln!("Tip: Change the port in paas.toml and try again.");
-    } else {
-        eprintln!("Deployment failed with status: {}", res.status());
+    if json() {
+        print_json(&status)?;
     }
-
     Ok(())
 }
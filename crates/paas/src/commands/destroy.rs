@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use crate::config::{client, remove_app_id, selected_app, selected_environment, target_app};
+use crate::errors::{Failure, fail, failed};
+use crate::output::{ask, json, print_json, say};
+
+/// Stop the app and delete it from the server. Unless purged, it can be brought back
+/// with `paas apps restore` during the restore window.
+pub async fn destroy_app(yes: bool, purge: bool) -> anyhow::Result<()> {
+    let app = target_app().await?;
+
+    if !yes {
+        if purge {
+            say!("This stops {} and permanently deletes it with its releases, logs and schedules.", app.name);
+        } else {
+            say!("This stops {} and deletes it. It can be restored for a while with `paas apps restore`.", app.name);
+        }
+        if ask("Type the app name to confirm: ")? != app.name {
+            return Err(fail(Failure::Error, "Aborted."));
+        }
+    }
+
+    let deleted = client()
+        .delete_app(app.id, purge)
+        .await
+        .map_err(|e| failed(&format!("Failed to destroy {}", app.name), e))?;
+
+    if purge {
+        say!("Destroyed {} permanently.", app.name);
+    } else {
+        say!("Destroyed {}.", app.name);
+        if let Some(purge_after) = deleted.purge_after {
+            say!(
+                "Restore it with `paas apps restore {}` until {}.",
+                app.name,
+                purge_after.format("%Y-%m-%d %H:%M UTC")
+            );
+        }
+    }
+
+    // The id in paas.toml now points nowhere; a new deploy starts from scratch
+    let filename = Path::new("paas.toml");
+    if selected_app().is_none() && filename.exists() {
+        remove_app_id(filename, selected_environment(), app.id)?;
+    }
+    if json() {
+        print_json(&deleted)?;
+    }
+    Ok(())
+}
@@ -1,86 +1,291 @@
 use anyhow::Result;
+use shared::EnvUpdate;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use uuid::Uuid;
+
+use crate::commands::releases::describe_release;
+use crate::config::{PaasConfig, client, require_paas_toml, selected_environment, target_app_id};
+use crate::dotenv;
+use crate::errors::{Failure, fail, failed};
+use crate::output::{ask, json, print_json, say};
+
+const INVALID_KEY_VALUE: &str = "Invalid format. Use KEY=VALUE";
+
+/// The [env] table local env commands edit: `[environments.<name>.env]` with `--env <name>`
+fn env_table(doc: &mut toml::Value) -> Result<&mut toml::map::Map<String, toml::Value>> {
+    let mut table = doc
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("paas.toml is not a table"))?;
+    if let Some(environment) = selected_environment() {
+        table = table
+            .get_mut("environments")
+            .and_then(|e| e.get_mut(environment))
+            .and_then(|e| e.as_table_mut())
+            .ok_or_else(|| anyhow::anyhow!("No [environments.{}] section in paas.toml", environment))?;
+    }
+    table
+        .entry("env")
+        .or_insert(toml::Value::Table(toml::map::Map::new()))
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("`env` in paas.toml is not a table"))
+}
 
 pub fn env_set(key_value: String) -> Result<()> {
     let parts: Vec<&str> = key_value.splitn(2, '=').collect();
     if parts.len() != 2 {
-        eprintln!("Invalid format. Use KEY=VALUE");
-        return Ok(());
+        return Err(fail(Failure::Error, INVALID_KEY_VALUE));
     }
     let key = parts[0].trim().to_string();
     let value = parts[1].trim().to_string();
 
     let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        eprintln!("No paas.toml found. Run `paas init` first.");
-        return Ok(());
-    }
+    require_paas_toml(filename)?;
 
     let content = fs::read_to_string(filename)?;
     let mut doc: toml::Value = toml::from_str(&content)?;
 
-    if let Some(table) = doc.as_table_mut() {
-        let env_table = table
-            .entry("env")
-            .or_insert(toml::Value::Table(toml::map::Map::new()));
-        if let Some(env_map) = env_table.as_table_mut() {
-            env_map.insert(key.clone(), toml::Value::String(value));
-        }
-    }
+    env_table(&mut doc)?.insert(key.clone(), toml::Value::String(value.clone()));
 
     fs::write(filename, toml::to_string_pretty(&doc)?)?;
+    if json() {
+        return print_json(&serde_json::json!({ "key": key, "value": value }));
+    }
     println!("Set env var: {}", key);
     Ok(())
 }
 
 pub fn env_list() -> Result<()> {
     let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        eprintln!("No paas.toml found. Run `paas init` first.");
-        return Ok(());
-    }
+    require_paas_toml(filename)?;
 
-    let content = fs::read_to_string(filename)?;
-    let doc: toml::Value = toml::from_str(&content)?;
-
-    if let Some(env_table) = doc.get("env").and_then(|e| e.as_table()) {
-        if env_table.is_empty() {
-            println!("No environment variables set.");
-        } else {
-            println!("Environment variables:");
-            for (key, val) in env_table {
-                println!("  {} = {}", key, val.as_str().unwrap_or(""));
-            }
-        }
-    } else {
+    // With `--env`, the environment's vars merged over the top-level ones
+    let config = PaasConfig::parse(&fs::read_to_string(filename)?)?;
+    let env: BTreeMap<String, String> = config.env.unwrap_or_default().into_iter().collect();
+    print_env(&env)
+}
+
+/// The env vars set on the server, for `paas env list --app`
+pub async fn env_list_remote() -> Result<()> {
+    let app_id = target_app_id().await?;
+    let env = fetch_remote_env(app_id).await?;
+    print_env(&env)
+}
+
+fn print_env(env: &BTreeMap<String, String>) -> Result<()> {
+    if json() {
+        return print_json(env);
+    }
+    if env.is_empty() {
         println!("No environment variables set.");
+    } else {
+        println!("Environment variables:");
+        for (key, val) in env {
+            println!("  {} = {}", key, val);
+        }
     }
     Ok(())
 }
 
 pub fn env_remove(key: String) -> Result<()> {
     let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        eprintln!("No paas.toml found. Run `paas init` first.");
-        return Ok(());
-    }
+    require_paas_toml(filename)?;
 
     let content = fs::read_to_string(filename)?;
     let mut doc: toml::Value = toml::from_str(&content)?;
 
-    let mut removed = false;
-    if let Some(table) = doc.as_table_mut() {
-        if let Some(env_table) = table.get_mut("env").and_then(|e| e.as_table_mut()) {
-            removed = env_table.remove(&key).is_some();
-        }
+    let removed = env_table(&mut doc)?.remove(&key).is_some();
+
+    if !removed {
+        return Err(fail(Failure::Error, format!("Env var '{}' not found.", key)));
+    }
+    fs::write(filename, toml::to_string_pretty(&doc)?)?;
+    if json() {
+        return print_json(&serde_json::json!({ "key": key, "removed": true }));
+    }
+    println!("Removed env var: {}", key);
+    Ok(())
+}
+
+/// What happened after the server accepted an env change
+fn report_update(update: &EnvUpdate) {
+    if let Some(release) = &update.release {
+        say!("Created release {}", describe_release(release));
     }
+    if update.building {
+        say!("Rebuilding, follow progress with `paas logs`.");
+    } else if update.restarted {
+        say!("Restarted application.");
+    } else {
+        say!("Run `paas redeploy`, or pass --restart, to apply the change.");
+    }
+}
+
+/// Report the outcome of an env change, or fail if the server refused it
+fn applied(result: std::result::Result<EnvUpdate, paas_client::Error>) -> Result<EnvUpdate> {
+    let update = result.map_err(|e| failed("Failed to update env vars", e))?;
+    report_update(&update);
+    Ok(update)
+}
+
+/// Finish an env change with a line for people, or the update as JSON
+fn finish_update(update: &EnvUpdate, message: String) -> Result<()> {
+    if json() {
+        return print_json(update);
+    }
+    println!("{}", message);
+    Ok(())
+}
+
+async fn fetch_remote_env(app_id: Uuid) -> Result<BTreeMap<String, String>> {
+    client()
+        .env(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch env vars", e))
+}
 
-    if removed {
+/// Set an env var on the server only; paas.toml is left alone
+pub async fn env_set_remote(key_value: String, restart: bool) -> Result<()> {
+    let Some((key, value)) = key_value.split_once('=') else {
+        return Err(fail(Failure::Error, INVALID_KEY_VALUE));
+    };
+    let app_id = target_app_id().await?;
+
+    let key = key.trim().to_string();
+    let change = BTreeMap::from([(key.clone(), Some(value.trim().to_string()))]);
+    let update = applied(client().patch_env(app_id, &change, restart).await)?;
+    finish_update(&update, format!("Set remote env var: {}", key))
+}
+
+pub async fn env_remove_remote(key: String, restart: bool) -> Result<()> {
+    let app_id = target_app_id().await?;
+
+    if !fetch_remote_env(app_id).await?.contains_key(&key) {
+        return Err(fail(Failure::Error, format!("Env var '{}' not found on the server.", key)));
+    }
+
+    let update = applied(client().remove_env(app_id, std::slice::from_ref(&key), restart).await)?;
+    finish_update(&update, format!("Removed remote env var: {}", key))
+}
+
+/// Write the server's env vars to `.env`, or to the [env] table of paas.toml
+pub async fn env_pull(toml_target: bool) -> Result<()> {
+    let app_id = target_app_id().await?;
+    let env = fetch_remote_env(app_id).await?;
+
+    let filename = if toml_target {
+        let filename = "paas.toml";
+        let mut doc: toml::Value = toml::from_str(&fs::read_to_string(filename)?)?;
+        let table = env_table(&mut doc)?;
+        table.clear();
+        table.extend(
+            env.iter()
+                .map(|(key, val)| (key.clone(), toml::Value::String(val.clone()))),
+        );
         fs::write(filename, toml::to_string_pretty(&doc)?)?;
-        println!("Removed env var: {}", key);
+        say!("Wrote {} env var(s) to the [env] section of {}", env.len(), filename);
+        filename
     } else {
-        eprintln!("Env var '{}' not found.", key);
+        let filename = ".env";
+        let mut content = dotenv::format(&env);
+        if content.is_empty() {
+            content.push('\n');
+        }
+        fs::write(filename, content)?;
+        say!("Wrote {} env var(s) to {}", env.len(), filename);
+
+        let ignored = fs::read_to_string(".gitignore")
+            .map(|g| g.lines().any(|l| matches!(l.trim(), ".env" | "/.env")))
+            .unwrap_or(false);
+        if !ignored {
+            say!("Add .env to .gitignore to keep it out of git.");
+        }
+        filename
+    };
+
+    if json() {
+        print_json(&serde_json::json!({ "file": filename, "env": env }))?;
+    }
+    Ok(())
+}
+
+/// Print the changes that turn `remote` into `local`. Returns whether there are any.
+fn print_env_diff(remote: &BTreeMap<String, String>, local: &BTreeMap<String, String>) -> bool {
+    let mut changed = false;
+    for (key, val) in local {
+        match remote.get(key) {
+            None => say!("  + {}={}", key, val),
+            Some(old) if old != val => say!("  ~ {}: {} -> {}", key, old, val),
+            Some(_) => continue,
+        }
+        changed = true;
+    }
+    for key in remote.keys().filter(|key| !local.contains_key(*key)) {
+        say!("  - {}", key);
+        changed = true;
+    }
+    changed
+}
+
+/// Replace the server's env vars with the [env] section of paas.toml, after showing what changes
+pub async fn env_push(yes: bool, restart: bool) -> Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+    let config = PaasConfig::parse(&fs::read_to_string(filename)?)?;
+    let app_id = target_app_id().await?;
+    let local: BTreeMap<String, String> = config.env.unwrap_or_default().into_iter().collect();
+
+    let remote = fetch_remote_env(app_id).await?;
+
+    say!("Changes to the server's env vars:");
+    if !print_env_diff(&remote, &local) {
+        say!("  (none)");
+        if json() {
+            print_json(&serde_json::json!({ "changed": false, "env": remote }))?;
+        }
+        return Ok(());
+    }
+
+    if !yes && !matches!(ask("Apply these changes? [y/N] ")?.as_str(), "y" | "Y" | "yes") {
+        return Err(fail(Failure::Error, "Aborted."));
+    }
+
+    let update = applied(client().replace_env(app_id, &local, restart).await)?;
+    finish_update(&update, format!("Pushed {} env var(s).", local.len()))
+}
+
+/// Merge the vars of a `.env` file into the [env] section of paas.toml
+pub fn env_import(file: String) -> Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+    let content =
+        fs::read_to_string(&file).map_err(|e| fail(Failure::Error, format!("Cannot read {}: {}", file, e)))?;
+    let vars = dotenv::parse(&content).map_err(|e| fail(Failure::Error, format!("{:#}", e)))?;
+
+    let mut doc: toml::Value = toml::from_str(&fs::read_to_string(filename)?)?;
+    let table = env_table(&mut doc)?;
+    for (key, value) in &vars {
+        table.insert(key.clone(), toml::Value::String(value.clone()));
+    }
+
+    fs::write(filename, toml::to_string_pretty(&doc)?)?;
+    if json() {
+        let imported: BTreeMap<&String, &String> = vars.iter().map(|(key, value)| (key, value)).collect();
+        return print_json(&imported);
+    }
+    println!("Imported {} env var(s) from {} into {}", vars.len(), file, filename);
+    Ok(())
+}
+
+/// Print the [env] section of paas.toml in `.env` format
+pub fn env_export() -> Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+    let config = PaasConfig::parse(&fs::read_to_string(filename)?)?;
+    let env: BTreeMap<String, String> = config.env.unwrap_or_default().into_iter().collect();
+    if json() {
+        return print_json(&env);
     }
+    print!("{}", dotenv::format(&env));
     Ok(())
 }
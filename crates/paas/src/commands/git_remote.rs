@@ -0,0 +1,57 @@
+use std::{fs::read_to_string, process::Command};
+
+use crate::config::{PaasConfig, client, require_paas_toml, server_host, with_environment};
+use crate::errors::{Failure, fail, failed};
+use crate::output::{json, print_json};
+
+/// Point a git remote at the app's repository on the paasd host, so `git push <remote> main` deploys
+pub async fn add_git_remote(remote: String, ssh: bool) -> anyhow::Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+
+    let app_data = PaasConfig::parse(&read_to_string(filename)?)?;
+    let Some(app_id) = app_data.id else {
+        return Err(fail(
+            Failure::NotDeployed,
+            format!("Project not deployed.\nRun `{}`", with_environment("paas deploy")),
+        ));
+    };
+
+    let is_repo = Command::new("git")
+        .args(["rev-parse", "--git-dir"])
+        .output()
+        .is_ok_and(|o| o.status.success());
+    if !is_repo {
+        return Err(fail(Failure::Error, "Not a git repository. Run `git init` first."));
+    }
+
+    let repo = client()
+        .git_repo(app_id)
+        .await
+        .map_err(|e| failed("Failed to set up the app's git repository", e))?;
+    let remote_url = if ssh {
+        format!("ssh://{}{}", server_host(), repo.path)
+    } else {
+        repo.path
+    };
+
+    let exists = Command::new("git")
+        .args(["remote", "get-url", &remote])
+        .output()
+        .is_ok_and(|o| o.status.success());
+    let action = if exists { "set-url" } else { "add" };
+    let status = Command::new("git")
+        .args(["remote", action, &remote, &remote_url])
+        .status()?;
+    if !status.success() {
+        return Err(fail(Failure::Error, format!("Failed to configure git remote '{}'", remote)));
+    }
+
+    if json() {
+        return print_json(&serde_json::json!({ "remote": remote, "url": remote_url }));
+    }
+    println!("Git remote '{}' -> {}", remote, remote_url);
+    println!("Deploy with `git push {} main`", remote);
+
+    Ok(())
+}
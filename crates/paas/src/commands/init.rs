@@ -1,15 +1,20 @@
 use std::{
+    collections::BTreeMap,
     fs::{self},
-    io::{self, Write},
     path::{Path, PathBuf},
 };
 
+use crate::dotenv;
+use crate::errors::{Failure, fail};
+use crate::output::{ask, json, print_json, say};
+
+/// A TOML string literal, escaped as needed
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
 fn prompt_command(runtime: &str) -> String {
-    print!("Could not auto-detect entry point for {}. Enter the run command: ", runtime);
-    io::stdout().flush().unwrap();
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_string()
+    ask(&format!("Could not auto-detect entry point for {}. Enter the run command: ", runtime)).unwrap()
 }
 
 fn detect_go_command() -> String {
@@ -36,7 +41,7 @@ fn detect_go_command() -> String {
 }
 
 pub fn init_project() -> anyhow::Result<()> {
-    println!("initializing a project");
+    say!("initializing a project");
 
     let runtime = if Path::new("package.json").exists() {
         "node"
@@ -51,8 +56,7 @@ pub fn init_project() -> anyhow::Result<()> {
     };
 
     if runtime == "unknown" {
-        println!("⚠ Could not detect project type. Please create a paas.toml manually");
-        return Ok(());
+        return Err(fail(Failure::Error, "⚠ Could not detect project type. Please create a paas.toml manually"));
     }
 
     let current_dir: PathBuf = std::env::current_dir()?;
@@ -63,61 +67,96 @@ pub fn init_project() -> anyhow::Result<()> {
         .to_string();
 
     let command = match runtime {
-        "node" => "npm run dev".to_string(),
-        "rust" => "cargo run".to_string(),
+        "node" => "npm start".to_string(),
+        "rust" => "cargo run --release".to_string(),
         "python" => "python main.py".to_string(),
         "go" => detect_go_command(),
         _ => prompt_command(runtime),
     };
 
+    let build = match runtime {
+        "node" => Some("npm ci && npm run build".to_string()),
+        "rust" => Some("cargo build --release".to_string()),
+        "python" if Path::new("requirements.txt").exists() => {
+            Some("pip install -r requirements.txt".to_string())
+        }
+        "go" => Some("go build ./...".to_string()),
+        _ => None,
+    };
+
     // Auto-import env vars from .env file if it exists
-    let env_section = if Path::new(".env").exists() {
-        let env_content = fs::read_to_string(".env").unwrap_or_default();
-        let mut env_lines = String::from("[env]\n");
-        for line in env_content.lines() {
-            let line = line.trim();
-            // Skip empty lines and comments
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            // Only process valid KEY=VALUE lines
-            if let Some((key, value)) = line.split_once('=') {
-                let key = key.trim();
-                let value = value.trim();
-                // Escape any quotes in the value
-                let value = value.trim_matches('"').trim_matches('\'');
-                env_lines.push_str(&format!("{} = \"{}\"\n", key, value));
+    let imported = if Path::new(".env").exists() {
+        match dotenv::parse(&fs::read_to_string(".env")?) {
+            Ok(vars) => Some(vars),
+            Err(e) => {
+                say!("⚠ Skipped importing .env: {}", e);
+                None
             }
         }
-        println!("✔ Imported env vars from .env file");
-        env_lines
     } else {
-        "[env]\n# DB_HOST = \"localhost\"\n# DB_PORT = \"5432\"\n".to_string()
+        None
+    };
+    let env_section = match imported {
+        Some(vars) => {
+            let mut env_lines = String::from("[env]\n");
+            for (key, value) in vars.into_iter().collect::<BTreeMap<_, _>>() {
+                env_lines.push_str(&format!("{} = {}\n", key, toml_string(&value)));
+            }
+            say!("✔ Imported env vars from .env file");
+            env_lines
+        }
+        None => "[env]\n# DB_HOST = \"localhost\"\n# DB_PORT = \"5432\"\n# DATABASE_URL = \"postgres://${DB_HOST}:${DB_PORT}/app\"\n".to_string(),
     };
 
+    let build_line = build
+        .as_ref()
+        .map(|b| format!("build = {}\n", toml_string(b)))
+        .unwrap_or_default();
     let config_content = format!(
-        "name = \"{}\"\nruntime = \"{}\"\ncommand = \"{}\"\nport = 3000\n\n{}\n",
-        folder_name, runtime, command, env_section
+        "name = {}\nruntime = \"{}\"\n{}command = {}\nport = \"auto\"\n\n{}\n",
+        toml_string(&folder_name),
+        runtime,
+        build_line,
+        toml_string(&command),
+        env_section
     );
 
-    if Path::new("paas.toml").exists() {
-        println!("⚠ paas.toml already exists, skipping creation.");
-    } else {
+    let created = !Path::new("paas.toml").exists();
+    if created {
         fs::write("paas.toml", config_content)?;
-        println!("✔ Created paas.toml");
+        say!("✔ Created paas.toml");
+    } else {
+        say!("⚠ paas.toml already exists, skipping creation.");
+    }
+
+    if json() {
+        return print_json(&serde_json::json!({
+            "created": created,
+            "name": folder_name,
+            "runtime": runtime,
+            "build": build,
+            "command": command,
+            "procfile": Path::new("Procfile").exists(),
+        }));
     }
 
     println!("✔ Paas project initialized");
     println!("Project name: {}", folder_name);
     println!("Runtime: {}", runtime);
+    if let Some(build) = &build {
+        println!("Build: {}", build);
+    }
     println!("Command: {}", command);
+    if Path::new("Procfile").exists() {
+        println!("✔ Found Procfile, its process types will be deployed alongside `web`");
+    }
     println!();
-    println!("Note: Make sure your app listens on the port specified in paas.toml.");
+    println!("Note: Your app gets its port in the PORT environment variable. Make sure it listens on it.");
     match runtime {
-        "node" => println!("  For Next.js/Vite: set port in your package.json script, e.g: \"next dev --port 3000\""),
-        "go" => println!("  For Go: make sure your app listens on the port in paas.toml, e.g: http.ListenAndServe(\":3000\", nil)"),
-        "python" => println!("  For Python: make sure your app listens on the port in paas.toml, e.g: app.run(port=3000)"),
-        "rust" => println!("  For Rust: make sure your app listens on the port in paas.toml"),
+        "node" => println!("  For Node: e.g. app.listen(process.env.PORT). Next.js reads PORT automatically."),
+        "go" => println!("  For Go: e.g. http.ListenAndServe(\":\"+os.Getenv(\"PORT\"), nil)"),
+        "python" => println!("  For Python: e.g. app.run(port=int(os.environ[\"PORT\"]))"),
+        "rust" => println!("  For Rust: e.g. bind to format!(\"0.0.0.0:{{}}\", std::env::var(\"PORT\")?)"),
         _ => {}
     }
 
@@ -1,111 +1,126 @@
-use std::path::Path;
-
 use anyhow::Ok;
-use reqwest::Client;
-use serde::Deserialize;
+use chrono::SecondsFormat;
+use shared::{AppLog, AppStatus, BUILD_PROCESS};
 use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
-struct PaasConfig {
-    pub id: Option<Uuid>,
-    pub name: Option<String>,
-}
+use crate::config::{client, target_app};
+use crate::errors::failed;
+use crate::output::{json, print_json, say};
 
-#[derive(Debug, Deserialize)]
-struct AppLog {
-    pub stream: String,
-    pub message: String,
-    pub created_at: String,
-}
-
-pub async fn show_logs(follow: bool) -> anyhow::Result<()> {
-    let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        println!("Initialize the project first. Use 'paas init' for that.");
-        return Ok(());
-    }
-
-    let content = std::fs::read_to_string(filename)?;
-    let app_data: PaasConfig = toml::from_str(&content)?;
-
-    let app_id = match app_data.id {
-        Some(id) => id,
-        None => {
-            println!("App not deployed yet. Use 'paas deploy' first.");
-            return Ok(());
-        }
-    };
-
-    let app_name = app_data.name.unwrap_or_else(|| "app".to_string());
-    println!("Fetching logs for {}...", app_name);
+/// Print the app's recent logs. With `--json` they're printed as an array, or with `--follow`
+/// as one JSON object per line.
+pub async fn show_logs(follow: bool, process: Option<String>) -> anyhow::Result<()> {
+    let app = target_app().await?;
+    let (app_id, app_name) = (app.id, app.name);
+    say!("Fetching logs for {}...", app_name);
 
     if follow {
         // Start by fetching existing logs
-        let initial_logs = fetch_logs(&app_id, 100).await.unwrap_or_default();
+        let initial_logs = fetch_logs(&app_id, 100, process.as_deref()).await?;
         let mut last_timestamp = if initial_logs.is_empty() {
-            chrono::Utc::now().to_rfc3339()
+            chrono::Utc::now()
         } else {
             for log in &initial_logs {
                 print_log(log);
             }
-            initial_logs.last().unwrap().created_at.clone()
+            initial_logs.last().unwrap().created_at
         };
 
         // Poll for new logs using since timestamp
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-            match fetch_logs_since(&app_id, &last_timestamp).await {
+            match fetch_logs_since(&app_id, last_timestamp, process.as_deref()).await {
                 Result::Ok(logs) => {
                     if !logs.is_empty() {
                         for log in &logs {
                             print_log(log);
                         }
-                        last_timestamp = logs.last().unwrap().created_at.clone();
+                        last_timestamp = logs.last().unwrap().created_at;
                     }
                 }
                 Err(e) => eprintln!("Error fetching logs: {}", e),
             }
         }
     } else {
-        match fetch_logs(&app_id, 100).await {
-            Result::Ok(logs) => {
-                if logs.is_empty() {
-                    println!("No logs yet.");
-                } else {
-                    for log in &logs {
-                        print_log(log);
-                    }
-                }
+        let logs = fetch_logs(&app_id, 100, process.as_deref()).await?;
+        if json() {
+            print_json(&logs)?;
+        } else if logs.is_empty() {
+            println!("No logs yet.");
+        } else {
+            for log in &logs {
+                print_log(log);
             }
-            Err(e) => eprintln!("Error fetching logs: {}", e),
         }
     }
 
     Ok(())
 }
 
-async fn fetch_logs(app_id: &Uuid, limit: i64) -> anyhow::Result<Vec<AppLog>> {
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}/logs?limit={}", app_id, limit);
-    let res = client.get(&url).send().await?;
-    let logs: Vec<AppLog> = res.json().await?;
-    Ok(logs)
+/// Stream build output while the app is BUILDING. Returns whether the build succeeded.
+pub async fn follow_build_logs(app_id: &Uuid, since: chrono::DateTime<chrono::Utc>) -> anyhow::Result<bool> {
+    let mut last_timestamp = since;
+    let mut succeeded = false;
+
+    loop {
+        // Check the status before fetching logs so the build summary line is never missed
+        let app = client()
+            .app(app_id)
+            .await
+            .map_err(|e| failed("Failed to fetch the build status", e))?
+            .app;
+        let logs = fetch_logs_since(app_id, last_timestamp, Some(BUILD_PROCESS)).await?;
+        for log in &logs {
+            if log.stream == "stderr" {
+                eprintln!("{}", log.message);
+            } else {
+                say!("{}", log.message);
+            }
+            if log.message.starts_with("[PaaS] Build succeeded") {
+                succeeded = true;
+            }
+        }
+        if let Some(last) = logs.last() {
+            last_timestamp = last.created_at;
+        }
+
+        if !matches!(app.status, AppStatus::BUILDING) {
+            return Ok(succeeded);
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn fetch_logs(app_id: &Uuid, limit: i64, process: Option<&str>) -> anyhow::Result<Vec<AppLog>> {
+    client()
+        .logs(app_id, limit, process)
+        .await
+        .map_err(|e| failed("Failed to fetch logs", e))
 }
 
-async fn fetch_logs_since(app_id: &Uuid, since: &str) -> anyhow::Result<Vec<AppLog>> {
-    let client = Client::new();
-    let since_encoded = urlencoding::encode(since);
-    let url = format!("http://127.0.0.1:8080/apps/{}/logs?since={}", app_id, since_encoded);
-    let res = client.get(&url).send().await?;
-    let logs: Vec<AppLog> = res.json().await?;
-    Ok(logs)
+async fn fetch_logs_since(
+    app_id: &Uuid,
+    since: chrono::DateTime<chrono::Utc>,
+    process: Option<&str>,
+) -> anyhow::Result<Vec<AppLog>> {
+    Ok(client().logs_since(app_id, since, process).await?)
 }
 
 fn print_log(log: &AppLog) {
+    if json() {
+        println!("{}", serde_json::to_string(log).unwrap_or_default());
+        return;
+    }
     let prefix = if log.stream == "stderr" {
         "[ERR]"
     } else {
         "[OUT]"
     };
-    println!("{} {} {}", log.created_at, prefix, log.message);
+    println!(
+        "{} {} [{}] {}",
+        log.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        prefix,
+        log.process_type,
+        log.message
+    );
 }
@@ -1,7 +1,18 @@
+pub mod apps;
+pub mod context;
+pub mod cron;
 pub mod deploy;
+pub mod destroy;
 pub mod env_cmd;
+pub mod git_remote;
 pub mod init;
 pub mod logs;
+pub mod promote;
+pub mod ps;
 pub mod redeploy;
+pub mod releases;
+pub mod run;
+pub mod secrets;
+pub mod stack;
 pub mod status;
 pub mod stop;
@@ -0,0 +1,69 @@
+use std::{fs::read_to_string, path::Path};
+
+use anyhow::Context;
+use shared::PromoteRelease;
+
+use crate::commands::deploy::create_app;
+use crate::commands::redeploy::{redeploy_release, report_started};
+use crate::commands::releases::describe_release;
+use crate::config::{PaasConfig, client, require_paas_toml, save_app_id};
+use crate::errors::{Failure, fail, failed_as};
+use crate::output::{json, print_json, say};
+
+/// Deploy the release `from` currently runs to `to`, with `to`'s own config.
+/// The target environment's app is created if it hasn't been deployed yet.
+pub async fn promote(from: String, to: String) -> anyhow::Result<()> {
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+
+    let content = read_to_string(filename)?;
+    let (source, target) = match (
+        toml::from_str::<PaasConfig>(&content)?.in_environment(&from),
+        toml::from_str::<PaasConfig>(&content)?.in_environment(&to),
+    ) {
+        (Ok(source), Ok(target)) => (source, target),
+        (Err(e), _) | (_, Err(e)) => return Err(e),
+    };
+
+    let Some(from_app_id) = source.id else {
+        return Err(fail(
+            Failure::NotDeployed,
+            format!("{} isn't deployed yet. Run `paas deploy --env {}` first.", from, from),
+        ));
+    };
+    let formation = target
+        .process_formation()
+        .map_err(|e| fail(Failure::DeployFailed, format!("{:#}", e)))?;
+
+    let (app_id, created) = match target.id {
+        Some(app_id) => (app_id, false),
+        None => {
+            say!("Creating {}", target.name);
+            (create_app(&target, &formation).await?, true)
+        }
+    };
+
+    let promote = PromoteRelease {
+        from_app_id,
+        release_id: None,
+    };
+    let release = client()
+        .promote_release(app_id, &promote)
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Promotion failed", e))?;
+    say!("Promoting {} to {} as {}", from, to, describe_release(&release));
+
+    let redeployed = redeploy_release(app_id, &target, &formation, release.id).await;
+    if created {
+        redeployed.context("Note: App ID not saved to paas.toml since the deploy failed.")?;
+        save_app_id(Path::new(filename), Some(&to), app_id)?;
+    } else {
+        redeployed?;
+    }
+    let status = report_started(app_id).await?;
+
+    if json() {
+        print_json(&status)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use shared::AppProcess;
+use uuid::Uuid;
+
+use crate::config::{client, target_app_id};
+use crate::errors::{Failure, fail, failed, report_failure};
+use crate::output::{json, print_json, say};
+
+async fn fetch_processes(app_id: Uuid) -> anyhow::Result<Vec<AppProcess>> {
+    client()
+        .processes(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch processes", e))
+}
+
+pub async fn list_processes() -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let processes = fetch_processes(app_id).await?;
+
+    if json() {
+        return print_json(&processes);
+    }
+    if processes.is_empty() {
+        println!("No processes.");
+        return Ok(());
+    }
+
+    println!("{:<16} {:<10} {:<8} COMMAND", "PROCESS", "STATUS", "PID");
+    for process in &processes {
+        println!(
+            "{:<16} {:<10} {:<8} {}",
+            format!("{}.{}", process.process_type, process.instance),
+            format!("{:?}", process.status),
+            process.pid.map(|p| p.to_string()).unwrap_or("-".into()),
+            process.command,
+        );
+    }
+
+    Ok(())
+}
+
+/// Scale each `TYPE=COUNT`. Every assignment is tried; the command fails if any of them did.
+pub async fn scale_processes(assignments: Vec<String>) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let mut scaled = BTreeMap::new();
+    let mut failures = 0;
+    for assignment in &assignments {
+        let Some((process_type, scale)) = assignment.split_once('=') else {
+            eprintln!("Invalid format '{}'. Use TYPE=COUNT, e.g. worker=2", assignment);
+            failures += 1;
+            continue;
+        };
+        let scale: i32 = match scale.trim().parse() {
+            Ok(scale) => scale,
+            Err(_) => {
+                eprintln!("Invalid count '{}' for {}", scale, process_type);
+                failures += 1;
+                continue;
+            }
+        };
+
+        if scale_process(app_id, process_type.trim(), scale).await {
+            scaled.insert(process_type.trim(), scale);
+        } else {
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        return Err(fail(Failure::Error, format!("{} of {} changes failed", failures, assignments.len())));
+    }
+    if json() {
+        print_json(&scaled)?;
+    }
+    Ok(())
+}
+
+/// Returns whether paasd scaled it
+async fn scale_process(app_id: Uuid, process_type: &str, scale: i32) -> bool {
+    match client().scale(app_id, process_type, scale).await {
+        Ok(_) => {
+            say!("Scaled {} to {}", process_type, scale);
+            true
+        }
+        Err(e) => {
+            report_failure(&format!("Failed to scale {}", process_type), &e);
+            false
+        }
+    }
+}
+
+/// Scale process types to the `replicas` of paas.toml after a deploy
+pub async fn apply_replicas(app_id: Uuid, replicas: &BTreeMap<String, i32>) {
+    for (process_type, scale) in replicas {
+        scale_process(app_id, process_type, *scale).await;
+    }
+}
+
+pub async fn restart_processes(process_type: Option<String>) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let types: BTreeSet<String> = match process_type {
+        Some(process_type) => BTreeSet::from([process_type]),
+        None => fetch_processes(app_id).await?.into_iter().map(|p| p.process_type).collect(),
+    };
+
+    let mut restarted = Vec::new();
+    for process_type in &types {
+        match client().restart_processes(app_id, process_type).await {
+            Ok(_) => {
+                say!("Restarted {} processes", process_type);
+                restarted.push(process_type);
+            }
+            Err(e) => report_failure(&format!("Failed to restart {}", process_type), &e),
+        }
+    }
+
+    if restarted.len() < types.len() {
+        return Err(fail(
+            Failure::Error,
+            format!("{} of {} process types failed to restart", types.len() - restarted.len(), types.len()),
+        ));
+    }
+    if json() {
+        print_json(&serde_json::json!({ "restarted": restarted }))?;
+    }
+    Ok(())
+}
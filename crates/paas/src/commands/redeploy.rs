@@ -1,65 +1,172 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
-use reqwest::Client;
+use shared::{AppLiveStatus, RedeployApp};
 use uuid::Uuid;
 
-pub async fn redeploy_project() -> anyhow::Result<()> {
-    let filename = "paas.toml";
-    if !Path::new(filename).exists() {
-        println!("Initialize the project first. use 'paas init' for that.");
-        return Ok(());
-    }
+use crate::commands::logs::follow_build_logs;
+use crate::commands::ps::apply_replicas;
+use crate::commands::releases::describe_release;
+use crate::config::{
+    PaasConfig, client, require_paas_toml, selected_app, server_host, sync_schedules, target_app_id, with_environment,
+};
+use crate::errors::{Failure, fail, failed, failed_as};
+use crate::output::{json, print_json, say};
+use crate::source::{prepare_source, upload_source};
 
-    let content = std::fs::read_to_string(filename)?;
-    let app_data: toml::Value = toml::from_str(&content)?;
+const BUILD_FAILED: &str = "Build failed, redeploy aborted. The previous version is still running.";
 
-    let id = match app_data.get("id").and_then(|v| v.as_str()) {
-        Some(id) => id.to_string(),
-        None => {
-            println!("Project not deployed yet. Use `paas deploy` first.");
-            return Ok(());
-        }
+/// Start `release` on the app with the process types, build, env and port of `config`,
+/// then follow the build and apply replicas and cron jobs
+pub async fn redeploy_release(
+    app_id: Uuid,
+    config: &PaasConfig,
+    formation: &BTreeMap<String, String>,
+    release_id: Uuid,
+) -> anyhow::Result<()> {
+    let redeploy_started = chrono::Utc::now();
+    let redeploy = RedeployApp {
+        // An automatically allocated port is kept
+        port: config.fixed_port(),
+        processes: Some(formation.clone()),
+        release_id: Some(release_id),
+        // An empty build removes a previously configured one
+        build: Some(config.build.clone().unwrap_or_default()),
+        build_timeout: config.build_timeout,
+        // Merged into the server's env vars; `paas env push` also removes vars
+        env_vars: Some(config.env.clone().unwrap_or_default().into_iter().collect()),
+        links: Some(config.links.clone().unwrap_or_default()),
+        on_link_change: Some(
+            config
+                .on_link_change
+                .clone()
+                .unwrap_or_else(|| shared::DEFAULT_LINK_CHANGE.to_string()),
+        ),
+        labels: Some(config.labels.clone().unwrap_or_default()),
     };
+    let redeployed = client()
+        .redeploy(app_id, &redeploy)
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Redeploy failed", e))?;
 
-    let app_id: Uuid = id.parse()?;
-    let port = app_data.get("port").and_then(|v| v.as_integer()).unwrap_or(3000) as i32;
-
-    println!("Redeploying app with id: {}", app_id);
-
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}/redeploy", app_id);
-
-    let res = client.post(&url).json(&serde_json::json!({ "port": port })).send().await?;
-
-    if res.status().is_success() {
-        println!("Application successfully redeployed.");
-        println!("Starting application...");
-
-        // Wait for app to start and detect actual port
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-        let client2 = Client::new();
-        let status_url = format!("http://127.0.0.1:8080/apps/{}/status", app_id);
-        if let Ok(status_res) = client2.get(&status_url).send().await {
-            if let Ok(status_body) = status_res.json::<serde_json::Value>().await {
-                let status = status_body["status"].as_str().unwrap_or("UNKNOWN");
-                if status == "STOPPED" || status == "CRASHED" {
-                    eprintln!("Application failed to start! Check logs with `paas logs`");
-                } else {
-                    let port = status_body["port"].as_i64().unwrap_or(0);
-                    if port > 0 {
-                        println!("Application is running on port {}", port);
-                        println!("Local: http://localhost:{}", port);
-                    } else {
-                        println!("Application is running. Port not yet detected.");
-                        println!("Check `paas logs` for the actual port.");
-                    }
-                }
-            }
+    if redeployed.building {
+        say!("Building...");
+        if !follow_build_logs(&app_id, redeploy_started).await? {
+            return Err(fail(Failure::DeployFailed, BUILD_FAILED));
         }
+    }
+    say!("Application successfully redeployed.");
+
+    if let Some(replicas) = &config.replicas {
+        apply_replicas(app_id, replicas).await;
+    }
+    let schedules = config.schedules();
+    match sync_schedules(app_id, &schedules).await {
+        Ok(_) if !schedules.is_empty() => say!("Updated {} cron job(s)", schedules.len()),
+        Ok(_) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+    Ok(())
+}
+
+/// Wait for the app to start and report where it's listening. Fails with `AppCrashed` if it didn't start.
+pub async fn report_started(app_id: Uuid) -> anyhow::Result<AppLiveStatus> {
+    say!("Starting application...");
+    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+    let status = client()
+        .app_status(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch the app status", e))?;
+
+    if status.status == "STOPPED" || status.status == "CRASHED" {
+        return Err(fail(
+            Failure::AppCrashed,
+            format!("Application failed to start! Check logs with `{}`", with_environment("paas logs")),
+        ));
+    }
+
+    let port = status.port;
+    if port > 0 {
+        say!("Application is running on port {}", port);
+        say!("URL: http://{}:{}", server_host(), port);
     } else {
-        eprintln!("Redeploy failed with status: {}", res.status());
+        say!("Application is running. Port not yet detected.");
+        say!("Check `{}` for the actual port.", with_environment("paas logs"));
     }
+    Ok(status)
+}
+
+/// Package `dir` as a new release of the app and start it
+pub async fn redeploy_dir(dir: &Path, app_id: Uuid, config: &PaasConfig, git_ref: Option<&str>) -> anyhow::Result<()> {
+    let formation = config
+        .process_formation_in(dir)
+        .map_err(|e| fail(Failure::DeployFailed, format!("{:#}", e)))?;
+
+    say!("Redeploying app with id: {}", app_id);
+
+    let (archive, git) =
+        prepare_source(dir, git_ref).map_err(|e| fail(Failure::DeployFailed, format!("{:#}", e)))?;
+    say!(
+        "Packaged {} files ({:.1} KB)",
+        archive.files,
+        archive.bytes.len() as f64 / 1024.0
+    );
+    let release = upload_source(app_id, &archive, git.as_ref()).await?;
+    say!("Uploaded release {}", describe_release(&release));
 
+    redeploy_release(app_id, config, &formation, release.id).await
+}
+
+/// Restart the app named with `--app` on the release and config it already has
+async fn restart_named_app() -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+    say!("Redeploying app with id: {}", app_id);
+
+    let redeploy_started = chrono::Utc::now();
+    let redeployed = client()
+        .redeploy(app_id, &RedeployApp::default())
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Redeploy failed", e))?;
+    if redeployed.building {
+        say!("Building...");
+        if !follow_build_logs(&app_id, redeploy_started).await? {
+            return Err(fail(Failure::DeployFailed, BUILD_FAILED));
+        }
+    }
+    let status = report_started(app_id).await?;
+    if json() {
+        print_json(&status)?;
+    }
+    Ok(())
+}
+
+pub async fn redeploy_project(git_ref: Option<String>) -> anyhow::Result<()> {
+    // Without paas.toml there is no source to upload
+    if selected_app().is_some() {
+        if git_ref.is_some() {
+            return Err(fail(
+                Failure::Error,
+                "--ref can't be combined with --app; run `paas redeploy --ref` in the project directory.",
+            ));
+        }
+        return restart_named_app().await;
+    }
+
+    let filename = "paas.toml";
+    require_paas_toml(filename)?;
+
+    let config = PaasConfig::parse(&std::fs::read_to_string(filename)?)?;
+    let Some(app_id) = config.id else {
+        return Err(fail(
+            Failure::NotDeployed,
+            format!("Project not deployed yet. Use `{}` first.", with_environment("paas deploy")),
+        ));
+    };
+
+    redeploy_dir(&std::env::current_dir()?, app_id, &config, git_ref.as_deref()).await?;
+    let status = report_started(app_id).await?;
+    if json() {
+        print_json(&status)?;
+    }
     Ok(())
 }
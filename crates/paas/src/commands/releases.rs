@@ -0,0 +1,80 @@
+use shared::Release;
+
+use crate::config::{client, target_app_id};
+use crate::errors::failed;
+use crate::output::{json, print_json};
+
+/// `v3 (1a2b3c4 on main, uncommitted changes)`
+pub fn describe_release(release: &Release) -> String {
+    // Config changes: `v4: Set DATABASE_URL`
+    if let Some(description) = &release.description {
+        return format!("v{}: {}", release.version, description);
+    }
+    let Some(commit) = &release.git_commit else {
+        return format!("v{}", release.version);
+    };
+    let mut description = format!("v{} ({}", release.version, short_commit(commit));
+    if let Some(branch) = &release.git_branch {
+        description.push_str(&format!(" on {}", branch));
+    }
+    if release.git_dirty == Some(true) {
+        description.push_str(", uncommitted changes");
+    }
+    description.push(')');
+    description
+}
+
+fn short_commit(commit: &str) -> &str {
+    &commit[..commit.len().min(7)]
+}
+
+pub async fn list_releases() -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let current = client()
+        .app(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch releases", e))?
+        .app
+        .release_id;
+
+    let releases = client()
+        .releases(app_id, None)
+        .await
+        .map_err(|e| failed("Failed to fetch releases", e))?;
+
+    if json() {
+        return print_json(&serde_json::json!({ "current": current, "releases": releases }));
+    }
+    if releases.is_empty() {
+        println!("No releases yet. Deploy with `paas deploy`.");
+        return Ok(());
+    }
+
+    println!(
+        "  {:<8} {:<20} {:<9} {:<20} {:<6} {:<10} CHANGE",
+        "VERSION", "CREATED (UTC)", "COMMIT", "BRANCH", "DIRTY", "SIZE"
+    );
+    for release in &releases {
+        let marker = if current == Some(release.id) { "*" } else { " " };
+        println!(
+            "{} {:<8} {:<20} {:<9} {:<20} {:<6} {:<10} {}",
+            marker,
+            format!("v{}", release.version),
+            release.created_at.format("%Y-%m-%d %H:%M:%S"),
+            release.git_commit.as_deref().map(short_commit).unwrap_or("-"),
+            release.git_branch.as_deref().unwrap_or("-"),
+            match release.git_dirty {
+                Some(true) => "yes",
+                Some(false) => "no",
+                None => "-",
+            },
+            format!("{:.1} KB", release.source_size as f64 / 1024.0),
+            release.description.as_deref().unwrap_or("Deploy"),
+        );
+    }
+    println!();
+    println!("* current release");
+
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use shared::RunStatus;
+
+use crate::config::{client, target_app};
+use crate::errors::failed;
+use crate::output::{json, print_json};
+
+/// Run a one-off command in the deployed app's environment, stream its output
+/// and exit with the command's exit code. With `--json` the output is collected and
+/// printed with the run once it finished.
+pub async fn run_command(command: Vec<String>) -> anyhow::Result<()> {
+    let app = target_app().await?;
+    let app_id = app.id;
+
+    let run = client()
+        .start_run(app_id, &command.join(" "))
+        .await
+        .map_err(|e| failed("Failed to start run", e))?;
+    eprintln!("Running `{}` on {} (run {})", run.command, app.name, run.id);
+
+    let mut last_log_id = 0;
+    let mut output = Vec::new();
+    loop {
+        // Fetch the run's state before its logs so no output written before it finished is missed
+        let current = client()
+            .run(app_id, run.id)
+            .await
+            .map_err(|e| failed("Failed to fetch the run", e))?;
+        let logs = client()
+            .run_logs(app_id, run.id, last_log_id)
+            .await
+            .map_err(|e| failed("Failed to fetch the run's output", e))?;
+        for log in logs {
+            last_log_id = log.id;
+            if json() {
+                output.push(log);
+                continue;
+            }
+            if log.stream == "stderr" {
+                eprintln!("{}", log.message);
+            } else {
+                println!("{}", log.message);
+            }
+        }
+
+        if current.status != RunStatus::RUNNING {
+            let exit_code = current.exit_code.unwrap_or(1);
+            eprintln!(
+                "Run finished with exit code {} in {:.1}s",
+                exit_code,
+                current.duration_ms.unwrap_or(0) as f64 / 1000.0
+            );
+            if json() {
+                print_json(&serde_json::json!({ "run": current, "output": output }))?;
+            }
+            std::process::exit(exit_code);
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+}
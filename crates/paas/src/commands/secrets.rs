@@ -0,0 +1,82 @@
+use std::io::{IsTerminal, Read, stdin};
+
+use crate::config::{client, target_app_id};
+use crate::errors::{Failure, fail, failed};
+use crate::output::{json, print_json};
+
+/// `KEY=VALUE`, or just `KEY` to read the value from stdin so it stays out of shell history
+fn parse_assignment(key_value: &str) -> anyhow::Result<(String, String)> {
+    if let Some((key, value)) = key_value.split_once('=') {
+        return Ok((key.trim().to_string(), value.to_string()));
+    }
+
+    if stdin().is_terminal() {
+        eprintln!("Enter the value for {} and press Ctrl-D:", key_value);
+    }
+    let mut value = String::new();
+    stdin().read_to_string(&mut value)?;
+    let value = value.strip_suffix('\n').unwrap_or(&value);
+    let value = value.strip_suffix('\r').unwrap_or(value);
+    Ok((key_value.trim().to_string(), value.to_string()))
+}
+
+pub async fn secrets_set(key_value: String) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+    let (key, value) = parse_assignment(&key_value)?;
+
+    client()
+        .set_secret(app_id, &key, &value)
+        .await
+        .map_err(|e| failed("Failed to set secret", e))?;
+
+    if json() {
+        return print_json(&serde_json::json!({ "key": key, "set": true }));
+    }
+    println!("Set secret: {}", key);
+    println!("Run `paas redeploy` to apply it.");
+    Ok(())
+}
+
+pub async fn secrets_list() -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    let secrets = client()
+        .secrets(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch secrets", e))?;
+
+    if json() {
+        return print_json(&secrets);
+    }
+    if secrets.is_empty() {
+        println!("No secrets set. Add one with `paas secrets set KEY=VALUE`.");
+        return Ok(());
+    }
+
+    println!("{:<32} UPDATED (UTC)", "NAME");
+    for secret in &secrets {
+        println!(
+            "{:<32} {}",
+            secret.name,
+            secret.updated_at.format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+    Ok(())
+}
+
+pub async fn secrets_remove(key: String) -> anyhow::Result<()> {
+    let app_id = target_app_id().await?;
+
+    match client().remove_secret(app_id, &key).await {
+        Ok(()) => {}
+        Err(e) if e.is_not_found() => return Err(fail(Failure::Error, format!("Secret '{}' not found.", key))),
+        Err(e) => return Err(failed("Failed to remove secret", e)),
+    }
+
+    if json() {
+        return print_json(&serde_json::json!({ "key": key, "removed": true }));
+    }
+    println!("Removed secret: {}", key);
+    println!("Run `paas redeploy` to apply it.");
+    Ok(())
+}
@@ -0,0 +1,333 @@
+//! `paas-stack.toml`: several apps deployed together, each from its own directory with its own
+//! paas.toml, in `depends_on` order.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use paas_client::Error;
+use serde::{Deserialize, Serialize};
+use shared::{AppStatus, PatchApplication};
+use uuid::Uuid;
+
+use crate::commands::deploy::deploy_new;
+use crate::commands::redeploy::redeploy_dir;
+use crate::config::{EnvironmentConfig, PaasConfig, PortConfig, client, server_host};
+use crate::errors::{Failure, fail, failed, report_failure};
+use crate::output::{json, print_json, say};
+
+const STACK_FILE: &str = "paas-stack.toml";
+
+#[derive(Debug, Deserialize)]
+struct StackConfig {
+    /// Env vars shared by every app. An app's own paas.toml [env] wins over these.
+    env: Option<HashMap<String, String>>,
+    apps: BTreeMap<String, StackApp>,
+}
+
+/// An `[apps.<name>]` section
+#[derive(Debug, Deserialize)]
+struct StackApp {
+    /// Directory holding the app's paas.toml, relative to paas-stack.toml. Defaults to the app's key.
+    path: Option<PathBuf>,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    /// Path polled on the app's port until it answers 2xx, e.g. `/health`
+    health_check: Option<String>,
+    /// Seconds to wait for the app to become healthy
+    health_timeout: Option<u64>,
+    command: Option<String>,
+    port: Option<PortConfig>,
+    /// Merged over the app's paas.toml [env]
+    env: Option<HashMap<String, String>>,
+    replicas: Option<BTreeMap<String, i32>>,
+}
+
+/// One app of the stack with its config resolved
+struct StackMember {
+    name: String,
+    dir: PathBuf,
+    config: PaasConfig,
+    depends_on: Vec<String>,
+    health_check: Option<String>,
+    health_timeout: Duration,
+}
+
+/// A stack member's state, for `--json`
+#[derive(Debug, Serialize)]
+struct MemberOutput {
+    name: String,
+    app: String,
+    id: Option<Uuid>,
+    status: String,
+    port: Option<i32>,
+    depends_on: Vec<String>,
+}
+
+impl MemberOutput {
+    fn new(member: &StackMember, id: Option<Uuid>, status: &str) -> Self {
+        MemberOutput {
+            name: member.name.clone(),
+            app: member.config.name.clone(),
+            id,
+            status: status.to_string(),
+            port: None,
+            depends_on: member.depends_on.clone(),
+        }
+    }
+}
+
+fn read_stack() -> anyhow::Result<StackConfig> {
+    if !Path::new(STACK_FILE).exists() {
+        return Err(fail(
+            Failure::NotInitialized,
+            format!("can not find {} in this directory.", STACK_FILE),
+        ));
+    }
+    toml::from_str(&read_to_string(STACK_FILE)?).map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", STACK_FILE, e))
+}
+
+/// App keys in an order where every app comes after its dependencies.
+/// Fails on unknown dependencies and cycles.
+fn deploy_order(apps: &BTreeMap<String, StackApp>) -> anyhow::Result<Vec<String>> {
+    for (name, app) in apps {
+        for dependency in &app.depends_on {
+            if !apps.contains_key(dependency) {
+                anyhow::bail!("{} depends on '{}', which isn't in {}", name, dependency, STACK_FILE);
+            }
+        }
+    }
+
+    let mut remaining: BTreeMap<&str, Vec<&str>> = apps
+        .iter()
+        .map(|(name, app)| (name.as_str(), app.depends_on.iter().map(String::as_str).collect()))
+        .collect();
+    let mut order = Vec::new();
+    while !remaining.is_empty() {
+        let ready: Vec<&str> = remaining
+            .iter()
+            .filter(|(_, deps)| deps.iter().all(|d| !remaining.contains_key(d)))
+            .map(|(name, _)| *name)
+            .collect();
+        if ready.is_empty() {
+            let cycle: Vec<&str> = remaining.keys().copied().collect();
+            anyhow::bail!("Apps depend on each other in a cycle: {}", cycle.join(", "));
+        }
+        for name in ready {
+            remaining.remove(name);
+            order.push(name.to_string());
+        }
+    }
+    Ok(order)
+}
+
+/// The stack's apps in dependency order, each with its paas.toml (for `--env`, if given)
+/// and the stack's shared env and overrides applied
+fn load_members(stack: StackConfig) -> anyhow::Result<Vec<StackMember>> {
+    let order = deploy_order(&stack.apps)?;
+    let shared_env = stack.env.unwrap_or_default();
+    let mut apps = stack.apps;
+
+    let mut members = Vec::new();
+    for name in order {
+        let app = apps.remove(&name).expect("ordered apps come from the stack");
+        let dir = app.path.unwrap_or_else(|| PathBuf::from(&name));
+        let config_path = dir.join("paas.toml");
+        let content = read_to_string(&config_path)
+            .map_err(|e| anyhow::anyhow!("{}: cannot read {}: {}", name, config_path.display(), e))?;
+        let mut config = PaasConfig::parse(&content)
+            .map_err(|e| anyhow::anyhow!("{}: invalid {}: {}", name, config_path.display(), e))?;
+
+        let mut env = shared_env.clone();
+        env.extend(config.env.take().unwrap_or_default());
+        config.env = Some(env);
+        config.apply_overrides(EnvironmentConfig {
+            id: None,
+            command: app.command,
+            port: app.port,
+            env: app.env,
+            replicas: app.replicas,
+        });
+
+        members.push(StackMember {
+            name,
+            dir,
+            config,
+            depends_on: app.depends_on,
+            health_check: app.health_check,
+            health_timeout: Duration::from_secs(app.health_timeout.unwrap_or(60)),
+        });
+    }
+    Ok(members)
+}
+
+/// Wait until the app reports RUNNING and, if it has a health check, that answers 2xx.
+/// Fails if it stopped, crashed or timed out. Returns its port.
+async fn wait_healthy(member: &StackMember, app_id: Uuid) -> anyhow::Result<i32> {
+    let deadline = Instant::now() + member.health_timeout;
+
+    let port = loop {
+        if Instant::now() > deadline {
+            return Err(fail(
+                Failure::DeployFailed,
+                format!("{} didn't start within {}s", member.name, member.health_timeout.as_secs()),
+            ));
+        }
+        if let Ok(status) = client().app_status(app_id).await {
+            match status.status.as_str() {
+                "RUNNING" => break status.port,
+                status @ ("STOPPED" | "CRASHED" | "FAILED") => {
+                    return Err(fail(
+                        Failure::AppCrashed,
+                        format!("{} is {}. Check its logs with `paas logs` in {}", member.name, status, member.dir.display()),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    };
+
+    let Some(path) = &member.health_check else {
+        say!("{} is running on port {}", member.name, port);
+        return Ok(port);
+    };
+    let health_url = format!("http://{}:{}/{}", server_host(), port, path.trim_start_matches('/'));
+    say!("Waiting for {} to pass {}", member.name, health_url);
+    let http = reqwest::Client::new();
+    loop {
+        if let Ok(res) = http.get(&health_url).send().await
+            && res.status().is_success()
+        {
+            say!("{} is healthy on port {}", member.name, port);
+            return Ok(port);
+        }
+        if Instant::now() > deadline {
+            return Err(fail(
+                Failure::DeployFailed,
+                format!("{} didn't pass its health check within {}s", member.name, member.health_timeout.as_secs()),
+            ));
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Deploy or redeploy every app in dependency order. An app only starts once its
+/// dependencies are healthy; the first failure stops the rollout.
+pub async fn stack_up() -> anyhow::Result<()> {
+    let members = load_members(read_stack()?)?;
+
+    let mut started = Vec::new();
+    for member in &members {
+        say!();
+        say!("==> {}", member.name);
+        let app_id = match member.config.id {
+            Some(app_id) => {
+                redeploy_dir(&member.dir, app_id, &member.config, None)
+                    .await
+                    .with_context(|| format!("Stack rollout stopped at {}.", member.name))?;
+                app_id
+            }
+            None => {
+                deploy_new(&member.dir, &member.config, None)
+                    .await
+                    .with_context(|| format!("Stack rollout stopped at {}.", member.name))?
+            }
+        };
+
+        let port = wait_healthy(member, app_id).await.with_context(|| {
+            format!("Stack rollout stopped at {}; apps depending on it weren't started.", member.name)
+        })?;
+        started.push(MemberOutput {
+            port: Some(port),
+            ..MemberOutput::new(member, Some(app_id), "RUNNING")
+        });
+    }
+
+    if json() {
+        return print_json(&started);
+    }
+    println!();
+    println!("Stack is up: {} app(s)", members.len());
+    Ok(())
+}
+
+/// Stop every deployed app, dependents before their dependencies
+pub async fn stack_down() -> anyhow::Result<()> {
+    let members = load_members(read_stack()?)?;
+
+    let mut results = Vec::new();
+    let mut failures = 0;
+    for member in members.iter().rev() {
+        let Some(app_id) = member.config.id else {
+            say!("{}: not deployed", member.name);
+            results.push(MemberOutput::new(member, None, "NOT DEPLOYED"));
+            continue;
+        };
+        let patch = PatchApplication {
+            status: Some(AppStatus::STOPPED),
+            ..Default::default()
+        };
+        match client().patch_app(app_id, &patch).await {
+            Ok(()) => {
+                say!("{}: stopped", member.name);
+                results.push(MemberOutput::new(member, Some(app_id), "STOPPED"));
+            }
+            Err(e @ Error::Api(_)) => {
+                report_failure(&format!("{}: failed to stop", member.name), &e);
+                failures += 1;
+            }
+            Err(e) => return Err(failed(&format!("{}: failed to stop", member.name), e)),
+        }
+    }
+
+    if failures > 0 {
+        return Err(fail(Failure::Error, format!("{} app(s) failed to stop", failures)));
+    }
+    if json() {
+        print_json(&results)?;
+    }
+    Ok(())
+}
+
+pub async fn stack_status() -> anyhow::Result<()> {
+    let members = load_members(read_stack()?)?;
+
+    let mut rows = Vec::new();
+    for member in &members {
+        let row = match member.config.id {
+            None => MemberOutput::new(member, None, "NOT DEPLOYED"),
+            Some(app_id) => match client().app_status(app_id).await {
+                Ok(status) => MemberOutput {
+                    port: Some(status.port).filter(|p| *p > 0),
+                    ..MemberOutput::new(member, Some(app_id), &status.status)
+                },
+                Err(Error::Api(error)) => {
+                    MemberOutput::new(member, Some(app_id), &format!("ERROR {}", error.code.status()))
+                }
+                Err(e) => return Err(failed("Failed to fetch the stack's status", e)),
+            },
+        };
+        rows.push(row);
+    }
+
+    if json() {
+        return print_json(&rows);
+    }
+    println!("{:<20} {:<24} {:<10} {:<6} DEPENDS ON", "NAME", "APP", "STATUS", "PORT");
+    for row in &rows {
+        println!(
+            "{:<20} {:<24} {:<10} {:<6} {}",
+            row.name,
+            row.app,
+            row.status,
+            row.port.map(|p| p.to_string()).unwrap_or_default(),
+            row.depends_on.join(", ")
+        );
+    }
+    Ok(())
+}
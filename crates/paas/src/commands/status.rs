@@ -1,60 +1,57 @@
-use serde::Deserialize;
-use std::{fs::read_to_string, path::Path};
-use uuid::Uuid;
-
 use anyhow::Ok;
-use reqwest::Client;
-
-#[derive(Debug, Deserialize)]
-pub struct PaasConfig {
-    pub id: Option<Uuid>,
+use serde::Serialize;
+use shared::AppLiveStatus;
+
+use crate::commands::releases::describe_release;
+use crate::config::{active_context, client, target_app_id};
+use crate::errors::{Exit, Failure, failed};
+use crate::output::{json, print_json, say};
+
+/// `paas status --json`: the app's live status and the server it's on
+#[derive(Serialize)]
+struct StatusOutput<'a> {
+    context: Option<&'a str>,
+    server: &'a str,
+    #[serde(flatten)]
+    app: &'a AppLiveStatus,
 }
 
+/// Print the app's status. Exits with `AppCrashed` if it crashed or failed.
 pub async fn check_status() -> anyhow::Result<()> {
-    let filename = "paas.toml";
+    let app_id = target_app_id().await?;
 
-    if !Path::new(filename).exists() {
-        println!("can not find paas.toml config file. run `paas init` to initialize a project.");
-        return Ok(());
+    say!("getting the status of your application (id: {})", app_id);
+    match active_context() {
+        Some(context) => say!("Context: {} ({})", context, client().base_url()),
+        None => say!("Context: none ({})", client().base_url()),
     }
-
-    let config_file_content = read_to_string(filename)?;
-
-    let app_data: PaasConfig = toml::from_str(&config_file_content)?;
-
-    if app_data.id.is_none() {
-        println!("Project not deployed.");
-        println!("Run `paas deploy`");
-        return Ok(());
+    say!();
+
+    let info = client().app_status(app_id).await.map_err(|e| failed("Failed to fetch status", e))?;
+
+    if json() {
+        print_json(&StatusOutput {
+            context: active_context(),
+            server: client().base_url(),
+            app: &info,
+        })?;
+    } else {
+        println!(
+            "Application: {}\nId: {}\nStatus: {}\nPID: {}\nPort: {}\nCommand: {}",
+            info.name,
+            info.id,
+            info.status,
+            info.pid.map(|p| p.to_string()).unwrap_or("none".into()),
+            info.port,
+            info.command,
+        );
+        if let Some(release) = &info.release {
+            println!("Release: {}", describe_release(release));
+        }
     }
 
-    println!(
-        "getting the status of your application (id: {:?}",
-        app_data.id
-    );
-    println!();
-
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}/status", app_data.id.unwrap());
-
-    let res = client.get(&url).send().await?;
-
-    if !res.status().is_success() {
-        eprintln!("Failed to fetch status: {}", res.status());
-        return Ok(());
+    if matches!(info.status.as_str(), "CRASHED" | "FAILED") {
+        return Err(Exit(Failure::AppCrashed).into());
     }
-
-    let info: serde_json::Value = res.json().await?;
-
-    println!(
-        "Application: {}\nId: {}\nStatus: {}\nPID: {}\nPort: {}\nCommand: {}",
-        info["name"].as_str().unwrap_or("unknown"),
-        info["id"].as_str().unwrap_or("unknown"),
-        info["status"].as_str().unwrap_or("unknown"),
-        info["pid"].as_i64().map(|p: i64| p.to_string()).unwrap_or("none".into()),
-        info["port"].as_i64().unwrap_or(0),
-        info["command"].as_str().unwrap_or("unknown"),
-    );
-
     Ok(())
 }
@@ -1,117 +1,53 @@
-use std::{fs::read_to_string, path::Path};
-
 use anyhow::Ok;
-use reqwest::{Client, StatusCode};
-use shared::{AppStatus, Application, PatchApplication};
+use shared::{AppStatus, PatchApplication};
 
-use crate::commands::deploy::PaasConfig;
+use crate::config::{client, target_app_id};
+use crate::errors::failed;
+use crate::output::{json, print_json, say};
 
 pub async fn stop_application() -> anyhow::Result<()> {
-    let filename = "paas.toml";
-
-    if !Path::new(filename).exists() {
-        println!("can not find paas.toml config file. run `paas init` to initialize a project.");
-        return Ok(());
-    }
+    let app_id = target_app_id().await?;
 
-    let config_file_content = read_to_string(filename)?;
+    say!("Fetching application from server");
 
-    let app_data: PaasConfig = match toml::from_str(&config_file_content) {
-        Result::Ok(data) => data,
-        Result::Err(e) => {
-            eprintln!("Failed to parse paas.toml: {}", e);
-            eprintln!("Check for duplicate keys in your [env] section.");
-            return Ok(());
-        }
-    };
-
-    if app_data.id.is_none() {
-        println!("Project not deployed");
-        println!("Run `paas deploy`");
-        return Ok(());
-    }
-
-    let app_id = app_data.id.unwrap();
-
-    println!("Fetching application from server");
-
-    let client = Client::new();
-    let url = format!("http://127.0.0.1:8080/apps/{}", app_id);
-
-    let res = match client.get(&url).send().await {
-        Result::Ok(res) => res,
-        Result::Err(_) => {
-            eprintln!("Cannot connect to server");
-            return Ok(());
-        }
-    };
+    let application_infos = client()
+        .app(app_id)
+        .await
+        .map_err(|e| failed("Failed to fetch status", e))?
+        .app;
 
-    match res.status() {
-        StatusCode::NOT_FOUND => {
-            eprintln!("Application not found on server");
-            return Ok(());
-        }
-        StatusCode::INTERNAL_SERVER_ERROR => {
-            eprintln!("Server Error");
-            return Ok(());
-        }
-        s if !s.is_success() => {
-            eprintln!("Failed to fetch status: {}", res.status());
-            return Ok(());
-        }
-        _ => {}
-    }
-
-    let application_infos: Application = res.json().await?;
-
-    match application_infos.status {
+    let stopped = match application_infos.status {
         AppStatus::STOPPED => {
-            println!("Application already stopped.");
-            return Ok(());
+            say!("Application already stopped.");
+            false
         }
         AppStatus::RUNNING | AppStatus::PENDING => {
-            println!("Stopping application...");
-            let url = format!("http://127.0.0.1:8080/apps/{}", app_id);
-
+            say!("Stopping application...");
             let request_payload = PatchApplication {
                 status: Option::Some(AppStatus::STOPPED),
-                name: Option::None,
-                command: Option::None,
-                port: Option::None,
-                working_dir: Option::None,
-                pid: Option::None,
-                env_vars: Option::None,
-            };
-
-            let res = match client.patch(&url).json(&request_payload).send().await {
-                Result::Ok(res) => res,
-                Result::Err(_) => {
-                    eprintln!("Cannot connect to server");
-                    return Ok(());
-                }
+                ..Default::default()
             };
 
-            match res.status() {
-                StatusCode::OK => {
-                    println!("Application stopped successfully.");
-                    println!("Run `paas redeploy` to start it again.");
-                }
-                StatusCode::NOT_FOUND => {
-                    eprintln!("Application not found on server.");
-                }
-                StatusCode::INTERNAL_SERVER_ERROR => {
-                    eprintln!("Server error.");
-                }
-                s => {
-                    eprintln!(" Failed to stop application: {}", s);
-                }
-            }
+            client()
+                .patch_app(app_id, &request_payload)
+                .await
+                .map_err(|e| failed("Failed to stop application", e))?;
+            say!("Application stopped successfully.");
+            say!("Run `paas redeploy` to start it again.");
+            true
         }
         _ => {
-            println!("Application is not running.");
-            return Ok(());
+            say!("Application is not running.");
+            false
         }
-    }
+    };
 
+    if json() {
+        print_json(&serde_json::json!({
+            "id": app_id,
+            "name": application_infos.name,
+            "stopped": stopped,
+        }))?;
+    }
     Ok(())
 }
@@ -0,0 +1,526 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use paas_client::Client;
+use serde::{Deserialize, Serialize};
+use shared::{NewSchedule, WEB_PROCESS};
+use uuid::Uuid;
+
+use crate::errors::{Failure, fail, failed};
+
+/// The environment picked with `--env`, set once at startup
+static ENVIRONMENT: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn select_environment(environment: Option<String>) {
+    let _ = ENVIRONMENT.set(environment);
+}
+
+pub fn selected_environment() -> Option<&'static str> {
+    ENVIRONMENT.get().and_then(|e| e.as_deref())
+}
+
+/// The app named with `--app`, which commands act on instead of paas.toml's
+static APP: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn select_app(app: Option<String>) {
+    let _ = APP.set(app);
+}
+
+pub fn selected_app() -> Option<&'static str> {
+    APP.get().and_then(|a| a.as_deref())
+}
+
+/// The paasd client commands talk to the server through
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+pub fn client() -> &'static Client {
+    CLIENT.get_or_init(Client::new)
+}
+
+/// The context picked with `--context` or `paas context use`, if any, set once at startup
+static CONTEXT: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn active_context() -> Option<&'static str> {
+    CONTEXT.get().and_then(|c| c.as_deref())
+}
+
+/// The host of the paasd server, where the agent runs the apps and their git repositories live
+pub fn server_host() -> String {
+    reqwest::Url::parse(client().base_url())
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Point `client()` at the server `UserConfig::select` picks for `--context`
+pub fn connect(context: Option<String>) -> anyhow::Result<()> {
+    let server = UserConfig::load()?.select(context)?;
+
+    let mut builder = Client::builder();
+    if let Some(url) = server.url {
+        builder = builder.base_url(url);
+    }
+    if let Some(token) = server.token {
+        builder = builder.token(token);
+    }
+    let _ = CLIENT.set(builder.build());
+    let _ = CONTEXT.set(server.context);
+    Ok(())
+}
+
+fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Settings of the CLI itself rather than of a project, in `~/.config/paas/config.toml`
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// paasd's URL when no context is in use, e.g. `server = "http://10.0.0.5:8080"`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// The context set with `paas context use`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_context: Option<String>,
+    /// Named servers, e.g. `[contexts.staging]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub contexts: BTreeMap<String, ContextConfig>,
+}
+
+/// A `[contexts.<name>]` section: a paasd server and the token to use with it
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ContextConfig {
+    pub server: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// The server commands talk to, and the context it comes from
+pub struct SelectedServer {
+    pub context: Option<String>,
+    /// None for the local paasd
+    pub url: Option<String>,
+    pub token: Option<String>,
+}
+
+impl UserConfig {
+    /// `$XDG_CONFIG_HOME/paas/config.toml`, falling back to `~/.config`
+    pub fn path() -> Option<PathBuf> {
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("paas").join("config.toml"))
+    }
+
+    /// The user config, or the defaults if there is none
+    pub fn load() -> anyhow::Result<Self> {
+        let Some(path) = Self::path().filter(|path| path.exists()) else {
+            return Ok(UserConfig::default());
+        };
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Write the config back. It holds tokens, so only the user may read it.
+    pub fn save(&self) -> anyhow::Result<PathBuf> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("Cannot find the config directory; set HOME"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Owner-only before anything is written, including files created by an older version
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::io::Write::write_all(&mut file, toml::to_string_pretty(self)?.as_bytes())?;
+        Ok(path)
+    }
+
+    /// The server to use: the `--context` one, else `PAAS_SERVER` (with `PAAS_TOKEN`), else the
+    /// current context, else the top-level `server`. None of them means the local paasd.
+    pub fn select(self, context: Option<String>) -> anyhow::Result<SelectedServer> {
+        if context.is_none()
+            && let Some(url) = non_empty_var("PAAS_SERVER")
+        {
+            return Ok(SelectedServer {
+                context: None,
+                url: Some(url),
+                token: non_empty_var("PAAS_TOKEN"),
+            });
+        }
+
+        match context.or(self.current_context) {
+            Some(name) => {
+                let Some(found) = self.contexts.get(&name) else {
+                    anyhow::bail!("No context named '{}'. Run `paas context list` to see them.", name);
+                };
+                Ok(SelectedServer {
+                    url: Some(found.server.clone()),
+                    token: found.token.clone(),
+                    context: Some(name),
+                })
+            }
+            None => Ok(SelectedServer {
+                context: None,
+                url: self.server,
+                token: self.token,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaasConfig {
+    pub name: String,
+    pub runtime: String,
+    pub command: Option<String>,
+    /// Runs in the app directory before every deploy; a failing build aborts the deploy
+    pub build: Option<String>,
+    pub build_timeout: Option<i32>,
+    pub port: Option<PortConfig>,
+    pub id: Option<Uuid>,
+    pub env: Option<HashMap<String, String>>,
+    pub processes: Option<BTreeMap<String, String>>,
+    /// Instances per process type, e.g. `{ web = 2, worker = 3 }`
+    pub replicas: Option<BTreeMap<String, i32>>,
+    pub cron: Option<Vec<CronConfig>>,
+    /// Apps whose address is injected as `<NAME>_URL`, e.g. `links = ["api"]`
+    pub links: Option<Vec<String>>,
+    /// `restart` (default), `reload` to send SIGHUP so the app re-reads `/discovery/<name>`, or `none`
+    pub on_link_change: Option<String>,
+    /// Tags for `paas apps --label`, e.g. `[labels] team = "payments"`
+    pub labels: Option<BTreeMap<String, String>>,
+    pub environments: Option<BTreeMap<String, EnvironmentConfig>>,
+}
+
+/// An `[environments.<name>]` section. Each environment is deployed as its own app.
+#[derive(Debug, Default, Deserialize)]
+pub struct EnvironmentConfig {
+    pub id: Option<Uuid>,
+    pub command: Option<String>,
+    pub port: Option<PortConfig>,
+    /// Merged over the top-level [env]
+    pub env: Option<HashMap<String, String>>,
+    /// Merged over the top-level `replicas`
+    pub replicas: Option<BTreeMap<String, i32>>,
+}
+
+/// `port = 8080`, or `port = "auto"` to have paasd allocate a free one
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(untagged, expecting = "a port number or \"auto\"")]
+pub enum PortConfig {
+    Fixed(i32),
+    Auto(AutoPort),
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoPort {
+    Auto,
+}
+
+/// A `[[cron]]` entry: run `command` in the app's environment on `schedule` (UTC)
+#[derive(Debug, Deserialize)]
+pub struct CronConfig {
+    pub name: Option<String>,
+    pub schedule: String,
+    pub command: String,
+}
+
+impl PaasConfig {
+    /// Parse paas.toml for the environment selected with `--env`, if any
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let config: PaasConfig = toml::from_str(content)?;
+        match selected_environment() {
+            Some(environment) => config.in_environment(environment),
+            None => Ok(config),
+        }
+    }
+
+    /// The config of one of the `[environments.<name>]`: its overrides applied, its own
+    /// app id, and `<name>-<environment>` as the app name
+    pub fn in_environment(mut self, environment: &str) -> anyhow::Result<Self> {
+        let Some(overrides) = self.environments.as_mut().and_then(|e| e.remove(environment)) else {
+            anyhow::bail!("No [environments.{}] section in paas.toml", environment);
+        };
+
+        self.name = format!("{}-{}", self.name, environment);
+        self.id = overrides.id;
+        self.apply_overrides(overrides);
+        Ok(self)
+    }
+
+    /// Apply an override section's command, port, env and replicas. Its `id` is left to the caller.
+    pub fn apply_overrides(&mut self, overrides: EnvironmentConfig) {
+        if let Some(command) = overrides.command {
+            if let Some(processes) = self.processes.as_mut()
+                && processes.contains_key(WEB_PROCESS)
+            {
+                processes.insert(WEB_PROCESS.to_string(), command.clone());
+            }
+            self.command = Some(command);
+        }
+        if overrides.port.is_some() {
+            self.port = overrides.port;
+        }
+        if let Some(env) = overrides.env {
+            self.env.get_or_insert_default().extend(env);
+        }
+        if let Some(replicas) = overrides.replicas {
+            self.replicas.get_or_insert_default().extend(replicas);
+        }
+    }
+
+    /// The port pinned in paas.toml. None when it is omitted or `auto`.
+    pub fn fixed_port(&self) -> Option<i32> {
+        match self.port {
+            Some(PortConfig::Fixed(port)) => Some(port),
+            _ => None,
+        }
+    }
+
+    /// Process types to run, from `[processes]` in paas.toml, falling back to a Procfile.
+    /// `command` is the `web` process unless `web` is defined explicitly.
+    pub fn process_formation(&self) -> anyhow::Result<BTreeMap<String, String>> {
+        self.process_formation_in(Path::new("."))
+    }
+
+    /// `process_formation` for a project in `dir` rather than the current directory
+    pub fn process_formation_in(&self, dir: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+        let procfile = dir.join("Procfile");
+        let mut formation = match &self.processes {
+            Some(processes) => processes.clone(),
+            None if procfile.exists() => parse_procfile(&std::fs::read_to_string(procfile)?)?,
+            None => BTreeMap::new(),
+        };
+
+        if !formation.contains_key(WEB_PROCESS) {
+            match &self.command {
+                Some(command) => {
+                    formation.insert(WEB_PROCESS.to_string(), command.clone());
+                }
+                None => anyhow::bail!(
+                    "No web process defined. Set `command` or add `web = \"...\"` under [processes]."
+                ),
+            }
+        }
+
+        Ok(formation)
+    }
+
+    /// Cron entries to send to paasd. Unnamed entries are named after their command.
+    pub fn schedules(&self) -> Vec<NewSchedule> {
+        self.cron
+            .iter()
+            .flatten()
+            .map(|cron| NewSchedule {
+                name: cron.name.clone().unwrap_or_else(|| cron.command.clone()),
+                schedule: cron.schedule.clone(),
+                command: cron.command.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A deployed app a command acts on
+pub struct TargetApp {
+    pub id: Uuid,
+    pub name: String,
+}
+
+/// The app named with `--app`, or else the deployed app of paas.toml. Fails with
+/// `NotInitialized` or `NotDeployed` when there is no such app.
+pub async fn target_app() -> anyhow::Result<TargetApp> {
+    if let Some(name) = selected_app() {
+        let app = match client().app(name).await {
+            Ok(details) => details.app,
+            Err(e) if e.is_not_found() => {
+                return Err(fail(Failure::NotDeployed, format!("No app named '{}'.", name)));
+            }
+            Err(e) => return Err(failed(&format!("Failed to look up app '{}'", name), e)),
+        };
+        let id = app.id.ok_or_else(|| fail(Failure::NotDeployed, format!("No app named '{}'.", name)))?;
+        return Ok(TargetApp { id, name: app.name });
+    }
+
+    let filename = "paas.toml";
+    if !Path::new(filename).exists() {
+        return Err(fail(
+            Failure::NotInitialized,
+            "can not find paas.toml config file. run `paas init` to initialize a project, or pass --app <name>.",
+        ));
+    }
+
+    let app_data = PaasConfig::parse(&std::fs::read_to_string(filename)?)?;
+    let Some(id) = app_data.id else {
+        return Err(fail(
+            Failure::NotDeployed,
+            format!("Project not deployed.\nRun `{}`", with_environment("paas deploy")),
+        ));
+    };
+    Ok(TargetApp { id, name: app_data.name })
+}
+
+/// Fail with `NotInitialized` unless the project has a paas.toml
+pub fn require_paas_toml(filename: &str) -> anyhow::Result<()> {
+    if Path::new(filename).exists() {
+        return Ok(());
+    }
+    Err(fail(Failure::NotInitialized, "No paas.toml found. Run `paas init` first."))
+}
+
+/// The id of the app `target_app` picks
+pub async fn target_app_id() -> anyhow::Result<Uuid> {
+    Ok(target_app().await?.id)
+}
+
+/// A CLI command with `--env` added when an environment is selected, for hints
+pub fn with_environment(command: &str) -> String {
+    match selected_environment() {
+        Some(environment) => format!("{} --env {}", command, environment),
+        None => command.to_string(),
+    }
+}
+
+/// Record the id of a newly deployed app in paas.toml, at the top level or in
+/// the `[environments.<name>]` section it was deployed for
+pub fn save_app_id(filename: &Path, environment: Option<&str>, app_id: Uuid) -> anyhow::Result<()> {
+    let content = std::fs::read_to_string(filename)?;
+
+    let new_content = match environment {
+        Some(environment) => {
+            let mut doc: toml::Value = toml::from_str(&content)?;
+            let section = doc
+                .get_mut("environments")
+                .and_then(|e| e.get_mut(environment))
+                .and_then(|e| e.as_table_mut())
+                .ok_or_else(|| anyhow::anyhow!("No [environments.{}] section in paas.toml", environment))?;
+            section.insert("id".to_string(), toml::Value::String(app_id.to_string()));
+            toml::to_string_pretty(&doc)?
+        }
+        // Insert id BEFORE the first table ([env], [processes], ...) so it stays a top-level key
+        None => match content.find("\n[") {
+            Some(idx) => format!("{}\nid = \"{}\"\n{}", &content[..idx], app_id, &content[idx..]),
+            None => format!("{}\nid = \"{}\"\n", content, app_id),
+        },
+    };
+    std::fs::write(filename, new_content)?;
+    Ok(())
+}
+
+/// Forget the id of a destroyed app, so the next `paas deploy` creates a new one
+pub fn remove_app_id(filename: &Path, environment: Option<&str>, app_id: Uuid) -> anyhow::Result<()> {
+    let content = std::fs::read_to_string(filename)?;
+
+    let new_content = match environment {
+        Some(environment) => {
+            let mut doc: toml::Value = toml::from_str(&content)?;
+            let section = doc
+                .get_mut("environments")
+                .and_then(|e| e.get_mut(environment))
+                .and_then(|e| e.as_table_mut());
+            match section {
+                Some(section) if section.get("id").and_then(|id| id.as_str()) == Some(&app_id.to_string()) => {
+                    section.remove("id");
+                    toml::to_string_pretty(&doc)?
+                }
+                _ => return Ok(()),
+            }
+        }
+        // Drop the top-level `id = "..."` line, leaving the rest of the file as written
+        None => {
+            let mut in_table = false;
+            let lines: Vec<&str> = content
+                .lines()
+                .filter(|line| {
+                    in_table |= line.trim_start().starts_with('[');
+                    let is_id = line
+                        .split_once('=')
+                        .is_some_and(|(key, value)| key.trim() == "id" && value.contains(&app_id.to_string()));
+                    in_table || !is_id
+                })
+                .collect();
+            format!("{}\n", lines.join("\n"))
+        }
+    };
+    std::fs::write(filename, new_content)?;
+    Ok(())
+}
+
+/// Push the `[[cron]]` entries of paas.toml to paasd, replacing the app's schedules
+pub async fn sync_schedules(app_id: Uuid, schedules: &[NewSchedule]) -> anyhow::Result<()> {
+    client()
+        .set_schedules(app_id, schedules)
+        .await
+        .map_err(|e| failed("Failed to update cron schedules", e))?;
+    Ok(())
+}
+
+/// Parse a Procfile: one `<type>: <command>` per line, `#` comments allowed
+pub fn parse_procfile(content: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut processes = BTreeMap::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, command) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Procfile line {}: expected `<type>: <command>`", n + 1))?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+            anyhow::bail!("Procfile line {}: invalid process type '{}'", n + 1, name);
+        }
+        processes.insert(name.to_string(), command.trim().to_string());
+    }
+    Ok(processes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_procfile;
+
+    #[test]
+    fn parses_process_types() {
+        let content = "# processes\nweb: ./server --port $PORT\n\n  worker-1 :  bin/work  \nclock_2: echo 'a: b'\n";
+        let processes = parse_procfile(content).unwrap();
+        assert_eq!(processes.len(), 3);
+        assert_eq!(processes["web"], "./server --port $PORT");
+        assert_eq!(processes["worker-1"], "bin/work");
+        assert_eq!(processes["clock_2"], "echo 'a: b'");
+    }
+
+    #[test]
+    fn later_lines_override_earlier_ones() {
+        assert_eq!(parse_procfile("web: a\nweb: b").unwrap()["web"], "b");
+    }
+
+    #[test]
+    fn empty_procfile_has_no_processes() {
+        assert!(parse_procfile("# nothing here\n\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let error = parse_procfile("web: ok\nno colon").unwrap_err().to_string();
+        assert!(error.contains("line 2"), "{}", error);
+        assert!(parse_procfile(": cmd").unwrap_err().to_string().contains("invalid process type"));
+        assert!(parse_procfile("my web: cmd").unwrap_err().to_string().contains("invalid process type"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Reading and writing `.env` files.
+//!
+//! Supported: `export` prefixes, `#` comments (inline ones need whitespace before the `#`),
+//! single-quoted values taken literally, and double-quoted values with `\n`, `\t`, `\"`,
+//! `\\` and `\$` escapes. Quoted values may span lines.
+
+use shared::is_valid_env_name;
+
+/// The `KEY=value` pairs of a `.env` file in file order. Later duplicates win when collected into a map.
+pub fn parse(content: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let mut vars = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+
+        let Some((key, rest)) = line.split_once('=') else {
+            anyhow::bail!(".env line {}: expected KEY=VALUE", n + 1);
+        };
+        let key = key.trim();
+        if !is_valid_env_name(key) {
+            anyhow::bail!(".env line {}: invalid name '{}'", n + 1, key);
+        }
+
+        let rest = rest.trim_start();
+        let (value, trailing) = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // Collect lines until the closing quote
+                let mut raw = rest[1..].to_string();
+                let (value, trailing) = loop {
+                    if let Some(parsed) = parse_quoted(&raw, quote) {
+                        break parsed;
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        anyhow::bail!(".env line {}: missing closing {} for {}", n + 1, quote, key);
+                    };
+                    raw.push('\n');
+                    raw.push_str(next);
+                };
+                (value, trailing)
+            }
+            _ => (unquoted_value(rest).to_string(), String::new()),
+        };
+
+        let trailing = trailing.trim();
+        if !trailing.is_empty() && !trailing.starts_with('#') {
+            anyhow::bail!(".env line {}: unexpected text after the value of {}", n + 1, key);
+        }
+        vars.push((key.to_string(), value));
+    }
+
+    Ok(vars)
+}
+
+/// A value up to an inline comment, without surrounding whitespace
+fn unquoted_value(rest: &str) -> &str {
+    let mut end = rest.len();
+    let mut prev_space = false;
+    for (i, c) in rest.char_indices() {
+        if c == '#' && prev_space {
+            end = i;
+            break;
+        }
+        prev_space = c.is_whitespace();
+    }
+    rest[..end].trim()
+}
+
+/// Unescape a quoted value up to its closing quote. Returns the value and whatever follows
+/// the quote, or None if the quote isn't closed yet.
+fn parse_quoted(raw: &str, quote: char) -> Option<(String, String)> {
+    let mut value = String::new();
+    let mut chars = raw.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((value, raw[i + 1..].to_string())),
+            // `${` is a reference on the platform; single quotes mean it's literal
+            '$' if quote == '\'' && chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                value.push_str("$$");
+            }
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, '"')) => value.push('"'),
+                Some((_, '\\')) => value.push('\\'),
+                Some((_, '$')) if chars.peek().is_some_and(|(_, next)| *next == '{') => value.push_str("$$"),
+                Some((_, '$')) => value.push('$'),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// `KEY=value`, double-quoting values that contain anything beyond a safe set of characters.
+/// `${...}` references are kept so they survive a round trip through `parse`.
+pub fn format_line(key: &str, value: &str) -> String {
+    let plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+%".contains(c));
+    if plain {
+        return format!("{}={}", key, value);
+    }
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+        .replace('\t', "\\t");
+    format!("{}=\"{}\"", key, escaped)
+}
+
+/// A whole `.env` file, one var per line
+pub fn format<'a>(vars: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    vars.into_iter()
+        .map(|(key, value)| format_line(key, value) + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, parse};
+
+    fn vars(content: &str) -> Vec<(String, String)> {
+        parse(content).unwrap()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn parses_plain_values_comments_and_export() {
+        let content = "# comment\n\nexport PORT=8080\nNAME = api # inline\nURL=http://x/#anchor\nEMPTY=\n";
+        assert_eq!(
+            vars(content),
+            vec![
+                pair("PORT", "8080"),
+                pair("NAME", "api"),
+                pair("URL", "http://x/#anchor"),
+                pair("EMPTY", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_double_quoted_values() {
+        let content = r#"MSG="a\nb\tc \"q\" \\ \$HOME \x""#;
+        assert_eq!(vars(content), vec![pair("MSG", "a\nb\tc \"q\" \\ $HOME \\x")]);
+    }
+
+    #[test]
+    fn single_quoted_values_are_literal() {
+        assert_eq!(vars(r"RAW='a\nb # not a comment'"), vec![pair("RAW", r"a\nb # not a comment")]);
+    }
+
+    #[test]
+    fn escaped_and_single_quoted_references_stay_literal() {
+        assert_eq!(
+            vars("A='${B}'\nC=\"\\${D}\"\nE=\"${F}\""),
+            vec![pair("A", "$${B}"), pair("C", "$${D}"), pair("E", "${F}")]
+        );
+    }
+
+    #[test]
+    fn quoted_values_span_lines() {
+        let content = "KEY=\"-----BEGIN\nabc\n-----END\" # pem\nNEXT=1";
+        assert_eq!(
+            vars(content),
+            vec![pair("KEY", "-----BEGIN\nabc\n-----END"), pair("NEXT", "1")]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse("NO_EQUALS").unwrap_err().to_string().contains("line 1"));
+        assert!(parse("1BAD=x").unwrap_err().to_string().contains("invalid name"));
+        assert!(parse("A=1\nB=\"open").unwrap_err().to_string().contains("missing closing"));
+        assert!(parse("A=\"x\" y").unwrap_err().to_string().contains("unexpected text"));
+    }
+
+    #[test]
+    fn format_round_trips_through_parse() {
+        let original = [
+            pair("PLAIN", "abc-1.2:3/4@5+6%"),
+            pair("SPACES", "hello world"),
+            pair("QUOTES", "say \"hi\" \\o/"),
+            pair("LINES", "one\ntwo\r\tthree"),
+            pair("REF", "${OTHER}"),
+        ];
+        let content = format(original.iter().map(|(k, v)| (k, v)));
+        assert!(content.starts_with("PLAIN=abc-1.2:3/4@5+6%\n"));
+        assert_eq!(vars(&content), original);
+    }
+}
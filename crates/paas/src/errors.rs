@@ -0,0 +1,273 @@
+//! Rendering failed commands and paasd requests, with a hint on what to do next and
+//! an exit code scripts can tell apart
+
+use std::fmt;
+
+use serde::Serialize;
+use shared::{ApiError, ErrorCode};
+
+use crate::output;
+
+/// Why a command failed. Each has its own exit code; clap exits with 2 on usage errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Failure {
+    /// Anything without a more specific code
+    Error,
+    /// There is no paas.toml; run `paas init`
+    NotInitialized,
+    /// The project hasn't been deployed, or there's no deployed app with the `--app` name
+    NotDeployed,
+    /// paasd couldn't be reached
+    ServerUnreachable,
+    /// Packaging, uploading, building or starting a deploy failed
+    DeployFailed,
+    /// The app crashed, or stopped right after starting
+    AppCrashed,
+}
+
+impl Failure {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Failure::Error => 1,
+            Failure::NotInitialized => 3,
+            Failure::NotDeployed => 4,
+            Failure::ServerUnreachable => 5,
+            Failure::DeployFailed => 6,
+            Failure::AppCrashed => 7,
+        }
+    }
+}
+
+/// A command that couldn't do what was asked
+#[derive(Debug)]
+pub struct Failed {
+    pub failure: Failure,
+    pub message: String,
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// Stop a command with `failure`'s exit code. `report` prints the message.
+pub fn fail(failure: Failure, message: impl Into<String>) -> anyhow::Error {
+    Failed {
+        failure,
+        message: message.into(),
+    }
+    .into()
+}
+
+/// A command that printed its result but still exits with `failure`'s code, e.g. `paas status`
+/// of a crashed app. `report` prints nothing for it.
+#[derive(Debug)]
+pub struct Exit(pub Failure);
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit code {}", self.0.exit_code())
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// A paasd request that failed, and what the CLI was trying to do
+#[derive(Debug)]
+pub struct RequestFailed {
+    pub action: String,
+    pub error: ApiError,
+    pub failure: Failure,
+}
+
+impl fmt::Display for RequestFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.action, self.error.message)
+    }
+}
+
+impl std::error::Error for RequestFailed {}
+
+/// A failed request as an error for `?`, rendered by `report` in main
+pub fn failed(action: &str, error: paas_client::Error) -> anyhow::Error {
+    failed_as(Failure::Error, action, error)
+}
+
+/// `failed`, exiting with `failure`'s code if paasd answered with an error
+pub fn failed_as(failure: Failure, action: &str, error: paas_client::Error) -> anyhow::Error {
+    match error {
+        paas_client::Error::Api(error) => RequestFailed {
+            action: action.to_string(),
+            error,
+            failure,
+        }
+        .into(),
+        error => anyhow::Error::new(error).context(action.to_string()),
+    }
+}
+
+/// What the user can do about an error
+fn hint(error: &ApiError) -> Option<String> {
+    let details = error.details.clone().unwrap_or_default();
+    match error.code {
+        ErrorCode::PortInUse => {
+            let free: Vec<String> = details["free_ports"]
+                .as_array()
+                .map(|ports| ports.iter().filter_map(|p| p.as_i64()).map(|p| p.to_string()).collect())
+                .unwrap_or_default();
+            Some(if free.is_empty() {
+                "Set `port = \"auto\"` in paas.toml to get a free port assigned.".to_string()
+            } else {
+                format!(
+                    "Free ports: {}. Set `port` in paas.toml to one of them, or `port = \"auto\"` to get one assigned.",
+                    free.join(", ")
+                )
+            })
+        }
+        ErrorCode::NoFreePort => Some(
+            "Destroy apps you no longer need with `paas destroy`, or widen PAAS_PORT_RANGE on the server.".to_string(),
+        ),
+        ErrorCode::NameTaken => {
+            let name = details["name"].as_str().unwrap_or("<name>");
+            Some(if details["destroyed"].as_bool().unwrap_or(false) {
+                format!(
+                    "Restore it with `paas apps restore {}`, or free the name with `paas apps purge {}`.",
+                    name, name
+                )
+            } else {
+                format!(
+                    "Change `name` in paas.toml, or manage the existing app with `--app {}`.",
+                    name
+                )
+            })
+        }
+        ErrorCode::NotFound => Some("Run `paas apps` to see the deployed apps.".to_string()),
+        ErrorCode::PayloadTooLarge => {
+            Some("Exclude build output and other large files with a .paasignore file.".to_string())
+        }
+        ErrorCode::AgentUnavailable => {
+            Some("Check that the agent is running at the `agent_url` paasd is configured with.".to_string())
+        }
+        ErrorCode::Internal | ErrorCode::Unknown => {
+            Some("This is a server problem; the paasd logs have the details.".to_string())
+        }
+        ErrorCode::InvalidRequest | ErrorCode::Conflict | ErrorCode::NotImplemented => None,
+    }
+}
+
+/// Print an error the way every command does: the message, a hint and the request id
+pub fn print_error(action: &str, error: &ApiError) {
+    eprintln!("{}: {}", action, error.message);
+    if let Some(hint) = hint(error) {
+        eprintln!("Hint: {}", hint);
+    }
+    if let Some(request_id) = &error.request_id {
+        eprintln!("Request id: {}", request_id);
+    }
+}
+
+/// Shown when paasd can't be reached
+const CONNECT_HINT: &str =
+    "Check that paasd is running, or pick another server with `paas context use`, --context or PAAS_SERVER.";
+
+/// Print a failed request, for commands that carry on or stop without an error
+pub fn report_failure(action: &str, error: &paas_client::Error) {
+    match error {
+        paas_client::Error::Api(error) => print_error(action, error),
+        error @ paas_client::Error::Connect(_) => {
+            eprintln!("{}: {}", action, error);
+            eprintln!("Hint: {}", CONNECT_HINT);
+        }
+        error => eprintln!("{}: {}", action, error),
+    }
+}
+
+fn failure_of(error: &anyhow::Error) -> Failure {
+    if let Some(failed) = error.downcast_ref::<Failed>() {
+        failed.failure
+    } else if let Some(failed) = error.downcast_ref::<RequestFailed>() {
+        failed.failure
+    } else if let Some(paas_client::Error::Connect(_)) = error.downcast_ref::<paas_client::Error>() {
+        Failure::ServerUnreachable
+    } else {
+        Failure::Error
+    }
+}
+
+/// `.context()` added on top of a `Failed` or `RequestFailed`, e.g. what wasn't saved because
+/// of it. Innermost first, so they read in the order they were added.
+fn notes(error: &anyhow::Error) -> Vec<String> {
+    let mut notes: Vec<String> = error
+        .chain()
+        .take_while(|e| !e.is::<Failed>() && !e.is::<RequestFailed>())
+        .map(|e| e.to_string())
+        .collect();
+    notes.reverse();
+    notes
+}
+
+/// The error with its causes. Below a `paas_client::Error` only the root cause is kept, since
+/// reqwest's causes repeat each other.
+fn message(error: &anyhow::Error) -> String {
+    let mut causes = Vec::new();
+    for cause in error.chain() {
+        causes.push(cause.to_string());
+        if cause.is::<paas_client::Error>() {
+            let root = error.root_cause().to_string();
+            if causes.last() != Some(&root) {
+                causes.push(root);
+            }
+            break;
+        }
+    }
+    causes.join(": ")
+}
+
+/// Print an error a command returned and give the exit code for it. With `--json` it is
+/// printed as `{"error": {...}}` on stdout, in place of the command's result.
+pub fn report(error: &anyhow::Error) -> i32 {
+    if let Some(Exit(failure)) = error.downcast_ref::<Exit>() {
+        return failure.exit_code();
+    }
+    let failure = failure_of(error);
+    if output::json() {
+        let api_error = error
+            .downcast_ref::<RequestFailed>()
+            .map(|failed| &failed.error)
+            .or_else(|| error.downcast_ref::<paas_client::Error>().and_then(|e| e.api()));
+        let hint = match api_error {
+            Some(api_error) => hint(api_error),
+            None if failure == Failure::ServerUnreachable => Some(CONNECT_HINT.to_string()),
+            None => None,
+        };
+        let body = serde_json::json!({
+            "error": {
+                "kind": failure,
+                "exit_code": failure.exit_code(),
+                "message": message(error),
+                "code": api_error.map(|e| e.code),
+                "request_id": api_error.and_then(|e| e.request_id.clone()),
+                "hint": hint,
+            }
+        });
+        println!("{}", serde_json::to_string_pretty(&body).unwrap_or_default());
+    } else if let Some(failed) = error.downcast_ref::<Failed>() {
+        eprintln!("{}", failed.message);
+        notes(error).iter().for_each(|note| eprintln!("{}", note));
+    } else if let Some(failed) = error.downcast_ref::<RequestFailed>() {
+        print_error(&failed.action, &failed.error);
+        notes(error).iter().for_each(|note| eprintln!("{}", note));
+    } else if let Some(paas_client::Error::Api(api_error)) = error.downcast_ref::<paas_client::Error>() {
+        print_error("Error", api_error);
+    } else if failure == Failure::ServerUnreachable {
+        eprintln!("Error: {}", message(error));
+        eprintln!("Hint: {}", CONNECT_HINT);
+    } else {
+        eprintln!("Error: {}", message(error));
+    }
+    failure.exit_code()
+}
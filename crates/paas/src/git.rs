@@ -0,0 +1,64 @@
+use std::path::Path;
+use std::process::Command;
+
+use shared::GitMetadata;
+
+use crate::source::SourceArchive;
+
+/// Run git in `dir`, returning trimmed stdout if it succeeded
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// HEAD commit, branch and dirty flag of the checkout in `dir`. None if it isn't a git checkout.
+pub fn head_metadata(dir: &Path) -> Option<GitMetadata> {
+    let commit = git(dir, &["rev-parse", "HEAD"])?;
+    // Detached HEAD has no branch
+    let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|b| b != "HEAD");
+    let dirty = git(dir, &["status", "--porcelain"]).map(|s| !s.is_empty());
+
+    Some(GitMetadata {
+        commit: Some(commit),
+        branch,
+        dirty,
+    })
+}
+
+/// Package the tree of `git_ref` with `git archive`. Uncommitted changes are not included,
+/// and `.paasignore` doesn't apply; exclude committed files with `export-ignore` in `.gitattributes`.
+pub fn archive_ref(dir: &Path, git_ref: &str) -> anyhow::Result<(SourceArchive, GitMetadata)> {
+    let commit = git(dir, &["rev-parse", "--verify", &format!("{}^{{commit}}", git_ref)])
+        .ok_or_else(|| anyhow::anyhow!("'{}' is not a commit in this git repository", git_ref))?;
+
+    let output = Command::new("git")
+        .args(["archive", "--format=tar.gz", &commit])
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git archive failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let files = git(dir, &["ls-tree", "-r", "--name-only", &commit])
+        .map(|list| list.lines().count())
+        .unwrap_or(0);
+    let branch = git(dir, &["show-ref", "--verify", "--quiet", &format!("refs/heads/{}", git_ref)])
+        .map(|_| git_ref.to_string());
+
+    let archive = SourceArchive {
+        bytes: output.stdout,
+        files,
+    };
+    let metadata = GitMetadata {
+        commit: Some(commit),
+        branch,
+        dirty: Some(false),
+    };
+    Ok((archive, metadata))
+}
@@ -1,5 +1,5 @@
 use crate::{
-    cli::{Commands, EnvAction, parse_cli},
+    cli::{AppsAction, Commands, ContextAction, CronAction, EnvAction, PsAction, SecretsAction, StackAction, parse_cli},
     commands::{
         deploy::deploy_project, init::init_project, logs::show_logs,
         redeploy::redeploy_project, status::check_status, stop::stop_application,
@@ -8,22 +8,124 @@ use crate::{
 
 mod cli;
 mod commands;
+mod config;
+mod dotenv;
+mod errors;
+mod git;
+mod output;
+mod source;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        std::process::exit(errors::report(&e));
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
     let args = parse_cli();
+    output::select_json(args.json);
+    // `paas context` has to work even when the current context is broken
+    if !matches!(args.command, Commands::Context { .. }) {
+        config::connect(args.context.clone())?;
+    }
+    config::select_environment(args.environment);
+    if let Some(app) = &args.app
+        && !args.command.accepts_app()
+    {
+        return Err(errors::fail(
+            errors::Failure::Error,
+            format!("--app {} can't be used with this command; run it in the project directory instead.", app),
+        ));
+    }
+    let named_app = args.app.is_some();
+    config::select_app(args.app);
 
     match args.command {
         Commands::Init => init_project(),
-        Commands::Deploy => deploy_project().await,
-        Commands::Redeploy => redeploy_project().await,
+        Commands::Deploy { git_ref } => deploy_project(git_ref).await,
+        Commands::Redeploy { git_ref } => redeploy_project(git_ref).await,
         Commands::Status => check_status().await,
-        Commands::Logs { follow } => show_logs(follow).await,
+        Commands::Apps { action: Some(AppsAction::Restore { name }), .. } => {
+            commands::apps::restore_app(name).await
+        }
+        Commands::Apps { action: Some(AppsAction::Purge { name, yes }), .. } => {
+            commands::apps::purge_app(name, yes).await
+        }
+        Commands::Apps { deleted: true, .. } => commands::apps::list_deleted_apps().await,
+        Commands::Apps { status, labels, sort, desc, limit, offset, .. } => {
+            commands::apps::list_apps(commands::apps::AppListOptions {
+                status,
+                labels,
+                sort,
+                desc,
+                limit,
+                offset,
+            })
+            .await
+        }
+        Commands::Releases => commands::releases::list_releases().await,
+        Commands::Promote { from, to } => commands::promote::promote(from, to).await,
+        Commands::GitRemote { remote, ssh } => commands::git_remote::add_git_remote(remote, ssh).await,
+        Commands::Logs { follow, process } => show_logs(follow, process).await,
+        Commands::Ps { action } => match action {
+            None => commands::ps::list_processes().await,
+            Some(PsAction::Scale { assignments }) => commands::ps::scale_processes(assignments).await,
+            Some(PsAction::Restart { process_type }) => {
+                commands::ps::restart_processes(process_type).await
+            }
+        },
         Commands::Stop => stop_application().await,
+        Commands::Destroy { yes, purge } => commands::destroy::destroy_app(yes, purge).await,
+        Commands::Run { command } => commands::run::run_command(command).await,
         Commands::Env { action } => match action {
-            EnvAction::Set { key_value } => commands::env_cmd::env_set(key_value),
+            // With --app there is no paas.toml to edit, so changes go to the server
+            EnvAction::Set { key_value, remote, restart } if remote || named_app => {
+                commands::env_cmd::env_set_remote(key_value, restart).await
+            }
+            EnvAction::Set { restart: true, .. } => {
+                Err(errors::fail(
+                    errors::Failure::Error,
+                    "--restart only applies to server-side changes, add --remote.",
+                ))
+            }
+            EnvAction::Set { key_value, .. } => commands::env_cmd::env_set(key_value),
+            EnvAction::List if named_app => commands::env_cmd::env_list_remote().await,
             EnvAction::List => commands::env_cmd::env_list(),
-            EnvAction::Remove { key } => commands::env_cmd::env_remove(key),
+            EnvAction::Remove { key, remote, restart } if remote || named_app => {
+                commands::env_cmd::env_remove_remote(key, restart).await
+            }
+            EnvAction::Remove { restart: true, .. } => {
+                Err(errors::fail(
+                    errors::Failure::Error,
+                    "--restart only applies to server-side changes, add --remote.",
+                ))
+            }
+            EnvAction::Remove { key, .. } => commands::env_cmd::env_remove(key),
+            EnvAction::Pull { toml } => commands::env_cmd::env_pull(toml).await,
+            EnvAction::Import { file } => commands::env_cmd::env_import(file),
+            EnvAction::Export => commands::env_cmd::env_export(),
+            EnvAction::Push { yes, restart } => commands::env_cmd::env_push(yes, restart).await,
+        },
+        Commands::Secrets { action } => match action {
+            SecretsAction::Set { key_value } => commands::secrets::secrets_set(key_value).await,
+            SecretsAction::List => commands::secrets::secrets_list().await,
+            SecretsAction::Rm { key } => commands::secrets::secrets_remove(key).await,
+        },
+        Commands::Cron { action } => match action {
+            CronAction::List => commands::cron::list_schedules().await,
+            CronAction::RunNow { name } => commands::cron::run_schedule_now(name).await,
+            CronAction::History { name } => commands::cron::schedule_history(name).await,
+        },
+        Commands::Stack { action } => match action {
+            StackAction::Up => commands::stack::stack_up().await,
+            StackAction::Down => commands::stack::stack_down().await,
+            StackAction::Status => commands::stack::stack_status().await,
+        },
+        Commands::Context { action } => match action {
+            ContextAction::Add { name, server, token } => commands::context::add_context(name, server, token),
+            ContextAction::Use { name } => commands::context::use_context(name),
+            ContextAction::List => commands::context::list_contexts(args.context),
         },
     }
 }
@@ -0,0 +1,50 @@
+//! How commands print: text for people, or with `--json` a single JSON document on stdout.
+//! With `--json`, progress meant for people moves to stderr so stdout stays parseable.
+
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+
+/// Whether `--json` was given, set once at startup
+static JSON: OnceLock<bool> = OnceLock::new();
+
+pub fn select_json(json: bool) {
+    let _ = JSON.set(json);
+}
+
+pub fn json() -> bool {
+    JSON.get().copied().unwrap_or(false)
+}
+
+/// `println!` for text that isn't the command's result, e.g. progress. Goes to stderr with `--json`.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if $crate::output::json() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+pub(crate) use say;
+
+/// Print a command's result as JSON on stdout
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Prompt for a line on stdin. Like `say!`, the prompt goes to stderr with `--json`.
+pub fn ask(prompt: &str) -> io::Result<String> {
+    if json() {
+        eprint!("{}", prompt);
+        io::stderr().flush()?;
+    } else {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+    }
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use flate2::{Compression, write::GzEncoder};
+use ignore::WalkBuilder;
+use shared::{GitMetadata, Release};
+use uuid::Uuid;
+
+use crate::config::client;
+use crate::errors::{Failure, failed_as};
+use crate::git;
+
+/// Extra ignore file read alongside `.gitignore`, for files that are committed but shouldn't be deployed
+const PAAS_IGNORE: &str = ".paasignore";
+
+/// A gzipped tarball of the project
+pub struct SourceArchive {
+    pub bytes: Vec<u8>,
+    pub files: usize,
+}
+
+/// Package the project directory, skipping anything matched by `.gitignore`, `.paasignore` and `.git` itself
+pub fn package_source(root: &Path) -> anyhow::Result<SourceArchive> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    archive.follow_symlinks(false);
+    let mut files = 0;
+
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .add_custom_ignore_filename(PAAS_IGNORE)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    for entry in walker {
+        let entry = entry?;
+        let is_file = entry
+            .file_type()
+            .is_some_and(|t| t.is_file() || t.is_symlink());
+        if !is_file {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(root)?;
+        archive.append_path_with_name(entry.path(), relative)?;
+        files += 1;
+    }
+
+    let bytes = archive.into_inner()?.finish()?;
+    Ok(SourceArchive { bytes, files })
+}
+
+/// Package what should be deployed: `git_ref` from git history if given, otherwise the
+/// directory as it is on disk, along with the git state it corresponds to
+pub fn prepare_source(
+    root: &Path,
+    git_ref: Option<&str>,
+) -> anyhow::Result<(SourceArchive, Option<GitMetadata>)> {
+    match git_ref {
+        Some(git_ref) => {
+            let (archive, metadata) = git::archive_ref(root, git_ref)?;
+            Ok((archive, Some(metadata)))
+        }
+        None => Ok((package_source(root)?, git::head_metadata(root))),
+    }
+}
+
+/// Upload a source archive as a new release of the app
+pub async fn upload_source(
+    app_id: Uuid,
+    archive: &SourceArchive,
+    git: Option<&GitMetadata>,
+) -> anyhow::Result<Release> {
+    client()
+        .upload_release(app_id, archive.bytes.clone(), git)
+        .await
+        .map_err(|e| failed_as(Failure::DeployFailed, "Source upload failed", e))
+}
@@ -0,0 +1,125 @@
+//! Lets every `/apps/{app_id}/...` route take an app's name instead of its id.
+//! The name is looked up and replaced by the id before routing, so handlers only ever see ids.
+
+use std::future::{Ready, ready};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::http::{Method, Uri};
+use actix_web::http::uri::PathAndQuery;
+use actix_web::{Error, web};
+use futures_util::future::LocalBoxFuture;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repository::app_repo::get_app_id_by_name;
+
+/// The name in an `/apps/<name>/...` path, unless the segment is already an id
+fn app_name(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("/apps/")?;
+    let segment = rest.split('/').next().filter(|s| !s.is_empty())?;
+    Uuid::parse_str(segment).is_err().then_some(segment)
+}
+
+/// Restoring (`POST /apps/<name>/restore`) and purging (`DELETE /apps/<name>`) are the only
+/// requests that address a destroyed app; for everything else it's gone until restored
+fn addresses_destroyed_app(method: &Method, path: &str) -> bool {
+    let Some(rest) = path.strip_prefix("/apps/") else {
+        return false;
+    };
+    match rest.split_once('/') {
+        Some((_, "restore")) => method == Method::POST,
+        None => method == Method::DELETE,
+        _ => false,
+    }
+}
+
+/// `uri` with its app name replaced by `app_id`
+fn with_app_id(uri: &Uri, name: &str, app_id: Uuid) -> Option<Uri> {
+    let path = uri.path().replacen(&format!("/apps/{}", name), &format!("/apps/{}", app_id), 1);
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::from_str(&path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+pub struct ResolveAppNames;
+
+impl<S, B> Transform<S, ServiceRequest> for ResolveAppNames
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ResolveAppNamesMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ResolveAppNamesMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct ResolveAppNamesMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ResolveAppNamesMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            let name = app_name(req.path()).map(str::to_string);
+            let pool = req.app_data::<web::Data<PgPool>>().cloned();
+            // Unknown names are left alone; the route then fails to parse the id and returns 404
+            if let (Some(name), Some(pool)) = (name, pool) {
+                let include_deleted = addresses_destroyed_app(req.method(), req.path());
+                match get_app_id_by_name(pool.get_ref(), &name, include_deleted).await {
+                    Ok(Some(app_id)) => {
+                        if let Some(uri) = with_app_id(req.uri(), &name, app_id) {
+                            req.match_info_mut().get_mut().update(&uri);
+                            req.head_mut().uri = uri;
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => eprintln!("DB Error resolving app name {}: {}", name, e),
+                }
+            }
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::Method;
+
+    use super::addresses_destroyed_app;
+
+    #[test]
+    fn only_restore_and_purge_address_destroyed_apps() {
+        assert!(addresses_destroyed_app(&Method::POST, "/apps/api/restore"));
+        assert!(addresses_destroyed_app(&Method::DELETE, "/apps/api"));
+        assert!(!addresses_destroyed_app(&Method::GET, "/apps/api"));
+        assert!(!addresses_destroyed_app(&Method::GET, "/apps/api/logs"));
+        assert!(!addresses_destroyed_app(&Method::GET, "/apps/api/secrets"));
+        assert!(!addresses_destroyed_app(&Method::DELETE, "/apps/api/secrets/TOKEN"));
+        assert!(!addresses_destroyed_app(&Method::POST, "/discovery/api"));
+    }
+}
@@ -0,0 +1,109 @@
+//! paasd's settings: `paasd.toml` in the working directory, or the file named by `PAASD_CONFIG`,
+//! with env vars taking precedence. Every setting has a default, so the file is optional.
+
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Address the API listens on, e.g. `0.0.0.0:8080`. `PAASD_LISTEN`
+    pub listen: String,
+    /// How the agent and git hooks reach paasd; `http://<listen>` if unset. `PAASD_URL`
+    pub url: Option<String>,
+    /// The agent that runs the apps. `PAAS_AGENT_URL`
+    pub agent_url: String,
+    /// Seconds an agent request may take. Builds get their own build timeout on top. `PAASD_AGENT_TIMEOUT`
+    pub agent_timeout: u64,
+    /// `DATABASE_URL`, also read from `.env`
+    pub database_url: Option<String>,
+    /// `PAASD_DB_MAX_CONNECTIONS`
+    pub db_max_connections: u32,
+    /// Seconds a request waits for a free database connection. `PAASD_DB_TIMEOUT`
+    pub db_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            listen: "127.0.0.1:8080".to_string(),
+            url: None,
+            agent_url: "http://127.0.0.1:8001".to_string(),
+            agent_timeout: 30,
+            database_url: None,
+            db_max_connections: 10,
+            db_timeout: 30,
+        }
+    }
+}
+
+impl Config {
+    /// paasd's own URL, without a trailing `/`
+    pub fn url(&self) -> String {
+        match &self.url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!("http://{}", self.listen),
+        }
+    }
+
+    /// The URL of an agent endpoint, e.g. `agent_url("/run")`
+    pub fn agent_url(&self, path: &str) -> String {
+        format!("{}{}", self.agent_url.trim_end_matches('/'), path)
+    }
+
+    pub fn agent_timeout(&self) -> Duration {
+        Duration::from_secs(self.agent_timeout)
+    }
+
+    pub fn db_timeout(&self) -> Duration {
+        Duration::from_secs(self.db_timeout)
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Read the config file and env vars. Called once at startup, before `config()` is used.
+pub fn load() -> Result<&'static Config, String> {
+    let mut config = match env::var("PAASD_CONFIG") {
+        Ok(path) => read(Path::new(&path))?,
+        Err(_) if Path::new("paasd.toml").exists() => read(Path::new("paasd.toml"))?,
+        Err(_) => Config::default(),
+    };
+
+    override_with(&mut config.listen, "PAASD_LISTEN")?;
+    if let Ok(url) = env::var("PAASD_URL") {
+        config.url = Some(url);
+    }
+    override_with(&mut config.agent_url, "PAAS_AGENT_URL")?;
+    override_with(&mut config.agent_timeout, "PAASD_AGENT_TIMEOUT")?;
+    if let Ok(database_url) = env::var("DATABASE_URL") {
+        config.database_url = Some(database_url);
+    }
+    override_with(&mut config.db_max_connections, "PAASD_DB_MAX_CONNECTIONS")?;
+    override_with(&mut config.db_timeout, "PAASD_DB_TIMEOUT")?;
+
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// The settings `load` read at startup
+pub fn config() -> &'static Config {
+    CONFIG.get().expect("config::load runs at startup")
+}
+
+fn read(path: &Path) -> Result<Config, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    toml::from_str(&content).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+}
+
+/// Replace a setting with the value of `var`, if it is set
+fn override_with<T: FromStr>(setting: &mut T, var: &str) -> Result<(), String> {
+    if let Ok(value) = env::var(var) {
+        *setting = value.parse().map_err(|_| format!("Invalid {}: '{}'", var, value))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use uuid::Uuid;
+
+/// Branches whose pushes are deployed
+pub const DEPLOY_BRANCHES: [&str; 2] = ["main", "master"];
+
+/// Root directory of the hosted bare repositories
+fn repos_root() -> PathBuf {
+    match std::env::var("PAAS_GIT_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => std::env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir())
+            .join(".paas")
+            .join("git"),
+    }
+}
+
+pub fn repo_path(app_id: Uuid) -> PathBuf {
+    repos_root().join(format!("{}.git", app_id))
+}
+
+/// Create the app's bare repository if needed and (re)install its post-receive hook.
+/// Returns the absolute path of the repository.
+pub fn ensure_repo(app_id: Uuid, paasd_url: &str) -> Result<PathBuf, String> {
+    let path = repo_path(app_id);
+    if !path.join("HEAD").exists() {
+        std::fs::create_dir_all(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let output = Command::new("git")
+            .args(["init", "--bare", "--quiet"])
+            .current_dir(&path)
+            .output()
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!(
+                "git init failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+    }
+
+    let hook = path.join("hooks").join("post-receive");
+    std::fs::create_dir_all(path.join("hooks")).map_err(|e| format!("Failed to create hooks dir: {}", e))?;
+    std::fs::write(&hook, post_receive_hook(app_id, paasd_url))
+        .map_err(|e| format!("Failed to write post-receive hook: {}", e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| format!("Failed to make hook executable: {}", e))?;
+    }
+
+    path.canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+}
+
+/// The hook hands each pushed deploy branch to paasd, which streams the deploy back.
+/// Git shows that output to the pusher prefixed with `remote:`.
+fn post_receive_hook(app_id: Uuid, paasd_url: &str) -> String {
+    let branches = DEPLOY_BRANCHES
+        .iter()
+        .map(|b| format!("refs/heads/{}", b))
+        .collect::<Vec<_>>()
+        .join("|");
+    format!(
+        r#"#!/bin/sh
+# Installed by paasd. Deploys pushes to {names}.
+while read oldrev newrev refname; do
+    case "$refname" in
+        {branches}) ;;
+        *) echo "Not deploying $refname, push {names} to deploy"; continue ;;
+    esac
+    if [ "$newrev" = "0000000000000000000000000000000000000000" ]; then
+        continue
+    fi
+    curl -sS -N -X POST -H "Content-Type: application/json" \
+        -d "{{\"commit\": \"$newrev\", \"branch\": \"${{refname#refs/heads/}}\"}}" \
+        "{url}/apps/{app_id}/git/deploy" || echo "Deploy failed: cannot reach paasd at {url}"
+done
+"#,
+        names = DEPLOY_BRANCHES.join(" or "),
+        branches = branches,
+        url = paasd_url,
+        app_id = app_id,
+    )
+}
+
+/// Gzipped tarball of a commit in the app's repository
+pub fn archive_commit(app_id: Uuid, commit: &str) -> Result<Vec<u8>, String> {
+    let output = Command::new("git")
+        .args(["archive", "--format=tar.gz", commit])
+        .current_dir(repo_path(app_id))
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git archive failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
@@ -1,18 +1,52 @@
+use crate::handlers::discovery_handlers::{check_links, notify_linked_apps};
+use crate::handlers::env_handlers::check_env_references;
+use crate::config::config;
+use crate::git_host;
 use crate::models::{Application, AppStatus, PatchApplication};
+use crate::ports::{host_check, is_bindable, port_range};
 use crate::repository::app_repo::{
-    clear_pid, delete_application, get_application, get_applications, insert_application,
-    is_port_in_use, patch_application,
+    AppListQuery, clear_pid, delete_application, get_application, get_application_by_name,
+    get_deleted_application, get_expired_applications, get_taken_ports,
+    insert_application_on_port, insert_application_with_free_port, is_name_taken, list_applications, mark_deployed,
+    move_application_to_port, patch_application, restore_application, soft_delete_application, PortConflict,
 };
+use crate::repository::log_repo::insert_log;
+use crate::repository::process_repo::{
+    delete_process, get_processes, insert_process, reset_processes, update_process_command,
+};
+use crate::repository::release_repo::get_release;
+use crate::secrets::app_secrets;
 use actix_web::{HttpResponse, Responder, web};
-use reqwest::Client;
+use futures_util::future::join_all;
+use serde::Deserialize;
+use shared::{
+    ApiError, AppDetails, AppLiveStatus, AppProcess, AppSummary, BUILD_PROCESS, BuildResult, CreatedApp,
+    DEFAULT_BUILD_TIMEOUT_SECS, DeletedApp, ErrorCode, NewAppLog, RedeployApp, Redeployed, Release, RunBuild,
+    RunProcess, WEB_PROCESS,
+};
 use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 use uuid::Uuid;
 
-async fn kill_app(pid: i32) {
-    let client = Client::new();
+/// The HTTP client for requests to the agent, with the configured `agent_timeout`
+pub(crate) fn agent() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(config().agent_timeout())
+            .build()
+            .expect("failed to initialize the HTTP client")
+    })
+}
+
+pub(crate) async fn kill_app(pid: i32) {
     let body = serde_json::json!({ "pid": pid });
-    if let Err(e) = client
-        .post("http://127.0.0.1:8001/stop")
+    if let Err(e) = agent()
+        .post(config().agent_url("/stop"))
         .json(&body)
         .send()
         .await
@@ -21,105 +55,660 @@ async fn kill_app(pid: i32) {
     }
 }
 
-async fn start_app(app: Application) {
-    let client = Client::new();
-    if let Err(e) = client
-        .post("http://127.0.0.1:8001/run")
-        .json(&app)
+/// How long to wait for a killed process to be gone before giving up on it
+const EXIT_WAIT: Duration = Duration::from_secs(10);
+
+/// Poll the agent until the process has exited. Returns false if it is still alive after `EXIT_WAIT`.
+pub(crate) async fn wait_for_exit(pid: i32) -> bool {
+    let deadline = tokio::time::Instant::now() + EXIT_WAIT;
+    loop {
+        let alive = match agent()
+            .get(config().agent_url(&format!("/status/{}", pid)))
+            .send()
+            .await
+        {
+            Ok(res) => match res.json::<serde_json::Value>().await {
+                Ok(body) => body["status"] == "RUNNING",
+                Err(_) => true,
+            },
+            Err(_) => true,
+        };
+        if !alive {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// The error an agent request failed with, from its `ApiError` body if it has one
+pub(crate) async fn agent_error(res: reqwest::Response) -> String {
+    let status = res.status();
+    match res.json::<ApiError>().await {
+        Ok(error) => format!("Agent error: {} ({})", error.message, status),
+        Err(_) => format!("Agent error: {}", status),
+    }
+}
+
+/// The app, or the 404 (also for destroyed apps) or 500 response to return instead
+pub(crate) async fn load_app(pool: &PgPool, app_id: Uuid) -> Result<Application, HttpResponse> {
+    match get_application(pool, app_id).await {
+        Ok(app) => Ok(app),
+        Err(sqlx::Error::RowNotFound) => Err(ApiError::not_found("Application not found").response()),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            Err(ApiError::internal().response())
+        }
+    }
+}
+
+/// Ask the agent to start a single process instance of an app
+pub(crate) async fn start_process(pool: &PgPool, app: &Application, process: AppProcess) -> Result<(), String> {
+    let secrets = app_secrets(pool, process.app_id).await?;
+    let payload = RunProcess {
+        app: app.clone(),
+        process,
+        secrets,
+    };
+    match agent()
+        .post(config().agent_url("/run"))
+        .json(&payload)
         .send()
         .await
     {
-        eprintln!("Failed to start app: {}", e);
+        Ok(res) if res.status().is_success() => Ok(()),
+        Ok(res) => Err(agent_error(res).await),
+        Err(e) => Err(format!("Cannot reach agent: {}", e)),
     }
 }
 
-pub async fn post_program(pool: web::Data<PgPool>, app: web::Json<Application>) -> impl Responder {
-    println!("{:?}", app);
+/// Process types of an app mapped to their commands. The app's `command` is always the `web` process.
+pub(crate) fn process_formation(app: &Application) -> BTreeMap<String, String> {
+    let mut formation: BTreeMap<String, String> = app
+        .processes
+        .as_ref()
+        .and_then(|p| p.as_object())
+        .map(|map| {
+            map.iter()
+                .filter_map(|(name, cmd)| cmd.as_str().map(|c| (name.clone(), c.to_string())))
+                .collect()
+        })
+        .unwrap_or_default();
+    formation.insert(WEB_PROCESS.to_string(), app.command.clone());
+    formation
+}
+
+/// Add, update and remove process rows so they match the app's formation.
+/// Existing instances (and their scale) are kept for types that still exist.
+async fn sync_processes(pool: &PgPool, app: &Application) -> Result<Vec<AppProcess>, sqlx::Error> {
+    let app_id = app.id.expect("app must be saved before syncing processes");
+    let formation = process_formation(app);
+    let existing = get_processes(pool, app_id).await?;
+
+    for process in &existing {
+        match formation.get(&process.process_type) {
+            Some(command) if command != &process.command => {
+                update_process_command(pool, app_id, &process.process_type, command).await?;
+            }
+            Some(_) => {}
+            None => {
+                if let Some(pid) = process.pid {
+                    kill_app(pid).await;
+                }
+                delete_process(pool, process.id).await?;
+            }
+        }
+    }
 
-    // Check for port conflicts
-    match is_port_in_use(pool.get_ref(), app.port).await {
-        Ok(true) => {
-            eprintln!("Port {} is already in use by another running app", app.port);
-            return HttpResponse::Conflict().body(format!(
-                "Port {} is already in use by another running application",
-                app.port
-            ));
+    for (process_type, command) in &formation {
+        if !existing.iter().any(|p| &p.process_type == process_type) {
+            insert_process(pool, app_id, process_type, 1, command).await?;
         }
+    }
+
+    get_processes(pool, app_id).await
+}
+
+/// The app's build command, if it has a non-empty one
+pub(crate) fn build_command(app: &Application) -> Option<&str> {
+    app.build_command
+        .as_deref()
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+}
+
+async fn request_build(pool: &PgPool, app: &Application, app_id: Uuid) -> Result<BuildResult, String> {
+    let payload = RunBuild {
+        app: app.clone(),
+        secrets: app_secrets(pool, app_id).await?,
+    };
+    // The agent answers once the build is done, so allow for the build's own timeout
+    let build_timeout = app.build_timeout.unwrap_or(DEFAULT_BUILD_TIMEOUT_SECS).max(1) as u64;
+    match agent()
+        .post(config().agent_url("/build"))
+        .timeout(config().agent_timeout() + Duration::from_secs(build_timeout))
+        .json(&payload)
+        .send()
+        .await
+    {
+        Ok(res) if res.status().is_success() => res
+            .json::<BuildResult>()
+            .await
+            .map_err(|e| format!("Invalid build result from agent: {}", e)),
+        Ok(res) => Err(agent_error(res).await),
+        Err(e) => Err(format!("Cannot reach agent: {}", e)),
+    }
+}
+
+/// Run the app's build on the agent. The agent streams the build output to the logs;
+/// the returned error describes why the build didn't succeed.
+async fn run_build(pool: &PgPool, app: &Application) -> Result<(), String> {
+    let app_id = app.id.expect("app must be saved before building");
+    match request_build(pool, app, app_id).await {
+        Ok(result) if result.success => Ok(()),
+        Ok(result) if result.timed_out => Err("Build timed out".to_string()),
+        Ok(result) => Err(format!(
+            "Build failed with exit code {}",
+            result.exit_code.map(|c| c.to_string()).unwrap_or("unknown".into())
+        )),
         Err(e) => {
-            eprintln!("DB Error checking port: {}", e);
-            return HttpResponse::InternalServerError().finish();
+            let log = NewAppLog {
+                app_id,
+                stream: "stderr".to_string(),
+                message: format!("[PaaS] Build could not run: {}", e),
+                process_type: Some(BUILD_PROCESS.to_string()),
+                run_id: None,
+            };
+            if let Err(db_err) = insert_log(pool, &log).await {
+                eprintln!("DB Error inserting log: {}", db_err);
+            }
+            Err(e)
         }
-        Ok(false) => {} // Port is free, continue
     }
+}
 
-    match insert_application(pool.get_ref(), &app).await {
-        Ok(app_id) => {
-            println!("Application saved. Starting agent...");
+/// Stop whatever is running for the app and start every process of its formation
+pub(crate) async fn restart_app(pool: &PgPool, app: &Application) -> Result<(), String> {
+    let app_id = app.id.expect("app must be saved before starting");
 
-            let client = Client::new();
+    // Kill the old processes if running. They are marked STOPPED first and waited for, so the
+    // agent's exit watcher doesn't take the kill for a crash and respawn the old version.
+    let old_processes = get_processes(pool, app_id).await.unwrap_or_default();
+    let mut pids: Vec<i32> = old_processes.iter().filter_map(|p| p.pid).collect();
+    let current_pid = get_application(pool, app_id).await.ok().and_then(|a| a.pid);
+    pids.extend(current_pid);
+    pids.sort_unstable();
+    pids.dedup();
+    reset_processes(pool, app_id, AppStatus::STOPPED)
+        .await
+        .map_err(|e| format!("Failed to stop processes: {}", e))?;
+    for &pid in &pids {
+        kill_app(pid).await;
+    }
+    for pid in pids {
+        if !wait_for_exit(pid).await {
+            return Err(format!("Process {} did not exit", pid));
+        }
+    }
 
-            let agent_url = "http://127.0.0.1:8001/run";
+    // Clear PID explicitly and reset status to PENDING
+    clear_pid(pool, app_id)
+        .await
+        .map_err(|e| format!("Failed to clear PID: {}", e))?;
+    let patch = PatchApplication {
+        status: Some(AppStatus::PENDING),
+        ..Default::default()
+    };
+    patch_application(pool, app_id, &patch)
+        .await
+        .map_err(|e| format!("Failed to reset app status: {}", e))?;
 
-            // Send full application data to agent
-            let mut app_with_id = app.into_inner();
-            app_with_id.id = Some(app_id);
+    let processes = sync_processes(pool, app)
+        .await
+        .map_err(|e| format!("Failed to sync processes: {}", e))?;
+    reset_processes(pool, app_id, AppStatus::PENDING)
+        .await
+        .map_err(|e| format!("Failed to reset processes: {}", e))?;
 
-            let agent_response = client.post(agent_url).json(&app_with_id).send().await;
+    // Start fresh processes
+    for process in processes {
+        start_process(pool, app, process).await?;
+    }
+    Ok(())
+}
 
-            match agent_response {
-                Ok(res) if res.status().is_success() => {
-                    println!("Agent started application.");
-                    HttpResponse::Ok().json(serde_json::json!({
-                        "id": app_id,
-                        "port": app_with_id.port,
-                    }))
-                }
+/// Build the app, then (re)start it. If the build fails the app is set to `on_failure`,
+/// so a redeploy leaves the previous version running and a first deploy ends up FAILED.
+/// Returns whether the build succeeded.
+async fn build_then_restart(pool: PgPool, app: Application, on_failure: AppStatus) -> bool {
+    let app_id = app.id.expect("app must be saved before building");
 
-                Ok(res) => {
-                    eprintln!("Agent error: {}", res.status());
-                    HttpResponse::InternalServerError()
-                        .body("Application saved but failed to start")
+    if let Err(e) = run_build(&pool, &app).await {
+        eprintln!("Build of {} failed: {}", app.name, e);
+        let patch = PatchApplication {
+            status: Some(on_failure.clone()),
+            ..Default::default()
+        };
+        if let Err(e) = patch_application(&pool, app_id, &patch).await {
+            eprintln!("DB Error: {}", e);
+        }
+        if matches!(on_failure, AppStatus::FAILED)
+            && let Err(e) = reset_processes(&pool, app_id, AppStatus::FAILED).await
+        {
+            eprintln!("DB Error: {}", e);
+        }
+        return false;
+    }
+
+    // `paas stop` during the build cancels the deploy
+    if let Ok(current) = get_application(&pool, app_id).await
+        && matches!(current.status, AppStatus::STOPPED)
+    {
+        println!("{} was stopped while building, not starting it", app.name);
+        return true;
+    }
+
+    if let Err(e) = restart_app(&pool, &app).await {
+        eprintln!("Failed to start {} after build: {}", app.name, e);
+    }
+    true
+}
+
+#[utoipa::path(
+    post,
+    path = "/apps",
+    tag = "apps",
+    request_body = Application,
+    responses(
+        (status = 200, description = "The app was created and is starting or building", body = CreatedApp),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 409, description = "The name or port is taken", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
+pub async fn post_program(pool: web::Data<PgPool>, app: web::Json<Application>) -> impl Responder {
+    println!("{:?}", app);
+
+    let mut app = app.into_inner();
+    if let Err(res) = check_name(&app.name) {
+        return res;
+    }
+    if let Err(res) = check_links(&app) {
+        return res;
+    }
+    if let Err(res) = check_labels(&app) {
+        return res;
+    }
+    if let Err(res) = check_env_references(pool.get_ref(), &app, &app.env_map()).await {
+        return res;
+    }
+    if build_command(&app).is_some() && !app.working_dir.is_empty() {
+        app.status = AppStatus::BUILDING;
+    }
+
+    let inserted = if app.port == 0 {
+        // Allocate a port from the managed range
+        let range = port_range();
+        match insert_application_with_free_port(pool.get_ref(), &app, range.clone(), is_bindable).await {
+            Ok(Some((app_id, port))) => {
+                println!("Allocated port {} to {}", port, app.name);
+                app.port = port;
+                Ok(app_id)
+            }
+            Ok(None) => {
+                eprintln!("No free port left in {:?}", range);
+                return no_free_port(&range, format!("No free port left in {}-{}", range.start(), range.end()));
+            }
+            Err(e) => Err(e),
+        }
+    } else {
+        // The port must be free of other apps and of anything else on the host
+        match insert_application_on_port(pool.get_ref(), &app, is_bindable).await {
+            Ok(Ok(app_id)) => Ok(app_id),
+            Ok(Err(conflict)) => return port_taken(pool.get_ref(), app.port, conflict).await,
+            Err(e) => Err(e),
+        }
+    };
+
+    match inserted {
+        Ok(app_id) => {
+            let mut app_with_id = app;
+            app_with_id.id = Some(app_id);
+
+            let processes = match sync_processes(pool.get_ref(), &app_with_id).await {
+                Ok(processes) => processes,
+                Err(e) => {
+                    eprintln!("DB Error creating processes: {}", e);
+                    return ApiError::internal().response();
                 }
+            };
+
+            // Source deploys upload a release first; `redeploy` with its release_id starts the app
+            if app_with_id.working_dir.is_empty() {
+                println!("Application saved. Waiting for source upload...");
+                return HttpResponse::Ok().json(CreatedApp {
+                    id: app_id,
+                    port: app_with_id.port,
+                    building: false,
+                });
+            }
+
+            if let Err(e) = mark_deployed(pool.get_ref(), app_id).await {
+                eprintln!("DB Error: {}", e);
+            }
 
-                Err(_) => {
-                    eprintln!("Cannot reach agent.");
-                    HttpResponse::InternalServerError()
-                        .body("Application saved but agent unavailable")
+            // Build in the background, the CLI follows progress through the build logs
+            if build_command(&app_with_id).is_some() {
+                println!("Application saved. Building...");
+                let port = app_with_id.port;
+                tokio::spawn(build_then_restart(
+                    pool.get_ref().clone(),
+                    app_with_id,
+                    AppStatus::FAILED,
+                ));
+                return HttpResponse::Ok().json(CreatedApp {
+                    id: app_id,
+                    port,
+                    building: true,
+                });
+            }
+
+            println!("Application saved. Starting agent...");
+
+            // Send full application data to agent, one request per process instance
+            for process in processes {
+                if let Err(e) = start_process(pool.get_ref(), &app_with_id, process).await {
+                    eprintln!("{}", e);
+                    return ApiError::agent_unavailable("Application saved but agent unavailable").response();
                 }
             }
+
+            println!("Agent started application.");
+            HttpResponse::Ok().json(CreatedApp {
+                id: app_id,
+                port: app_with_id.port,
+                building: false,
+            })
         }
+        Err(error) if is_name_taken(&error) => name_taken(pool.get_ref(), &app.name).await,
         Err(error) => {
             eprintln!("DB Error: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            ApiError::internal().response()
         }
     }
 }
 
-pub async fn get_programs(pool: web::Data<PgPool>) -> impl Responder {
-    match get_applications(pool.get_ref()).await {
-        Ok(apps) => HttpResponse::Ok().json(apps),
+fn check_name(name: &str) -> Result<(), HttpResponse> {
+    if shared::is_valid_app_name(name) {
+        return Ok(());
+    }
+    Err(ApiError::invalid_request(format!(
+        "Invalid app name '{}': use up to 63 letters, digits, '-', '_' and '.'",
+        name
+    ))
+    .response())
+}
+
+fn check_labels(app: &Application) -> Result<(), HttpResponse> {
+    match &app.labels {
+        Some(labels) if !labels.as_object().is_some_and(|l| l.values().all(|v| v.is_string())) => Err(
+            ApiError::invalid_request("labels must map names to string values").response(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+async fn name_taken(pool: &PgPool, name: &str) -> HttpResponse {
+    // Destroyed apps keep their name until they're purged
+    if let Err(sqlx::Error::RowNotFound) = get_application_by_name(pool, name).await {
+        return ApiError::new(
+            ErrorCode::NameTaken,
+            format!("An app named '{}' already exists but was destroyed", name),
+        )
+        .with_details(serde_json::json!({ "name": name, "destroyed": true }))
+        .response();
+    }
+    ApiError::new(ErrorCode::NameTaken, format!("An app named '{}' already exists", name))
+        .with_details(serde_json::json!({ "name": name, "destroyed": false }))
+        .response()
+}
+
+/// How many free ports a `port_in_use` error suggests
+const SUGGESTED_PORTS: usize = 3;
+
+/// A `port_in_use` error, suggesting ports of the managed range that are free right now
+async fn port_in_use(pool: &PgPool, port: i32, message: String) -> HttpResponse {
+    let range = port_range();
+    let free_ports: Vec<i32> = match get_taken_ports(pool, range.clone()).await {
+        Ok(taken) => range
+            .filter(|p| *p != port && !taken.contains(p))
+            .filter(|p| is_bindable(*p))
+            .take(SUGGESTED_PORTS)
+            .collect(),
+        Err(e) => {
+            eprintln!("DB Error fetching ports: {}", e);
+            Vec::new()
+        }
+    };
+    ApiError::new(ErrorCode::PortInUse, message)
+        .with_details(serde_json::json!({ "port": port, "free_ports": free_ports }))
+        .response()
+}
+
+/// The `port_in_use` error for a port another app or process holds
+async fn port_taken(pool: &PgPool, port: i32, conflict: PortConflict) -> HttpResponse {
+    let message = match conflict {
+        PortConflict::App => format!("Port {} is already in use by another application", port),
+        PortConflict::Host => format!("Port {} is already in use by another process on this host", port),
+    };
+    eprintln!("{}", message);
+    port_in_use(pool, port, message).await
+}
+
+/// Move the app to `port` unless it's already there, checking it like a new app's port.
+/// `detected` ports are the ones the app already listens on, so only other apps are checked.
+/// Returns an error response if another app or process holds it.
+async fn claim_port(pool: &PgPool, app: &Application, port: i32, detected: bool) -> Result<(), HttpResponse> {
+    if port == app.port {
+        return Ok(());
+    }
+    let app_id = app.id.expect("app loaded from the database has an id");
+    match move_application_to_port(pool, app_id, port, host_check(detected)).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(conflict)) => Err(port_taken(pool, port, conflict).await),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            Err(ApiError::internal().response())
+        }
+    }
+}
+
+/// Tell apps linking to this one about its new port, as saved in the database
+async fn notify_moved(pool: &PgPool, app_id: Uuid) {
+    match get_application(pool, app_id).await {
+        Ok(app) => {
+            tokio::spawn(notify_linked_apps(pool.clone(), app));
+        }
+        Err(e) => eprintln!("DB Error: {}", e),
+    }
+}
+
+fn no_free_port(range: &std::ops::RangeInclusive<i32>, message: String) -> HttpResponse {
+    ApiError::new(ErrorCode::NoFreePort, message)
+        .with_details(serde_json::json!({ "range": [range.start(), range.end()] }))
+        .response()
+}
+
+/// Query string of `GET /apps`, e.g. `?status=crashed&label=team=x&sort=-deployed&limit=20`
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AppListParams {
+    status: Option<String>,
+    /// Comma-separated `key=value` pairs an app must all have
+    label: Option<String>,
+    /// `name`, `status`, `port`, `created`, `deployed`, `started` or `restarts`; `-` for descending
+    sort: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// List destroyed apps that can still be restored
+    #[serde(default)]
+    deleted: bool,
+}
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 500;
+
+fn parse_list_params(params: AppListParams) -> Result<AppListQuery, String> {
+    let status = match params.status.as_deref() {
+        None | Some("") => None,
+        Some(status) => Some(
+            serde_json::from_value(serde_json::json!(status.to_uppercase()))
+                .map_err(|_| format!("Unknown status '{}'", status))?,
+        ),
+    };
+
+    let mut labels = serde_json::Map::new();
+    for pair in params.label.iter().flat_map(|l| l.split(',')).filter(|p| !p.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid label filter '{}', expected key=value", pair))?;
+        labels.insert(key.to_string(), serde_json::json!(value));
+    }
+
+    let sort = params.sort.unwrap_or_else(|| "name".to_string());
+    let (field, descending) = match sort.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (sort.as_str(), false),
+    };
+    let order_by = match (field, descending) {
+        ("name", false) => "name ASC",
+        ("name", true) => "name DESC",
+        ("status", false) => "status ASC",
+        ("status", true) => "status DESC",
+        ("port", false) => "port ASC",
+        ("port", true) => "port DESC",
+        ("created", false) => "created_at ASC",
+        ("created", true) => "created_at DESC",
+        ("deployed", false) => "deployed_at ASC NULLS FIRST",
+        ("deployed", true) => "deployed_at DESC NULLS LAST",
+        ("started", false) => "started_at ASC NULLS FIRST",
+        ("started", true) => "started_at DESC NULLS LAST",
+        ("restarts", false) => "restart_count ASC",
+        ("restarts", true) => "restart_count DESC",
+        _ => return Err(format!("Unknown sort field '{}'", field)),
+    };
+
+    Ok(AppListQuery {
+        status,
+        labels,
+        deleted: params.deleted,
+        order_by,
+        limit: params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        offset: params.offset.unwrap_or(0).max(0),
+    })
+}
+
+/// A page of apps with their live status. The number of matching apps is in `X-Total-Count`.
+#[utoipa::path(
+    get,
+    path = "/apps",
+    tag = "apps",
+    params(AppListParams),
+    responses(
+        (
+            status = 200,
+            description = "A page of apps",
+            body = Vec<AppSummary>,
+            headers(("X-Total-Count" = i64, description = "Number of matching apps")),
+        ),
+        (status = 400, description = "Invalid request", body = ApiError),
+    )
+)]
+pub async fn get_programs(pool: web::Data<PgPool>, params: web::Query<AppListParams>) -> impl Responder {
+    let list = match parse_list_params(params.into_inner()) {
+        Ok(list) => list,
+        Err(e) => return ApiError::invalid_request(e).response(),
+    };
+
+    let (apps, total) = match list_applications(pool.get_ref(), &list).await {
+        Ok(page) => page,
         Err(error) => {
             eprintln!("DB Error: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            return ApiError::internal().response();
+        }
+    };
+
+    let live = join_all(apps.iter().map(|(app, _)| live_status(app))).await;
+    let summaries: Vec<AppSummary> = apps
+        .into_iter()
+        .zip(live)
+        .map(|((app, stats), live_status)| AppSummary {
+            app,
+            stats,
+            live_status,
+        })
+        .collect();
+
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(summaries)
+}
+
+/// The release an app currently runs from, if it was deployed from uploaded source
+async fn current_release(pool: &PgPool, app: &Application) -> Option<Release> {
+    let (app_id, release_id) = (app.id?, app.release_id?);
+    match get_release(pool, app_id, release_id).await {
+        Ok(release) => Some(release),
+        Err(e) => {
+            eprintln!("DB Error fetching release: {}", e);
+            None
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}",
+    tag = "apps",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = AppDetails),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
 pub async fn get_program(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
     let app_id = path.into_inner();
     println!("app id: {}", app_id);
     match get_application(pool.get_ref(), app_id).await {
-        Ok(app) => HttpResponse::Ok().json(app),
+        Ok(app) => {
+            let release = current_release(pool.get_ref(), &app).await;
+            HttpResponse::Ok().json(AppDetails { app, release })
+        }
         Err(error) => match error {
-            sqlx::Error::RowNotFound => HttpResponse::NotFound().finish(),
+            sqlx::Error::RowNotFound => ApiError::not_found("Application not found").response(),
             _ => {
                 eprintln!("DB Error: {}", error);
-                return HttpResponse::InternalServerError().finish();
+                ApiError::internal().response()
             }
         },
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/apps/{app_id}",
+    tag = "apps",
+    request_body = PatchApplication,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The app was updated", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+        (status = 409, description = "The name or new port is taken", body = ApiError),
+    )
+)]
 pub async fn patch_program(
     pool: web::Data<PgPool>,
     path: web::Path<Uuid>,
@@ -128,6 +717,12 @@ pub async fn patch_program(
     let app_id = path.into_inner();
     println!("patch app id: {}", app_id);
 
+    if let Some(name) = &edited_app_info.name
+        && let Err(res) = check_name(name)
+    {
+        return res;
+    }
+
     // If stopping, update DB to STOPPED FIRST, then kill the process
     // This prevents the agent from restarting the app after kill
     if matches!(edited_app_info.status, Some(AppStatus::STOPPED)) {
@@ -136,17 +731,13 @@ pub async fn patch_program(
                 // Update DB to STOPPED before killing so agent sees STOPPED status
                 if let Err(e) = patch_application(pool.get_ref(), app_id, &edited_app_info).await {
                     eprintln!("DB Error: {}", e);
-                    return HttpResponse::InternalServerError().finish();
+                    return ApiError::internal().response();
                 }
 
-                // Now kill the process
-                if let Some(pid) = app.pid {
-                    let client = Client::new();
-                    let agent_url = "http://127.0.0.1:8001/stop";
-                    let body = serde_json::json!({ "pid": pid });
-                    if let Err(e) = client.post(agent_url).json(&body).send().await {
-                        eprintln!("Failed to contact agent to kill process: {}", e);
-                    }
+                // Now kill every process instance of the app
+                if let Err(e) = stop_processes(pool.get_ref(), &app).await {
+                    eprintln!("DB Error: {}", e);
+                    return ApiError::internal().response();
                 }
 
                 return HttpResponse::Ok().body(format!(
@@ -160,48 +751,237 @@ pub async fn patch_program(
         }
     }
 
+    // A new port is checked like a new app's. Apps linking to this one need to learn about it.
+    let mut moved = false;
+    if let Some(port) = edited_app_info.port
+        && let Ok(app) = get_application(pool.get_ref(), app_id).await
+        && app.port != port
+    {
+        if let Err(res) = claim_port(pool.get_ref(), &app, port, edited_app_info.port_detected).await {
+            return res;
+        }
+        moved = true;
+    }
+
     match patch_application(pool.get_ref(), app_id, &edited_app_info).await {
-        Ok(_) => HttpResponse::Ok().body(format!(
-            "Application Program ID = {} Information Successfully Updated",
-            app_id
-        )),
+        Ok(_) => {
+            if moved {
+                notify_moved(pool.get_ref(), app_id).await;
+            }
+            HttpResponse::Ok().body(format!(
+                "Application Program ID = {} Information Successfully Updated",
+                app_id
+            ))
+        }
+        Err(error) if is_name_taken(&error) => {
+            name_taken(pool.get_ref(), edited_app_info.name.as_deref().unwrap_or_default()).await
+        }
         Err(error) => {
             eprintln!("DB Error: {}", error);
-            return HttpResponse::InternalServerError().finish();
+            ApiError::internal().response()
         }
     }
 }
 
+/// Mark every process instance of the app STOPPED, so the agent won't restart them, then kill them
+async fn stop_processes(pool: &PgPool, app: &Application) -> Result<(), sqlx::Error> {
+    let Some(app_id) = app.id else {
+        return Ok(());
+    };
+    let processes = get_processes(pool, app_id).await?;
+    reset_processes(pool, app_id, AppStatus::STOPPED).await?;
+    let mut pids: Vec<i32> = processes.iter().filter_map(|p| p.pid).collect();
+    pids.extend(app.pid);
+    pids.sort_unstable();
+    pids.dedup();
+    for pid in pids {
+        kill_app(pid).await;
+    }
+    Ok(())
+}
+
+/// Remove the app's git repository and the agent's extracted releases
+async fn remove_app_files(app_id: Uuid) {
+    let repo = git_host::repo_path(app_id);
+    if repo.exists()
+        && let Err(e) = std::fs::remove_dir_all(&repo)
+    {
+        eprintln!("Failed to remove {}: {}", repo.display(), e);
+    }
+
+    let url = config().agent_url(&format!("/releases/{}", app_id));
+    match agent().delete(&url).send().await {
+        Ok(res) if res.status().is_success() => {}
+        Ok(res) => eprintln!("Agent failed to remove releases of {}: {}", app_id, res.status()),
+        Err(e) => eprintln!("Cannot reach agent to remove releases of {}: {}", app_id, e),
+    }
+}
+
+/// Delete a stopped app for good, with everything it left on disk
+async fn purge_app(pool: &PgPool, app_id: Uuid) -> Result<(), sqlx::Error> {
+    delete_application(pool, app_id).await?;
+    remove_app_files(app_id).await;
+    Ok(())
+}
+
+/// Purge destroyed apps whose restore window has passed
+pub async fn purge_expired_apps(pool: &PgPool) {
+    let expired = match get_expired_applications(pool).await {
+        Ok(expired) => expired,
+        Err(e) => {
+            eprintln!("DB Error fetching destroyed apps: {}", e);
+            return;
+        }
+    };
+    for app_id in expired {
+        match purge_app(pool, app_id).await {
+            Ok(_) => println!("Purged destroyed app {}", app_id),
+            Err(e) => eprintln!("Failed to purge app {}: {}", app_id, e),
+        }
+    }
+}
+
+/// Query string of `DELETE /apps/{app_id}`
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteParams {
+    /// Delete immediately instead of keeping the app for the restore window
+    #[serde(default)]
+    purge: bool,
+}
+
+/// Stop the app's processes and destroy it. Unless purged, the app can be restored
+/// for `RESTORE_WINDOW_DAYS`; until then it keeps its name but not its port.
+#[utoipa::path(
+    delete,
+    path = "/apps/{app_id}",
+    tag = "apps",
+    params(("app_id" = String, Path, description = "App id or name"), DeleteParams),
+    responses(
+        (status = 200, description = "The app was stopped and destroyed", body = DeletedApp),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
 pub async fn delete_program(
     pool: web::Data<PgPool>,
     path: web::Path<Uuid>,
+    params: web::Query<DeleteParams>,
 ) -> impl Responder {
     let app_id = path.into_inner();
-    match delete_application(pool.get_ref(), app_id).await {
-        Ok(_) => HttpResponse::Ok().body(format!("Application {} deleted", app_id)),
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        // An already destroyed app can still be purged early
+        Err(sqlx::Error::RowNotFound) if params.purge => {
+            match get_deleted_application(pool.get_ref(), app_id).await {
+                Ok(app) => app,
+                Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+                Err(e) => {
+                    eprintln!("DB Error: {}", e);
+                    return ApiError::internal().response();
+                }
+            }
+        }
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    if let Err(e) = stop_processes(pool.get_ref(), &app).await {
+        eprintln!("DB Error stopping {}: {}", app.name, e);
+        return ApiError::internal().response();
+    }
+
+    if params.purge {
+        return match purge_app(pool.get_ref(), app_id).await {
+            Ok(_) => {
+                println!("Purged app {} ({})", app.name, app_id);
+                HttpResponse::Ok().json(DeletedApp {
+                    id: app_id,
+                    name: app.name,
+                    purged: true,
+                    purge_after: None,
+                })
+            }
+            Err(e) => {
+                eprintln!("DB Error: {}", e);
+                ApiError::internal().response()
+            }
+        };
+    }
+
+    match soft_delete_application(pool.get_ref(), app_id).await {
+        Ok(purge_after) => {
+            println!("Destroyed app {} ({}), purging after {}", app.name, app_id, purge_after);
+            HttpResponse::Ok().json(DeletedApp {
+                id: app_id,
+                name: app.name,
+                purged: false,
+                purge_after: Some(purge_after),
+            })
+        }
         Err(e) => {
             eprintln!("DB Error: {}", e);
-            HttpResponse::InternalServerError().finish()
+            ApiError::internal().response()
         }
     }
 }
 
-pub async fn get_live_status(
-    pool: web::Data<PgPool>,
-    path: web::Path<Uuid>,
-) -> impl Responder {
+/// Bring back a destroyed app within its restore window. It comes back stopped.
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/restore",
+    tag = "apps",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The app was restored, stopped", body = Application),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn restore_program(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
     let app_id = path.into_inner();
+    let range = port_range();
+    match restore_application(pool.get_ref(), app_id, range.clone(), is_bindable).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return no_free_port(
+                &range,
+                format!(
+                    "The app's port was taken and no free port is left in {}-{}",
+                    range.start(),
+                    range.end()
+                ),
+            );
+        }
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiError::not_found(format!("App {} isn't destroyed or was already purged", app_id))
+                .response();
+        }
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    }
 
-    let app = match get_application(pool.get_ref(), app_id).await {
-        Ok(app) => app,
-        Err(_) => return HttpResponse::NotFound().body("Application not found"),
-    };
+    match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => {
+            println!("Restored app {} ({}) on port {}", app.name, app_id, app.port);
+            HttpResponse::Ok().json(app)
+        }
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
 
-    let live_status = match app.pid {
+/// Whether the app's web process is alive according to the agent
+async fn live_status(app: &Application) -> String {
+    match app.pid {
         Some(pid) => {
-            let client = Client::new();
-            let agent_url = format!("http://127.0.0.1:8001/status/{}", pid);
-            match client.get(&agent_url).send().await {
+            let agent_url = config().agent_url(&format!("/status/{}", pid));
+            match agent().get(&agent_url).send().await {
                 Ok(res) if res.status().is_success() => {
                     let body: serde_json::Value = res.json().await.unwrap_or_default();
                     body.get("status")
@@ -212,85 +992,225 @@ pub async fn get_live_status(
                 _ => "UNKNOWN".to_string(),
             }
         }
+        None if matches!(app.status, AppStatus::BUILDING) => "BUILDING".to_string(),
         None => "STOPPED".to_string(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/status",
+    tag = "apps",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = AppLiveStatus),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn get_live_status(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(_) => return ApiError::not_found("Application not found").response(),
     };
 
+    let live_status = live_status(&app).await;
+
     // Auto-correct DB if status has diverged
     if live_status == "STOPPED" && matches!(app.status, AppStatus::RUNNING) {
         let patch = PatchApplication {
-            name: None,
-            command: None,
-            port: None,
-            working_dir: None,
             status: Some(AppStatus::STOPPED),
-            pid: None,
-            env_vars: None,
+            ..Default::default()
         };
         let _ = patch_application(pool.get_ref(), app_id, &patch).await;
     }
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "id": app_id,
-        "name": app.name,
-        "status": live_status,
-        "pid": app.pid,
-        "port": app.port,
-        "command": app.command,
-    }))
+    let release = current_release(pool.get_ref(), &app).await;
+    HttpResponse::Ok().json(AppLiveStatus {
+        id: app_id,
+        name: app.name,
+        status: live_status,
+        pid: app.pid,
+        port: app.port,
+        command: app.command,
+        release,
+    })
+}
+
+/// Save the app's updated config and start it again, building first if it has a build.
+/// Returns whether a build was started; it runs in the background while the previous version keeps running.
+pub(crate) async fn deploy_app(pool: &PgPool, app: Application) -> Result<bool, String> {
+    start_deploy(pool, app).await.map(|build| build.is_some())
+}
+
+/// Like `deploy_app`, but returns the background build (if any), which resolves to whether it succeeded
+pub(crate) async fn start_deploy(pool: &PgPool, app: Application) -> Result<Option<JoinHandle<bool>>, String> {
+    let app_id = app.id.expect("app loaded from the database has an id");
+    let building = build_command(&app).is_some();
+    let previous_status = app.status.clone();
+    let patch = PatchApplication {
+        status: building.then_some(AppStatus::BUILDING),
+        port: Some(app.port),
+        command: Some(app.command.clone()),
+        processes: Some(serde_json::to_value(process_formation(&app)).unwrap_or_default()),
+        build_command: app.build_command.clone(),
+        build_timeout: app.build_timeout,
+        release_id: app.release_id,
+        env_vars: app.env_vars.clone(),
+        links: app.links.clone(),
+        on_link_change: app.on_link_change.clone(),
+        labels: app.labels.clone(),
+        ..Default::default()
+    };
+    patch_application(pool, app_id, &patch)
+        .await
+        .map_err(|e| format!("Failed to update app: {}", e))?;
+    mark_deployed(pool, app_id)
+        .await
+        .map_err(|e| format!("Failed to update app: {}", e))?;
+
+    if building {
+        let on_failure = match previous_status {
+            // Nothing was running before (first deploy or a failed one)
+            AppStatus::BUILDING | AppStatus::PENDING => AppStatus::FAILED,
+            status => status,
+        };
+        Ok(Some(tokio::spawn(build_then_restart(pool.clone(), app, on_failure))))
+    } else {
+        restart_app(pool, &app).await?;
+        Ok(None)
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/redeploy",
+    tag = "apps",
+    request_body = RedeployApp,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The app is restarting or building", body = Redeployed),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+        (status = 409, description = "The new port is taken", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
 pub async fn redeploy_program(
     pool: web::Data<PgPool>,
     path: web::Path<Uuid>,
-    body: web::Json<serde_json::Value>,
+    body: web::Json<RedeployApp>,
 ) -> impl Responder {
     let app_id = path.into_inner();
     println!("Redeploying app: {}", app_id);
+    let body = body.into_inner();
 
     let mut app = match get_application(pool.get_ref(), app_id).await {
         Ok(app) => app,
         Err(e) => {
             eprintln!("DB Error: {}", e);
-            return HttpResponse::NotFound().body("Application not found");
+            return ApiError::not_found("Application not found").response();
         }
     };
 
-    // Kill the old process if running
-    if let Some(pid) = app.pid {
-        kill_app(pid).await;
+    let previous_port = app.port;
+
+    // Pick up process types added or changed in paas.toml / Procfile
+    if let Some(processes) = body.processes {
+        if let Some(web) = processes.get(WEB_PROCESS) {
+            app.command = web.clone();
+        }
+        app.processes = Some(serde_json::json!(processes));
     }
 
-    // Update port from paas.toml if provided
-    if let Some(port) = body.get("port").and_then(|p| p.as_i64()) {
-        app.port = port as i32;
+    // An empty build command removes the build step
+    if let Some(build) = body.build {
+        app.build_command = Some(build);
+    }
+    if let Some(timeout) = body.build_timeout {
+        app.build_timeout = Some(timeout);
     }
 
-    // Clear PID explicitly and reset status to PENDING
-    if let Err(e) = clear_pid(pool.get_ref(), app_id).await {
-        eprintln!("Failed to clear PID: {}", e);
-        return HttpResponse::InternalServerError().finish();
+    // Switch to an uploaded release. Config releases (env changes, promotions, and rolling back
+    // to either) bring back the env vars they were created with.
+    if let Some(release_id) = body.release_id {
+        match get_release(pool.get_ref(), app_id, release_id).await {
+            Ok(release) => {
+                app.release_id = Some(release.id);
+                if let Some(env_vars) = release.env_vars {
+                    app.env_vars = Some(env_vars);
+                }
+            }
+            Err(sqlx::Error::RowNotFound) => {
+                return ApiError::not_found("Release not found").response();
+            }
+            Err(e) => {
+                eprintln!("DB Error: {}", e);
+                return ApiError::internal().response();
+            }
+        }
     }
 
-    let patch = PatchApplication {
-        name: None,
-        command: None,
-        port: None,
-        working_dir: None,
-        status: Some(AppStatus::PENDING),
-        pid: None,
-        env_vars: None,
-    };
-    if let Err(e) = patch_application(pool.get_ref(), app_id, &patch).await {
-        eprintln!("Failed to reset app status: {}", e);
-        return HttpResponse::InternalServerError().finish();
+    // Env vars from paas.toml override the app's; vars only set on the server are kept
+    if let Some(env) = body.env_vars {
+        let mut merged = app
+            .env_vars
+            .as_ref()
+            .and_then(|e| e.as_object())
+            .cloned()
+            .unwrap_or_default();
+        merged.extend(env.into_iter().map(|(key, val)| (key, serde_json::Value::String(val))));
+        app.env_vars = Some(serde_json::Value::Object(merged));
+    }
+
+    if let Some(links) = body.links {
+        app.links = Some(serde_json::json!(links));
+    }
+    if let Some(action) = body.on_link_change {
+        app.on_link_change = Some(action);
+    }
+    if let Some(labels) = body.labels {
+        app.labels = Some(serde_json::json!(labels));
+    }
+    if let Err(res) = check_links(&app) {
+        return res;
+    }
+    if let Err(res) = check_labels(&app) {
+        return res;
+    }
+
+    if app.release_id.is_none() && app.working_dir.is_empty() {
+        return ApiError::invalid_request("No source uploaded for this app yet").response();
+    }
+
+    if let Err(res) = check_env_references(pool.get_ref(), &app, &app.env_map()).await {
+        return res;
+    }
+
+    // A port changed in paas.toml is checked like a new app's and saved before the app moves to it
+    if let Some(port) = body.port {
+        if let Err(res) = claim_port(pool.get_ref(), &app, port, false).await {
+            return res;
+        }
+        app.port = port;
     }
 
-    // Start fresh process
     let port = app.port;
-    start_app(app.clone()).await;
+    let name = app.name.clone();
+    let building = match deploy_app(pool.get_ref(), app).await {
+        Ok(building) => building,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ApiError::internal().response();
+        }
+    };
+    if port != previous_port {
+        notify_moved(pool.get_ref(), app_id).await;
+    }
 
-    HttpResponse::Ok().json(serde_json::json!({
-        "port": port,
-        "name": app.name,
-    }))
+    HttpResponse::Ok().json(Redeployed { name, port, building })
 }
@@ -0,0 +1,138 @@
+use crate::config::config;
+use crate::handlers::app_handlers::{agent, restart_app};
+use crate::models::{AppStatus, Application};
+use crate::repository::app_repo::{get_application_by_name, get_linking_applications};
+use crate::repository::process_repo::{get_processes, get_processes_by_type};
+use actix_web::{HttpResponse, Responder, web};
+use shared::{ApiError, ServiceDiscovery, ServiceInstance, WEB_PROCESS};
+use sqlx::PgPool;
+
+const SERVICE_HOST: &str = "127.0.0.1";
+
+fn service_url(port: i32) -> String {
+    format!("http://{}:{}", SERVICE_HOST, port)
+}
+
+/// Addresses of an app's running web instances
+#[utoipa::path(
+    get,
+    path = "/discovery/{app_name}",
+    tag = "discovery",
+    params(("app_name" = String, Path)),
+    responses(
+        (status = 200, description = "OK", body = ServiceDiscovery),
+        (status = 404, description = "No app with this name", body = ApiError),
+    )
+)]
+pub async fn get_discovery(pool: web::Data<PgPool>, path: web::Path<String>) -> impl Responder {
+    let name = path.into_inner();
+
+    let app = match get_application_by_name(pool.get_ref(), &name).await {
+        Ok(app) => app,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiError::not_found(format!("No app named '{}'", name)).response();
+        }
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+    let app_id = app.id.expect("app loaded from the database has an id");
+
+    let processes = match get_processes_by_type(pool.get_ref(), app_id, WEB_PROCESS).await {
+        Ok(processes) => processes,
+        Err(e) => {
+            eprintln!("DB Error fetching processes: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    let instances = processes
+        .into_iter()
+        .filter(|p| matches!(p.status, AppStatus::RUNNING) && app.port > 0)
+        .map(|p| ServiceInstance {
+            process_id: p.id,
+            instance: p.instance,
+            host: SERVICE_HOST.to_string(),
+            port: app.port,
+            url: service_url(app.port),
+        })
+        .collect();
+
+    HttpResponse::Ok().json(ServiceDiscovery {
+        app_id,
+        name: app.name,
+        url: service_url(app.port),
+        instances,
+    })
+}
+
+/// Ask the agent to send SIGHUP to a process
+async fn reload_process(pid: i32) {
+    let body = serde_json::json!({ "pid": pid, "signal": "HUP" });
+    if let Err(e) = agent()
+        .post(config().agent_url("/signal"))
+        .json(&body)
+        .send()
+        .await
+    {
+        eprintln!("Failed to signal process {}: {}", pid, e);
+    }
+}
+
+/// Tell the apps linking to `app` that it moved, each according to its `on_link_change`
+pub(crate) async fn notify_linked_apps(pool: PgPool, app: Application) {
+    let dependents = match get_linking_applications(&pool, &app.name).await {
+        Ok(dependents) => dependents,
+        Err(e) => {
+            eprintln!("DB Error fetching apps linked to {}: {}", app.name, e);
+            return;
+        }
+    };
+
+    for dependent in dependents {
+        let Some(dependent_id) = dependent.id else {
+            continue;
+        };
+        match dependent.on_link_change.as_deref().unwrap_or(shared::DEFAULT_LINK_CHANGE) {
+            "restart" => {
+                println!("{} moved to port {}, restarting {}", app.name, app.port, dependent.name);
+                if let Err(e) = restart_app(&pool, &dependent).await {
+                    eprintln!("Failed to restart {}: {}", dependent.name, e);
+                }
+            }
+            "reload" => {
+                println!("{} moved to port {}, reloading {}", app.name, app.port, dependent.name);
+                let processes = get_processes(&pool, dependent_id).await.unwrap_or_default();
+                for pid in processes.iter().filter_map(|p| p.pid) {
+                    reload_process(pid).await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reject `links` that aren't a list of other apps' names and unknown `on_link_change` actions
+pub(crate) fn check_links(app: &Application) -> Result<(), HttpResponse> {
+    if let Some(links) = &app.links {
+        let names = links.as_array().filter(|l| l.iter().all(|n| n.is_string()));
+        if names.is_none() {
+            return Err(ApiError::invalid_request("links must be a list of app names").response());
+        }
+        if app.link_names().contains(&app.name) {
+            return Err(ApiError::invalid_request("An app can't link to itself").response());
+        }
+    }
+    if let Some(action) = &app.on_link_change
+        && !shared::LINK_CHANGE_ACTIONS.contains(&action.as_str())
+    {
+        return Err(ApiError::invalid_request(format!(
+            "Unknown on_link_change '{}', expected one of {}",
+            action,
+            shared::LINK_CHANGE_ACTIONS.join(", ")
+        ))
+        .response());
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use crate::handlers::app_handlers::{deploy_app, load_app};
+use crate::models::{Application, PatchApplication};
+use crate::repository::app_repo::{get_applications, patch_application};
+use crate::repository::release_repo::insert_derived_release;
+use crate::repository::secret_repo::get_secret_names;
+use actix_web::{HttpResponse, Responder, web};
+use shared::interpolate::{references_apps, resolve_env};
+use shared::{ApiError, EnvUpdate, ErrorCode, is_valid_env_name};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// `Set A, B; Unset C`
+fn describe_change(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Option<String> {
+    let set: Vec<&str> = new
+        .iter()
+        .filter(|(key, val)| old.get(*key) != Some(val))
+        .map(|(key, _)| key.as_str())
+        .collect();
+    let unset: Vec<&str> = old
+        .keys()
+        .filter(|key| !new.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+
+    let mut parts = Vec::new();
+    if !set.is_empty() {
+        parts.push(format!("Set {}", set.join(", ")));
+    }
+    if !unset.is_empty() {
+        parts.push(format!("Unset {}", unset.join(", ")));
+    }
+    (!parts.is_empty()).then(|| parts.join("; "))
+}
+
+/// Check the `${...}` references in `env` the same way the agent resolves them, so missing
+/// vars, unknown apps and cycles are reported before a deploy instead of when processes start.
+/// Returns the problem, if there is one.
+pub(crate) async fn env_reference_error(
+    pool: &PgPool,
+    app: &Application,
+    env: &BTreeMap<String, String>,
+) -> Result<Option<String>, sqlx::Error> {
+    // Secret values don't matter, only which names exist
+    let secrets: BTreeMap<String, String> = match app.id {
+        Some(app_id) => get_secret_names(pool, app_id)
+            .await?
+            .into_iter()
+            .map(|s| (s.name, String::new()))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+
+    let mut app_names = vec![app.name.clone()];
+    if references_apps(env) {
+        app_names.extend(get_applications(pool).await?.into_iter().map(|a| a.name));
+    }
+    let lookup = |name: &str, _: &str| app_names.iter().any(|n| n == name).then(String::new);
+
+    Ok(resolve_env(env, &secrets, &BTreeMap::new(), &lookup).err())
+}
+
+/// `env_reference_error` as a response: 400 for bad references
+pub(crate) async fn check_env_references(
+    pool: &PgPool,
+    app: &Application,
+    env: &BTreeMap<String, String>,
+) -> Result<(), HttpResponse> {
+    match env_reference_error(pool, app, env).await {
+        Ok(None) => Ok(()),
+        Ok(Some(e)) => Err(ApiError::invalid_request(e).response()),
+        Err(e) => {
+            eprintln!("DB Error checking env vars: {}", e);
+            Err(ApiError::internal().response())
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EnvQuery {
+    /// Restart the app so the change takes effect now
+    #[serde(default)]
+    pub restart: bool,
+    /// Comma separated names, for `DELETE`
+    pub keys: Option<String>,
+}
+
+/// Save the app's new env vars, record the change as a release and restart the app if asked to.
+/// Without a restart the change applies the next time the app's processes start.
+async fn apply_env_change(
+    pool: &PgPool,
+    mut app: Application,
+    env: BTreeMap<String, String>,
+    restart: bool,
+) -> HttpResponse {
+    let app_id = app.id.expect("app loaded from the database has an id");
+    let old = app.env_map();
+    if let Some(name) = env
+        .keys()
+        .find(|name| !old.contains_key(*name) && !is_valid_env_name(name))
+    {
+        return ApiError::invalid_request(format!(
+            "Invalid env var name '{}': use letters, digits and '_', not starting with a digit",
+            name
+        ))
+        .response();
+    }
+
+    if let Err(res) = check_env_references(pool, &app, &env).await {
+        return res;
+    }
+
+    let Some(description) = describe_change(&old, &env) else {
+        return HttpResponse::Ok().json(EnvUpdate {
+            env_vars: env,
+            release: None,
+            restarted: false,
+            building: false,
+        });
+    };
+
+    let env_vars = serde_json::to_value(&env).unwrap_or_default();
+    let patch = PatchApplication {
+        env_vars: Some(env_vars.clone()),
+        ..Default::default()
+    };
+    if let Err(e) = patch_application(pool, app_id, &patch).await {
+        eprintln!("DB Error updating env vars: {}", e);
+        return ApiError::internal().response();
+    }
+    app.env_vars = Some(env_vars.clone());
+    println!("{} of {}", description, app.name);
+
+    // Apps running from working_dir have no source to release
+    let release = match app.release_id {
+        Some(current) => match insert_derived_release(pool, app_id, app_id, current, &description, &env_vars).await {
+            Ok(release) => Some(release),
+            Err(e) => {
+                eprintln!("DB Error creating release: {}", e);
+                return ApiError::internal().response();
+            }
+        },
+        None => None,
+    };
+
+    let mut building = false;
+    let restarted = restart && (release.is_some() || !app.working_dir.is_empty());
+    if restarted {
+        if let Some(release) = &release {
+            app.release_id = Some(release.id);
+        }
+        building = match deploy_app(pool, app).await {
+            Ok(building) => building,
+            Err(e) => {
+                eprintln!("{}", e);
+                return ApiError::new(ErrorCode::Internal, "Env vars saved but the app failed to restart")
+                    .response();
+            }
+        };
+    }
+
+    HttpResponse::Ok().json(EnvUpdate {
+        env_vars: env,
+        release,
+        restarted,
+        building,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/env",
+    tag = "env",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = BTreeMap<String, String>),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn get_app_env(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+    match load_app(pool.get_ref(), path.into_inner()).await {
+        Ok(app) => HttpResponse::Ok().json(app.env_map()),
+        Err(res) => res,
+    }
+}
+
+/// Replace all of the app's env vars
+#[utoipa::path(
+    put,
+    path = "/apps/{app_id}/env",
+    tag = "env",
+    request_body = BTreeMap<String, String>,
+    params(("app_id" = String, Path, description = "App id or name"), EnvQuery),
+    responses(
+        (status = 200, description = "OK", body = EnvUpdate),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn put_app_env(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<EnvQuery>,
+    body: web::Json<BTreeMap<String, String>>,
+) -> impl Responder {
+    let app = match load_app(pool.get_ref(), path.into_inner()).await {
+        Ok(app) => app,
+        Err(res) => return res,
+    };
+    apply_env_change(pool.get_ref(), app, body.into_inner(), query.restart).await
+}
+
+/// Set the given env vars, removing those whose value is `null`
+#[utoipa::path(
+    patch,
+    path = "/apps/{app_id}/env",
+    tag = "env",
+    request_body = BTreeMap<String, Option<String>>,
+    params(("app_id" = String, Path, description = "App id or name"), EnvQuery),
+    responses(
+        (status = 200, description = "OK", body = EnvUpdate),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn patch_app_env(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<EnvQuery>,
+    body: web::Json<BTreeMap<String, Option<String>>>,
+) -> impl Responder {
+    let app = match load_app(pool.get_ref(), path.into_inner()).await {
+        Ok(app) => app,
+        Err(res) => return res,
+    };
+
+    let mut env = app.env_map();
+    for (key, val) in body.into_inner() {
+        match val {
+            Some(val) => env.insert(key, val),
+            None => env.remove(&key),
+        };
+    }
+    apply_env_change(pool.get_ref(), app, env, query.restart).await
+}
+
+/// Remove the env vars named in `?keys=A,B`
+#[utoipa::path(
+    delete,
+    path = "/apps/{app_id}/env",
+    tag = "env",
+    params(("app_id" = String, Path, description = "App id or name"), EnvQuery),
+    responses(
+        (status = 200, description = "OK", body = EnvUpdate),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn delete_app_env(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<EnvQuery>,
+) -> impl Responder {
+    let Some(keys) = query.keys.as_deref().filter(|k| !k.trim().is_empty()) else {
+        return ApiError::invalid_request("Pass the env vars to remove as ?keys=A,B").response();
+    };
+    let app = match load_app(pool.get_ref(), path.into_inner()).await {
+        Ok(app) => app,
+        Err(res) => return res,
+    };
+
+    let mut env = app.env_map();
+    for key in keys.split(',') {
+        env.remove(key.trim());
+    }
+    apply_env_change(pool.get_ref(), app, env, query.restart).await
+}
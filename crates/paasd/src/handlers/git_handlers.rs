@@ -0,0 +1,183 @@
+use crate::config::config;
+use crate::git_host::{DEPLOY_BRANCHES, archive_commit, ensure_repo};
+use crate::handlers::app_handlers::start_deploy;
+use crate::handlers::env_handlers::env_reference_error;
+use crate::repository::app_repo::get_application;
+use crate::repository::log_repo::get_logs_since;
+use crate::repository::release_repo::insert_release;
+use actix_web::{HttpResponse, Responder, web};
+use sha2::{Digest, Sha256};
+use shared::{ApiError, BUILD_PROCESS, ErrorCode, GitMetadata, GitPush, GitRepo};
+use sqlx::PgPool;
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+/// Create (or repair) the app's bare git repository and return where to push
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/git",
+    tag = "git",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = GitRepo),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn post_app_git(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+    let app_id = path.into_inner();
+    match get_application(pool.get_ref(), app_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    }
+
+    match ensure_repo(app_id, &config().url()) {
+        Ok(repo) => HttpResponse::Ok().json(GitRepo {
+            path: repo.to_string_lossy().to_string(),
+            branches: DEPLOY_BRANCHES.iter().map(|b| b.to_string()).collect(),
+        }),
+        Err(e) => {
+            eprintln!("{}", e);
+            ApiError::new(ErrorCode::Internal, e).response()
+        }
+    }
+}
+
+/// Called by the post-receive hook: deploy the pushed commit, streaming progress back as plain text
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/git/deploy",
+    tag = "git",
+    request_body = GitPush,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (
+            status = 200,
+            description = "Build and deploy progress, streamed",
+            body = String,
+            content_type = "text/plain",
+        ),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn git_deploy(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    push: web::Json<GitPush>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+    let push = push.into_inner();
+    if push.commit.len() != 40 || !push.commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return ApiError::invalid_request("Invalid commit").response();
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    tokio::spawn(deploy_push(pool.get_ref().clone(), app_id, push, tx));
+
+    let output = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|line| (Ok::<_, actix_web::Error>(web::Bytes::from(line + "\n")), rx))
+    });
+    HttpResponse::Ok().content_type("text/plain").streaming(output)
+}
+
+async fn deploy_push(pool: PgPool, app_id: Uuid, push: GitPush, out: UnboundedSender<String>) {
+    let say = |line: String| {
+        let _ = out.send(line);
+    };
+
+    let mut app = match get_application(&pool, app_id).await {
+        Ok(app) => app,
+        Err(e) => {
+            say(format!("Deploy failed: application not found ({})", e));
+            return;
+        }
+    };
+    let short = &push.commit[..7];
+    say(format!("-----> Deploying {} ({}) to {}", push.branch, short, app.name));
+
+    match env_reference_error(&pool, &app, &app.env_map()).await {
+        Ok(None) => {}
+        Ok(Some(e)) => {
+            say(format!("Deploy failed: {}", e));
+            return;
+        }
+        Err(e) => {
+            eprintln!("DB Error checking env vars: {}", e);
+            say("Deploy failed: could not check env vars".to_string());
+            return;
+        }
+    }
+
+    let commit = push.commit.clone();
+    let source = match tokio::task::spawn_blocking(move || archive_commit(app_id, &commit)).await {
+        Ok(Ok(source)) => source,
+        Ok(Err(e)) => {
+            say(format!("Deploy failed: {}", e));
+            return;
+        }
+        Err(e) => {
+            say(format!("Deploy failed: {}", e));
+            return;
+        }
+    };
+
+    let git = GitMetadata {
+        commit: Some(push.commit.clone()),
+        branch: Some(push.branch.clone()),
+        dirty: Some(false),
+    };
+    let sha256 = hex::encode(Sha256::digest(&source));
+    let release = match insert_release(&pool, app_id, &source, &sha256, &git).await {
+        Ok(release) => release,
+        Err(e) => {
+            eprintln!("DB Error storing release: {}", e);
+            say("Deploy failed: could not store the release".to_string());
+            return;
+        }
+    };
+    say(format!("-----> Stored release v{}", release.version));
+
+    app.release_id = Some(release.id);
+    let mut since = chrono::Utc::now();
+    let build = match start_deploy(&pool, app).await {
+        Ok(build) => build,
+        Err(e) => {
+            eprintln!("{}", e);
+            say(format!("Deploy failed: {}", e));
+            return;
+        }
+    };
+
+    if let Some(build) = build {
+        say("-----> Building".to_string());
+        loop {
+            // Check first, so the build's last lines are still picked up below
+            let done = build.is_finished();
+            if let Ok(logs) = get_logs_since(&pool, app_id, since, Some(BUILD_PROCESS)).await {
+                for log in logs {
+                    since = log.created_at;
+                    say(format!("       {}", log.message));
+                }
+            }
+            if done {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        }
+        if !build.await.unwrap_or(false) {
+            say(format!(
+                "Build failed, release v{} was not deployed. The previous release keeps running.",
+                release.version
+            ));
+            return;
+        }
+    }
+
+    say(format!("-----> Released v{} of {}", release.version, push.branch));
+}
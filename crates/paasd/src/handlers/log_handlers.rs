@@ -1,10 +1,21 @@
+use crate::handlers::app_handlers::load_app;
 use crate::repository::log_repo::{get_logs, get_logs_since, insert_log};
 use actix_web::{HttpResponse, Responder, web};
 use chrono::{DateTime, Utc};
-use shared::NewAppLog;
+use shared::{ApiError, AppLog, NewAppLog};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/logs",
+    tag = "logs",
+    request_body = NewAppLog,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The line was stored"),
+    )
+)]
 pub async fn post_log(
     pool: web::Data<PgPool>,
     log: web::Json<NewAppLog>,
@@ -13,43 +24,59 @@ pub async fn post_log(
         Ok(_) => HttpResponse::Ok().finish(),
         Err(e) => {
             eprintln!("DB Error inserting log: {}", e);
-            HttpResponse::InternalServerError().finish()
+            ApiError::internal().response()
         }
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/logs",
+    tag = "logs",
+    params(("app_id" = String, Path, description = "App id or name"), LogQuery),
+    responses(
+        (status = 200, description = "Log lines, oldest first", body = Vec<AppLog>),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
 pub async fn get_app_logs(
     pool: web::Data<PgPool>,
     path: web::Path<Uuid>,
     query: web::Query<LogQuery>,
 ) -> impl Responder {
     let app_id = path.into_inner();
+    if let Err(res) = load_app(pool.get_ref(), app_id).await {
+        return res;
+    }
 
     if let Some(since) = &query.since {
         match since.parse::<DateTime<Utc>>() {
-            Ok(since_dt) => match get_logs_since(pool.get_ref(), app_id, since_dt).await {
+            Ok(since_dt) => match get_logs_since(pool.get_ref(), app_id, since_dt, query.process.as_deref()).await {
                 Ok(logs) => return HttpResponse::Ok().json(logs),
                 Err(e) => {
                     eprintln!("DB Error fetching logs since: {}", e);
-                    return HttpResponse::InternalServerError().finish();
+                    return ApiError::internal().response();
                 }
             },
-            Err(_) => return HttpResponse::BadRequest().body("Invalid since timestamp"),
+            Err(_) => return ApiError::invalid_request("Invalid since timestamp").response(),
         }
     }
 
     let limit = query.limit.unwrap_or(100);
-    match get_logs(pool.get_ref(), app_id, limit).await {
+    match get_logs(pool.get_ref(), app_id, limit, query.process.as_deref()).await {
         Ok(logs) => HttpResponse::Ok().json(logs),
         Err(e) => {
             eprintln!("DB Error fetching logs: {}", e);
-            HttpResponse::InternalServerError().finish()
+            ApiError::internal().response()
         }
     }
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct LogQuery {
     pub limit: Option<i64>,
     pub since: Option<String>,
+    pub process: Option<String>,
 }
@@ -1,2 +1,10 @@
 pub mod app_handlers;
+pub mod discovery_handlers;
+pub mod env_handlers;
+pub mod git_handlers;
 pub mod log_handlers;
+pub mod process_handlers;
+pub mod release_handlers;
+pub mod run_handlers;
+pub mod schedule_handlers;
+pub mod secret_handlers;
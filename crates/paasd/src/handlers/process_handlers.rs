@@ -0,0 +1,277 @@
+use crate::handlers::app_handlers::{kill_app, start_process, wait_for_exit};
+use crate::models::{AppStatus, PatchApplication};
+use crate::repository::app_repo::{get_application, increment_restart_count, mark_started, patch_application};
+use crate::repository::process_repo::{
+    delete_process, get_process, get_processes, get_processes_by_type, insert_process,
+    patch_process, set_process_type_status,
+};
+use actix_web::{HttpResponse, Responder, web};
+use shared::{ApiError, AppProcess, PatchProcess, ScaleProcess, ScaledProcess, WEB_PROCESS};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/processes",
+    tag = "processes",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = Vec<AppProcess>),
+    )
+)]
+pub async fn get_app_processes(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+    let app_id = path.into_inner();
+    match get_processes(pool.get_ref(), app_id).await {
+        Ok(processes) => HttpResponse::Ok().json(processes),
+        Err(e) => {
+            eprintln!("DB Error fetching processes: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/processes/{process_id}",
+    tag = "processes",
+    params(("app_id" = String, Path, description = "App id or name"), ("process_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "OK", body = AppProcess),
+        (status = 404, description = "Process not found", body = ApiError),
+    )
+)]
+pub async fn get_app_process(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (app_id, process_id) = path.into_inner();
+    match get_process(pool.get_ref(), app_id, process_id).await {
+        Ok(process) => HttpResponse::Ok().json(process),
+        Err(sqlx::Error::RowNotFound) => ApiError::not_found("Process not found").response(),
+        Err(e) => {
+            eprintln!("DB Error fetching process: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Called by the agent to report the PID and status of a process instance.
+/// The first web instance is mirrored onto the app itself.
+#[utoipa::path(
+    patch,
+    path = "/apps/{app_id}/processes/{process_id}",
+    tag = "processes",
+    request_body = PatchProcess,
+    params(("app_id" = String, Path, description = "App id or name"), ("process_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The process was updated"),
+        (status = 404, description = "Process not found", body = ApiError),
+    )
+)]
+pub async fn patch_app_process(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    patch: web::Json<PatchProcess>,
+) -> impl Responder {
+    let (app_id, process_id) = path.into_inner();
+
+    let process = match get_process(pool.get_ref(), app_id, process_id).await {
+        Ok(process) => process,
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Process not found").response(),
+        Err(e) => {
+            eprintln!("DB Error fetching process: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    if let Err(e) = patch_process(pool.get_ref(), process.app_id, process_id, &patch).await {
+        eprintln!("DB Error updating process: {}", e);
+        return ApiError::internal().response();
+    }
+
+    if patch.restarted
+        && let Err(e) = increment_restart_count(pool.get_ref(), process.app_id).await
+    {
+        eprintln!("DB Error counting restart: {}", e);
+    }
+
+    if process.process_type == WEB_PROCESS && process.instance == 1 {
+        let app_patch = PatchApplication {
+            status: patch.status.clone(),
+            pid: patch.pid,
+            ..Default::default()
+        };
+        if let Err(e) = patch_application(pool.get_ref(), process.app_id, &app_patch).await {
+            eprintln!("DB Error updating app: {}", e);
+            return ApiError::internal().response();
+        }
+        if matches!(patch.status, Some(AppStatus::RUNNING))
+            && let Err(e) = mark_started(pool.get_ref(), process.app_id).await
+        {
+            eprintln!("DB Error updating app: {}", e);
+        }
+    }
+
+    HttpResponse::Ok().finish()
+}
+
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/processes/{process_type}/scale",
+    tag = "processes",
+    request_body = ScaleProcess,
+    params(("app_id" = String, Path, description = "App id or name"), ("process_type" = String, Path)),
+    responses(
+        (status = 200, description = "OK", body = ScaledProcess),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
+pub async fn scale_process(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, String)>,
+    body: web::Json<ScaleProcess>,
+) -> impl Responder {
+    let (app_id, process_type) = path.into_inner();
+
+    if body.scale < 0 {
+        return ApiError::invalid_request("Scale must be zero or greater").response();
+    }
+    if process_type == WEB_PROCESS && body.scale > 1 {
+        return ApiError::invalid_request("The web process binds the app's port and can only run a single instance")
+            .response();
+    }
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(_) => return ApiError::not_found("Application not found").response(),
+    };
+
+    let command = match super::app_handlers::process_formation(&app).remove(&process_type) {
+        Some(command) => command,
+        None => {
+            return ApiError::not_found(format!("Process type '{}' is not defined for this app", process_type))
+                .response();
+        }
+    };
+
+    let instances = match get_processes_by_type(pool.get_ref(), app_id, &process_type).await {
+        Ok(instances) => instances,
+        Err(e) => {
+            eprintln!("DB Error fetching processes: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    let current = instances.len() as i32;
+    let app_running = matches!(app.status, AppStatus::RUNNING | AppStatus::PENDING);
+
+    // Scale down: stop and remove the highest-numbered instances first
+    for process in instances.iter().filter(|p| p.instance > body.scale) {
+        if let Err(e) = patch_process(
+            pool.get_ref(),
+            app_id,
+            process.id,
+            &PatchProcess {
+                status: Some(AppStatus::STOPPED),
+                ..Default::default()
+            },
+        )
+        .await
+        {
+            eprintln!("DB Error stopping process: {}", e);
+            return ApiError::internal().response();
+        }
+        if let Some(pid) = process.pid {
+            kill_app(pid).await;
+        }
+        if let Err(e) = delete_process(pool.get_ref(), process.id).await {
+            eprintln!("DB Error removing process: {}", e);
+            return ApiError::internal().response();
+        }
+    }
+
+    // Scale up: add new instances and start them if the app is running
+    for instance in (current + 1)..=body.scale {
+        let process = match insert_process(pool.get_ref(), app_id, &process_type, instance, &command).await {
+            Ok(process) => process,
+            Err(e) => {
+                eprintln!("DB Error adding process: {}", e);
+                return ApiError::internal().response();
+            }
+        };
+        if app_running
+            && let Err(e) = start_process(pool.get_ref(), &app, process).await
+        {
+            eprintln!("{}", e);
+            return ApiError::agent_unavailable("Process added but agent unavailable").response();
+        }
+    }
+
+    HttpResponse::Ok().json(ScaledProcess {
+        process_type,
+        scale: body.scale,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/processes/{process_type}/restart",
+    tag = "processes",
+    params(("app_id" = String, Path, description = "App id or name"), ("process_type" = String, Path)),
+    responses(
+        (status = 200, description = "The processes were restarted", body = String, content_type = "text/plain"),
+        (status = 404, description = "App not found", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
+pub async fn restart_process_type(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, String)>,
+) -> impl Responder {
+    let (app_id, process_type) = path.into_inner();
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(_) => return ApiError::not_found("Application not found").response(),
+    };
+
+    let instances = match get_processes_by_type(pool.get_ref(), app_id, &process_type).await {
+        Ok(instances) if instances.is_empty() => {
+            return ApiError::not_found(format!("No '{}' processes are running for this app", process_type))
+                .response();
+        }
+        Ok(instances) => instances,
+        Err(e) => {
+            eprintln!("DB Error fetching processes: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    // Mark as STOPPED first so the agent doesn't treat the kill as a crash
+    if let Err(e) = set_process_type_status(pool.get_ref(), app_id, &process_type, AppStatus::STOPPED).await {
+        eprintln!("DB Error stopping processes: {}", e);
+        return ApiError::internal().response();
+    }
+    let pids: Vec<i32> = instances.iter().filter_map(|p| p.pid).collect();
+    for &pid in &pids {
+        kill_app(pid).await;
+    }
+    for pid in pids {
+        if !wait_for_exit(pid).await {
+            return ApiError::agent_unavailable(format!("Process {} did not exit", pid)).response();
+        }
+    }
+
+    // The instances stay STOPPED until the agent reports the new PIDs, so an exit watcher
+    // that still looks at an old PID sees either STOPPED or a replaced PID and never respawns it
+    for process in instances {
+        if let Err(e) = start_process(pool.get_ref(), &app, process).await {
+            eprintln!("{}", e);
+            return ApiError::agent_unavailable("Agent unavailable").response();
+        }
+    }
+
+    HttpResponse::Ok().body(format!("Restarted '{}' processes", process_type))
+}
@@ -0,0 +1,211 @@
+use crate::handlers::app_handlers::load_app;
+use crate::repository::app_repo::get_application;
+use crate::repository::release_repo::{
+    get_release, get_release_source, get_releases, insert_derived_release, insert_release,
+};
+use actix_web::{HttpResponse, Responder, web};
+use sha2::{Digest, Sha256};
+use shared::{ApiError, GitMetadata, PromoteRelease, Release};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Largest source tarball accepted by `POST /apps/{app_id}/releases`
+pub const MAX_SOURCE_BYTES: usize = 256 * 1024 * 1024;
+
+/// Store an uploaded source tarball as a new release. The release is not
+/// started here; `POST /apps/{app_id}/redeploy` with its `release_id` does that.
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/releases",
+    tag = "releases",
+    request_body(content = Vec<u8>, description = "Gzipped tarball of the source", content_type = "application/gzip"),
+    params(("app_id" = String, Path, description = "App id or name"), GitMetadata),
+    responses(
+        (status = 200, description = "The source was stored as a new release", body = Release),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn post_release(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    git: web::Query<GitMetadata>,
+    body: web::Bytes,
+) -> impl Responder {
+    let app_id = path.into_inner();
+    let git = git.into_inner();
+
+    if let Some(commit) = &git.commit
+        && (commit.len() > 64 || !commit.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return ApiError::invalid_request("Invalid git commit").response();
+    }
+
+    // Only gzipped tarballs are accepted
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return ApiError::invalid_request("Source must be a gzipped tarball").response();
+    }
+
+    match get_application(pool.get_ref(), app_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    }
+
+    let sha256 = hex::encode(Sha256::digest(&body));
+    match insert_release(pool.get_ref(), app_id, &body, &sha256, &git).await {
+        Ok(release) => {
+            println!(
+                "Stored release v{} of app {} ({} bytes)",
+                release.version, app_id, release.source_size
+            );
+            HttpResponse::Ok().json(release)
+        }
+        Err(e) => {
+            eprintln!("DB Error storing release: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Copy a release of another app (e.g. staging) as this app's next release, without
+/// duplicating its source. Like uploads, `POST /apps/{app_id}/redeploy` starts it.
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/releases/promote",
+    tag = "releases",
+    request_body = PromoteRelease,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The promoted release", body = Release),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn promote_release(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<PromoteRelease>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+    let from_app_id = body.from_app_id;
+    if from_app_id == app_id {
+        return ApiError::invalid_request("Can't promote an app's release to itself").response();
+    }
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+    let from_app = match get_application(pool.get_ref(), from_app_id).await {
+        Ok(app) => app,
+        Err(sqlx::Error::RowNotFound) => {
+            return ApiError::not_found("Application to promote from not found").response();
+        }
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    let Some(release_id) = body.release_id.or(from_app.release_id) else {
+        return ApiError::invalid_request(format!("{} has no release to promote", from_app.name)).response();
+    };
+    let from_release = match get_release(pool.get_ref(), from_app_id, release_id).await {
+        Ok(release) => release,
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Release not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    // The promoted release keeps this app's own env vars, not those of the app it comes from
+    let description = format!("Promote v{} of {}", from_release.version, from_app.name);
+    let env_vars = app.env_vars.unwrap_or_else(|| serde_json::json!({}));
+    match insert_derived_release(pool.get_ref(), app_id, from_app_id, release_id, &description, &env_vars).await {
+        Ok(release) => {
+            println!("{} as v{} of app {}", description, release.version, app_id);
+            HttpResponse::Ok().json(release)
+        }
+        Err(e) => {
+            eprintln!("DB Error storing release: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/releases",
+    tag = "releases",
+    params(("app_id" = String, Path, description = "App id or name"), ReleaseListQuery),
+    responses(
+        (status = 200, description = "Releases, newest first", body = Vec<Release>),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn get_app_releases(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<ReleaseListQuery>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+    if let Err(res) = load_app(pool.get_ref(), app_id).await {
+        return res;
+    }
+    match get_releases(pool.get_ref(), app_id, query.limit.unwrap_or(20)).await {
+        Ok(releases) => HttpResponse::Ok().json(releases),
+        Err(e) => {
+            eprintln!("DB Error fetching releases: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Download a release's source tarball. Used by the agent to extract the release.
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/releases/{release_id}/source",
+    tag = "releases",
+    params(("app_id" = String, Path, description = "App id or name"), ("release_id" = Uuid, Path)),
+    responses(
+        (
+            status = 200,
+            description = "Gzipped tarball of the source",
+            body = Vec<u8>,
+            content_type = "application/gzip",
+        ),
+        (status = 404, description = "App or release not found", body = ApiError),
+    )
+)]
+pub async fn get_app_release_source(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (app_id, release_id) = path.into_inner();
+    if let Err(res) = load_app(pool.get_ref(), app_id).await {
+        return res;
+    }
+    match get_release_source(pool.get_ref(), app_id, release_id).await {
+        Ok(source) => HttpResponse::Ok().content_type("application/gzip").body(source),
+        Err(sqlx::Error::RowNotFound) => ApiError::not_found("Release not found").response(),
+        Err(e) => {
+            eprintln!("DB Error fetching release source: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReleaseListQuery {
+    pub limit: Option<i64>,
+}
@@ -0,0 +1,209 @@
+use crate::config::config;
+use crate::handlers::app_handlers::{agent, agent_error};
+use crate::repository::app_repo::get_application;
+use crate::repository::log_repo::get_run_logs;
+use crate::repository::run_repo::{finish_run, get_run, get_runs, insert_run};
+use crate::secrets::app_secrets;
+use actix_web::{HttpResponse, Responder, web};
+use shared::{ApiError, AppLog, AppRun, Application, FinishRun, NewRun, StartRun};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Create a run record and hand it to the agent. If the agent can't be reached
+/// the run is marked FAILED straight away so it never looks stuck in RUNNING.
+pub(crate) async fn launch_run(
+    pool: &PgPool,
+    app: Application,
+    command: &str,
+    schedule_id: Option<Uuid>,
+) -> Result<AppRun, String> {
+    let app_id = app.id.expect("app loaded from the database has an id");
+    let run = insert_run(pool, app_id, command, schedule_id)
+        .await
+        .map_err(|e| format!("DB Error creating run: {}", e))?;
+
+    println!("Starting run {} for app {}: {}", run.id, app_id, run.command);
+
+    let agent_error = match app_secrets(pool, app_id).await {
+        Ok(secrets) => {
+            let payload = StartRun {
+                app,
+                run: run.clone(),
+                secrets,
+            };
+            match agent()
+                .post(config().agent_url("/runs"))
+                .json(&payload)
+                .send()
+                .await
+            {
+                Ok(res) if res.status().is_success() => return Ok(run),
+                Ok(res) => agent_error(res).await,
+                Err(e) => format!("Cannot reach agent: {}", e),
+            }
+        }
+        Err(e) => e,
+    };
+
+    let failed = FinishRun {
+        exit_code: -1,
+        duration_ms: 0,
+    };
+    if let Err(e) = finish_run(pool, run.app_id, run.id, &failed).await {
+        eprintln!("DB Error failing run: {}", e);
+    }
+    Err(agent_error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/runs",
+    tag = "runs",
+    request_body = NewRun,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The run was started", body = AppRun),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
+pub async fn post_run(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<NewRun>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+
+    if body.command.trim().is_empty() {
+        return ApiError::invalid_request("Command must not be empty").response();
+    }
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    match launch_run(pool.get_ref(), app, body.command.trim(), None).await {
+        Ok(run) => HttpResponse::Ok().json(run),
+        Err(e) => {
+            eprintln!("{}", e);
+            ApiError::agent_unavailable("Run created but agent unavailable").response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/runs",
+    tag = "runs",
+    params(("app_id" = String, Path, description = "App id or name"), RunListQuery),
+    responses(
+        (status = 200, description = "Runs, newest first", body = Vec<AppRun>),
+    )
+)]
+pub async fn get_app_runs(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    query: web::Query<RunListQuery>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+    match get_runs(pool.get_ref(), app_id, query.limit.unwrap_or(20)).await {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => {
+            eprintln!("DB Error fetching runs: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/runs/{run_id}",
+    tag = "runs",
+    params(("app_id" = String, Path, description = "App id or name"), ("run_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "OK", body = AppRun),
+        (status = 404, description = "Run not found", body = ApiError),
+    )
+)]
+pub async fn get_app_run(pool: web::Data<PgPool>, path: web::Path<(Uuid, Uuid)>) -> impl Responder {
+    let (app_id, run_id) = path.into_inner();
+    match get_run(pool.get_ref(), app_id, run_id).await {
+        Ok(run) => HttpResponse::Ok().json(run),
+        Err(sqlx::Error::RowNotFound) => ApiError::not_found("Run not found").response(),
+        Err(e) => {
+            eprintln!("DB Error fetching run: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Called by the agent when a run exits
+#[utoipa::path(
+    patch,
+    path = "/apps/{app_id}/runs/{run_id}",
+    tag = "runs",
+    request_body = FinishRun,
+    params(("app_id" = String, Path, description = "App id or name"), ("run_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The run was finished"),
+        (status = 404, description = "Run not found", body = ApiError),
+    )
+)]
+pub async fn patch_run(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    body: web::Json<FinishRun>,
+) -> impl Responder {
+    let (app_id, run_id) = path.into_inner();
+    match finish_run(pool.get_ref(), app_id, run_id, &body).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(sqlx::Error::RowNotFound) => ApiError::not_found("Run not found").response(),
+        Err(e) => {
+            eprintln!("DB Error finishing run: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/runs/{run_id}/logs",
+    tag = "runs",
+    params(("app_id" = String, Path, description = "App id or name"), ("run_id" = Uuid, Path), RunLogQuery),
+    responses(
+        (status = 200, description = "OK", body = Vec<AppLog>),
+    )
+)]
+pub async fn get_app_run_logs(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<RunLogQuery>,
+) -> impl Responder {
+    let (app_id, run_id) = path.into_inner();
+    match get_run_logs(pool.get_ref(), app_id, run_id, query.after.unwrap_or(0)).await {
+        Ok(logs) => HttpResponse::Ok().json(logs),
+        Err(e) => {
+            eprintln!("DB Error fetching run logs: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RunListQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RunLogQuery {
+    /// Only return log lines with an id greater than this
+    pub after: Option<i64>,
+}
@@ -0,0 +1,177 @@
+use crate::handlers::run_handlers::{RunListQuery, launch_run};
+use crate::repository::app_repo::get_application;
+use crate::repository::run_repo::get_schedule_runs;
+use crate::repository::schedule_repo::{get_schedule, get_schedules, record_schedule_run, replace_schedules};
+use crate::scheduler::{next_run_after, parse_schedule};
+use actix_web::{HttpResponse, Responder, web};
+use chrono::Utc;
+use shared::{ApiError, AppRun, NewSchedule, RunStatus, Schedule};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/schedules",
+    tag = "schedules",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = Vec<Schedule>),
+    )
+)]
+pub async fn get_app_schedules(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+    let app_id = path.into_inner();
+    match get_schedules(pool.get_ref(), app_id).await {
+        Ok(schedules) => HttpResponse::Ok().json(schedules),
+        Err(e) => {
+            eprintln!("DB Error fetching schedules: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Replace the app's schedules with the `[[cron]]` entries from paas.toml
+#[utoipa::path(
+    put,
+    path = "/apps/{app_id}/schedules",
+    tag = "schedules",
+    request_body = Vec<NewSchedule>,
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "The app's new schedules", body = Vec<Schedule>),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn put_app_schedules(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+    body: web::Json<Vec<NewSchedule>>,
+) -> impl Responder {
+    let app_id = path.into_inner();
+
+    if let Err(sqlx::Error::RowNotFound) = get_application(pool.get_ref(), app_id).await {
+        return ApiError::not_found("Application not found").response();
+    }
+
+    let now = Utc::now();
+    let mut seen = HashSet::new();
+    let mut schedules = Vec::new();
+    for schedule in body.into_inner() {
+        if !seen.insert(schedule.name.clone()) {
+            return ApiError::invalid_request(format!("Duplicate cron name '{}'", schedule.name)).response();
+        }
+        if schedule.command.trim().is_empty() {
+            return ApiError::invalid_request(format!("Cron '{}' has an empty command", schedule.name))
+                .response();
+        }
+        if let Err(e) = parse_schedule(&schedule.schedule) {
+            return ApiError::invalid_request(e).response();
+        }
+        let next = match next_run_after(&schedule.schedule, now) {
+            Some(next) => next,
+            None => {
+                return ApiError::invalid_request(format!("Schedule '{}' never fires", schedule.schedule))
+                    .response();
+            }
+        };
+        schedules.push((schedule, next));
+    }
+
+    if let Err(e) = replace_schedules(pool.get_ref(), app_id, &schedules).await {
+        eprintln!("DB Error saving schedules: {}", e);
+        return ApiError::internal().response();
+    }
+
+    match get_schedules(pool.get_ref(), app_id).await {
+        Ok(schedules) => HttpResponse::Ok().json(schedules),
+        Err(e) => {
+            eprintln!("DB Error fetching schedules: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+/// Trigger a schedule immediately without changing its next regular run
+#[utoipa::path(
+    post,
+    path = "/apps/{app_id}/schedules/{schedule_id}/run",
+    tag = "schedules",
+    params(("app_id" = String, Path, description = "App id or name"), ("schedule_id" = Uuid, Path)),
+    responses(
+        (status = 200, description = "The run was started", body = AppRun),
+        (status = 404, description = "Schedule not found", body = ApiError),
+        (status = 409, description = "The schedule is already running", body = ApiError),
+        (status = 503, description = "The agent is unavailable", body = ApiError),
+    )
+)]
+pub async fn run_schedule_now(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+) -> impl Responder {
+    let (app_id, schedule_id) = path.into_inner();
+
+    let schedule = match get_schedule(pool.get_ref(), app_id, schedule_id).await {
+        Ok(schedule) => schedule,
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Schedule not found").response(),
+        Err(e) => {
+            eprintln!("DB Error fetching schedule: {}", e);
+            return ApiError::internal().response();
+        }
+    };
+
+    if matches!(schedule.last_status, Some(RunStatus::RUNNING)) {
+        return ApiError::conflict(format!(
+            "The previous run of '{}' is still running",
+            schedule.name
+        ))
+        .response();
+    }
+
+    let app = match get_application(pool.get_ref(), app_id).await {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::not_found("Application not found").response();
+        }
+    };
+
+    match launch_run(pool.get_ref(), app, &schedule.command, Some(schedule.id)).await {
+        Ok(run) => {
+            if let Err(e) =
+                record_schedule_run(pool.get_ref(), schedule.id, Some(run.id), schedule.next_run_at).await
+            {
+                eprintln!("DB Error updating schedule: {}", e);
+            }
+            HttpResponse::Ok().json(run)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ApiError::agent_unavailable("Run created but agent unavailable").response()
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/schedules/{schedule_id}/runs",
+    tag = "schedules",
+    params(("app_id" = String, Path, description = "App id or name"), ("schedule_id" = Uuid, Path), RunListQuery),
+    responses(
+        (status = 200, description = "Runs, newest first", body = Vec<AppRun>),
+    )
+)]
+pub async fn get_schedule_history(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<RunListQuery>,
+) -> impl Responder {
+    let (_app_id, schedule_id) = path.into_inner();
+    match get_schedule_runs(pool.get_ref(), schedule_id, query.limit.unwrap_or(20)).await {
+        Ok(runs) => HttpResponse::Ok().json(runs),
+        Err(e) => {
+            eprintln!("DB Error fetching schedule runs: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
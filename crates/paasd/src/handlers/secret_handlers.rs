@@ -0,0 +1,107 @@
+use crate::handlers::app_handlers::load_app;
+use crate::repository::app_repo::get_application;
+use crate::repository::secret_repo::{delete_secret, get_secret_names, upsert_secret};
+use crate::secrets::encrypt;
+use actix_web::{HttpResponse, Responder, web};
+use shared::{ApiError, ErrorCode, SecretInfo, SetSecret, is_valid_env_name};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Names and last-updated times only; values can't be read back
+#[utoipa::path(
+    get,
+    path = "/apps/{app_id}/secrets",
+    tag = "secrets",
+    params(("app_id" = String, Path, description = "App id or name")),
+    responses(
+        (status = 200, description = "OK", body = Vec<SecretInfo>),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn get_app_secrets(pool: web::Data<PgPool>, path: web::Path<Uuid>) -> impl Responder {
+    let app_id = path.into_inner();
+    if let Err(res) = load_app(pool.get_ref(), app_id).await {
+        return res;
+    }
+    match get_secret_names(pool.get_ref(), app_id).await {
+        Ok(secrets) => HttpResponse::Ok().json(secrets),
+        Err(e) => {
+            eprintln!("DB Error fetching secrets: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/apps/{app_id}/secrets/{name}",
+    tag = "secrets",
+    request_body = SetSecret,
+    params(("app_id" = String, Path, description = "App id or name"), ("name" = String, Path)),
+    responses(
+        (status = 200, description = "OK", body = SecretInfo),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "App not found", body = ApiError),
+    )
+)]
+pub async fn put_app_secret(
+    pool: web::Data<PgPool>,
+    path: web::Path<(Uuid, String)>,
+    body: web::Json<SetSecret>,
+) -> impl Responder {
+    let (app_id, name) = path.into_inner();
+    if !is_valid_env_name(&name) {
+        return ApiError::invalid_request("Secret names may only contain letters, digits and '_', and can't start with a digit")
+            .response();
+    }
+
+    match get_application(pool.get_ref(), app_id).await {
+        Ok(_) => {}
+        Err(sqlx::Error::RowNotFound) => return ApiError::not_found("Application not found").response(),
+        Err(e) => {
+            eprintln!("DB Error: {}", e);
+            return ApiError::internal().response();
+        }
+    }
+
+    let (ciphertext, nonce) = match encrypt(&body.value) {
+        Ok(encrypted) => encrypted,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ApiError::new(ErrorCode::Internal, e).response();
+        }
+    };
+
+    match upsert_secret(pool.get_ref(), app_id, &name, &ciphertext, &nonce).await {
+        Ok(secret) => HttpResponse::Ok().json(secret),
+        Err(e) => {
+            eprintln!("DB Error storing secret: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/apps/{app_id}/secrets/{name}",
+    tag = "secrets",
+    params(("app_id" = String, Path, description = "App id or name"), ("name" = String, Path)),
+    responses(
+        (status = 200, description = "The secret was removed"),
+        (status = 404, description = "App or secret not found", body = ApiError),
+    )
+)]
+pub async fn delete_app_secret(pool: web::Data<PgPool>, path: web::Path<(Uuid, String)>) -> impl Responder {
+    let (app_id, name) = path.into_inner();
+    if let Err(res) = load_app(pool.get_ref(), app_id).await {
+        return res;
+    }
+    match delete_secret(pool.get_ref(), app_id, &name).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => ApiError::not_found("Secret not found").response(),
+        Err(e) => {
+            eprintln!("DB Error deleting secret: {}", e);
+            ApiError::internal().response()
+        }
+    }
+}
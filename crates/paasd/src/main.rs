@@ -1,20 +1,35 @@
+mod app_names;
+mod config;
+mod git_host;
 mod handlers;
 mod models;
+mod openapi;
+mod ports;
 mod repository;
+mod routes;
+mod scheduler;
+mod secrets;
 
-use std::env;
-
-use crate::handlers::app_handlers::{delete_program, get_live_status, get_program, get_programs, patch_program, post_program, redeploy_program};
-use crate::handlers::log_handlers::{get_app_logs, post_log};
+use crate::handlers::app_handlers::purge_expired_apps;
+use crate::openapi::ApiDoc;
 use crate::repository::app_repo::mark_stale_apps_stopped;
+use crate::repository::process_repo::mark_stale_processes_stopped;
+use crate::repository::run_repo::mark_stale_runs_failed;
 use crate::repository::log_repo::cleanup_logs;
 use actix_web::{App, HttpServer, web};
-use sqlx::PgPool;
+use shared::error;
+use sqlx::postgres::PgPoolOptions;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 //db connection
-async fn connect_db() -> Result<sqlx::PgPool, sqlx::Error> {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool = PgPool::connect(&database_url).await?;
+async fn connect_db(config: &config::Config) -> Result<sqlx::PgPool, sqlx::Error> {
+    let database_url = config.database_url.as_deref().expect("DATABASE_URL must be set");
+    let pool = PgPoolOptions::new()
+        .max_connections(config.db_max_connections)
+        .acquire_timeout(config.db_timeout())
+        .connect(database_url)
+        .await?;
 
     Ok(pool)
 }
@@ -24,14 +39,26 @@ async fn main() -> std::io::Result<()> {
     //load the environmnet variables at the start of the server
     dotenvy::dotenv().ok();
 
-    let addr = ("127.0.0.1", 8080);
-    let pool = connect_db().await.expect("DB connection failed");
+    let config = config::load().unwrap_or_else(|e| {
+        eprintln!("Startup: {}", e);
+        std::process::exit(1);
+    });
+    let pool = connect_db(config).await.expect("DB connection failed");
+    if let Err(e) = secrets::init_key() {
+        eprintln!("Startup: secrets are unavailable: {}", e);
+    }
 
     // On startup, mark any apps that were left in RUNNING/PENDING state as STOPPED
     match mark_stale_apps_stopped(&pool).await {
         Ok(_) => println!("Startup: cleaned up stale app records"),
         Err(e) => eprintln!("Startup: failed to clean stale apps: {}", e),
     }
+    if let Err(e) = mark_stale_processes_stopped(&pool).await {
+        eprintln!("Startup: failed to clean stale processes: {}", e);
+    }
+    if let Err(e) = mark_stale_runs_failed(&pool).await {
+        eprintln!("Startup: failed to clean stale runs: {}", e);
+    }
 
     // Run log cleanup on startup
     cleanup_logs(&pool).await;
@@ -45,21 +72,33 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
-    println!("app is bound to http://{}:{}", addr.0, addr.1);
+    // Purge destroyed apps once their restore window has passed
+    let pool_purge = pool.clone();
+    tokio::spawn(async move {
+        loop {
+            purge_expired_apps(&pool_purge).await;
+            tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60)).await;
+        }
+    });
+
+    // Trigger cron schedules
+    tokio::spawn(scheduler::run_scheduler(pool.clone()));
+
+    let api_doc = ApiDoc::openapi();
+    println!("app is bound to http://{}", config.listen);
     HttpServer::new(move || {
         App::new()
+            .wrap(app_names::ResolveAppNames)
+            .wrap(error::RequestId)
             .app_data(web::Data::new(pool.clone()))
-            .route("/apps", web::post().to(post_program))
-            .route("/apps", web::get().to(get_programs))
-            .route("/apps/{app_id}", web::get().to(get_program))
-            .route("/apps/{app_id}", web::delete().to(delete_program))
-            .route("/apps/{app_id}/status", web::get().to(get_live_status))
-            .route("/apps/{app_id}", web::patch().to(patch_program))
-            .route("/apps/{app_id}/redeploy", web::post().to(redeploy_program))
-            .route("/apps/{app_id}/logs", web::post().to(post_log))
-            .route("/apps/{app_id}/logs", web::get().to(get_app_logs))
+            .app_data(error::json_config())
+            .app_data(error::query_config())
+            .app_data(error::path_config())
+            .default_service(web::to(error::no_route))
+            .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", api_doc.clone()))
+            .configure(routes::configure)
     })
-    .bind(addr)?
+    .bind(config.listen.as_str())?
     .run()
     .await
 }
@@ -0,0 +1,125 @@
+//! The OpenAPI document served at `/openapi.json`, with a docs page at `/docs`
+
+use shared::{ApiError, ErrorCode};
+use utoipa::OpenApi;
+
+use crate::handlers::{
+    app_handlers, discovery_handlers, env_handlers, git_handlers, log_handlers, process_handlers, release_handlers,
+    run_handlers, schedule_handlers, secret_handlers,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "paasd", description = "Deploy and run apps, their processes, releases, runs and schedules"),
+    paths(
+        app_handlers::post_program,
+        app_handlers::get_programs,
+        app_handlers::get_program,
+        app_handlers::delete_program,
+        app_handlers::get_live_status,
+        app_handlers::patch_program,
+        app_handlers::redeploy_program,
+        app_handlers::restore_program,
+        log_handlers::post_log,
+        log_handlers::get_app_logs,
+        process_handlers::get_app_processes,
+        process_handlers::get_app_process,
+        process_handlers::patch_app_process,
+        process_handlers::scale_process,
+        process_handlers::restart_process_type,
+        git_handlers::post_app_git,
+        git_handlers::git_deploy,
+        release_handlers::post_release,
+        release_handlers::get_app_releases,
+        release_handlers::promote_release,
+        release_handlers::get_app_release_source,
+        run_handlers::post_run,
+        run_handlers::get_app_runs,
+        run_handlers::get_app_run,
+        run_handlers::patch_run,
+        run_handlers::get_app_run_logs,
+        env_handlers::get_app_env,
+        env_handlers::put_app_env,
+        env_handlers::patch_app_env,
+        env_handlers::delete_app_env,
+        secret_handlers::get_app_secrets,
+        secret_handlers::put_app_secret,
+        secret_handlers::delete_app_secret,
+        schedule_handlers::get_app_schedules,
+        schedule_handlers::put_app_schedules,
+        schedule_handlers::run_schedule_now,
+        schedule_handlers::get_schedule_history,
+        discovery_handlers::get_discovery,
+    ),
+    components(schemas(ApiError, ErrorCode)),
+    tags(
+        (name = "apps"),
+        (name = "processes"),
+        (name = "releases"),
+        (name = "runs"),
+        (name = "logs"),
+        (name = "env"),
+        (name = "secrets"),
+        (name = "schedules"),
+        (name = "git"),
+        (name = "discovery"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::Method;
+    use utoipa::OpenApi;
+    use utoipa::openapi::path::{HttpMethod, PathItem};
+
+    use super::ApiDoc;
+    use crate::routes::routes;
+
+    fn operation(item: &PathItem, method: &Method) -> bool {
+        match *method {
+            Method::GET => item.get.is_some(),
+            Method::POST => item.post.is_some(),
+            Method::PUT => item.put.is_some(),
+            Method::PATCH => item.patch.is_some(),
+            Method::DELETE => item.delete.is_some(),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let doc = ApiDoc::openapi();
+        let missing: Vec<String> = routes()
+            .iter()
+            .filter(|r| !doc.paths.paths.get(r.path).is_some_and(|item| operation(item, &r.method)))
+            .map(|r| format!("{} {}", r.method, r.path))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "routes without a #[utoipa::path] listed in ApiDoc: {:?}",
+            missing
+        );
+    }
+
+    #[test]
+    fn every_documented_operation_is_routed() {
+        let routes = routes();
+        let methods = [
+            (HttpMethod::Get, Method::GET),
+            (HttpMethod::Post, Method::POST),
+            (HttpMethod::Put, Method::PUT),
+            (HttpMethod::Patch, Method::PATCH),
+            (HttpMethod::Delete, Method::DELETE),
+        ];
+        let mut stale = Vec::new();
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            for (_, method) in methods.iter().filter(|(_, m)| operation(&item, m)) {
+                if !routes.iter().any(|r| r.path == path && r.method == method) {
+                    stale.push(format!("{} {}", method, path));
+                }
+            }
+        }
+        assert!(stale.is_empty(), "documented operations paasd doesn't serve: {:?}", stale);
+    }
+}
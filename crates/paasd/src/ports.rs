@@ -0,0 +1,47 @@
+use std::net::TcpListener;
+use std::ops::RangeInclusive;
+
+/// Ports handed out to apps that don't ask for a specific one
+const DEFAULT_PORT_RANGE: RangeInclusive<i32> = 9000..=9999;
+
+/// The allocation range, from `PAAS_PORT_RANGE` (e.g. `9000-9999`)
+pub fn port_range() -> RangeInclusive<i32> {
+    let Ok(value) = std::env::var("PAAS_PORT_RANGE") else {
+        return DEFAULT_PORT_RANGE;
+    };
+    let parsed = value.split_once('-').and_then(|(start, end)| {
+        let start: i32 = start.trim().parse().ok()?;
+        let end: i32 = end.trim().parse().ok()?;
+        (1024 < start && start <= end && end <= 65535).then_some(start..=end)
+    });
+    parsed.unwrap_or_else(|| {
+        eprintln!("Invalid PAAS_PORT_RANGE '{}', using {:?}", value, DEFAULT_PORT_RANGE);
+        DEFAULT_PORT_RANGE
+    })
+}
+
+/// Whether nothing on this host is listening on the port
+pub fn is_bindable(port: i32) -> bool {
+    u16::try_from(port).is_ok_and(|port| TcpListener::bind(("0.0.0.0", port)).is_ok())
+}
+
+/// The host check for a port an app moves to. A port the agent found the app's own web process
+/// listening on can't be bound by paasd, but it's the app itself holding it, so only other apps conflict.
+pub fn host_check(detected: bool) -> fn(i32) -> bool {
+    if detected { |_| true } else { is_bindable }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::host_check;
+
+    #[test]
+    fn a_port_the_app_listens_on_is_not_a_host_conflict() {
+        let listener = TcpListener::bind(("0.0.0.0", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port() as i32;
+        assert!(host_check(true)(port), "the app's detected port was rejected");
+        assert!(!host_check(false)(port), "a port another process listens on was accepted");
+    }
+}
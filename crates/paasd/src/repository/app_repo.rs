@@ -1,15 +1,122 @@
-use crate::models::{Application, PatchApplication};
-use sqlx::{Error, PgPool, Row};
+use crate::models::{AppStatus, Application, PatchApplication};
+use chrono::{DateTime, Utc};
+use shared::AppStats;
+use sqlx::{Error, FromRow, PgPool, Row};
 use uuid::Uuid;
 
+const APP_COLUMNS: &str = "id, name, command, status, port, working_dir, pid, env_vars, processes, build_command, \
+     build_timeout, release_id, links, on_link_change, labels";
+
+/// Days a destroyed app can be restored before it's purged
+pub const RESTORE_WINDOW_DAYS: i64 = 7;
+
+/// Permanently delete an app along with its processes, logs, runs, releases, schedules and secrets
 pub async fn delete_application(pool: &PgPool, app_id: Uuid) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+    // Releases promoted from this app into others share its source; give them their own copy
+    sqlx::query(
+        "UPDATE releases r SET source = s.source, source_release_id = NULL
+         FROM releases s
+         WHERE r.source_release_id = s.id AND s.app_id = $1 AND r.app_id != $1",
+    )
+    .bind(app_id)
+    .execute(&mut tx)
+    .await?;
     sqlx::query("DELETE FROM apps WHERE id = $1")
         .bind(app_id)
-        .execute(pool)
+        .execute(&mut tx)
         .await?;
+    tx.commit().await?;
     Ok(())
 }
 
+/// Hide the app until it's restored or purged, and release its port.
+/// Returns when it will be purged.
+pub async fn soft_delete_application(pool: &PgPool, app_id: Uuid) -> Result<DateTime<Utc>, Error> {
+    let row: (DateTime<Utc>,) = sqlx::query_as(
+        "UPDATE apps SET deleted_at = now(), status = 'STOPPED'::app_status, pid = NULL
+         WHERE id = $1 AND deleted_at IS NULL
+         RETURNING deleted_at + make_interval(days => $2)",
+    )
+    .bind(app_id)
+    .bind(RESTORE_WINDOW_DAYS as i32)
+    .fetch_one(pool)
+    .await?;
+    Ok(row.0)
+}
+
+/// A destroyed app that hasn't been purged yet
+pub async fn get_deleted_application(pool: &PgPool, app_id: Uuid) -> Result<Application, Error> {
+    sqlx::query_as(&format!(
+        "SELECT {} FROM apps WHERE id = $1 AND deleted_at IS NOT NULL",
+        APP_COLUMNS
+    ))
+    .bind(app_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Destroyed apps whose restore window has passed
+pub async fn get_expired_applications(pool: &PgPool) -> Result<Vec<Uuid>, Error> {
+    let rows: Vec<(Uuid,)> =
+        sqlx::query_as("SELECT id FROM apps WHERE deleted_at < now() - make_interval(days => $1)")
+            .bind(RESTORE_WINDOW_DAYS as i32)
+            .fetch_all(pool)
+            .await?;
+    Ok(rows.into_iter().map(|(id,)| id).collect())
+}
+
+/// Bring a destroyed app back, stopped. Its old port is kept unless another app took it
+/// in the meantime, then it gets the first free one in `range` that `is_free` accepts.
+/// Returns the app's port, or `None` if it needed a new one and the range is exhausted.
+pub async fn restore_application(
+    pool: &PgPool,
+    app_id: Uuid,
+    range: std::ops::RangeInclusive<i32>,
+    is_free: impl Fn(i32) -> bool,
+) -> Result<Option<i32>, Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(PORT_ALLOCATION_LOCK)
+        .execute(&mut tx)
+        .await?;
+
+    let (port,): (i32,) = sqlx::query_as("SELECT port FROM apps WHERE id = $1 AND deleted_at IS NOT NULL")
+        .bind(app_id)
+        .fetch_one(&mut tx)
+        .await?;
+    let (conflicts,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM apps WHERE port = $1 AND id != $2 AND deleted_at IS NULL")
+            .bind(port)
+            .bind(app_id)
+            .fetch_one(&mut tx)
+            .await?;
+
+    let port = if conflicts == 0 {
+        port
+    } else {
+        let taken: Vec<(i32,)> =
+            sqlx::query_as("SELECT port FROM apps WHERE port BETWEEN $1 AND $2 AND deleted_at IS NULL")
+                .bind(range.start())
+                .bind(range.end())
+                .fetch_all(&mut tx)
+                .await?;
+        let taken: std::collections::HashSet<i32> = taken.into_iter().map(|(port,)| port).collect();
+        match range.filter(|p| !taken.contains(p)).find(|p| is_free(*p)) {
+            Some(port) => port,
+            None => return Ok(None),
+        }
+    };
+
+    sqlx::query("UPDATE apps SET deleted_at = NULL, port = $2 WHERE id = $1")
+        .bind(app_id)
+        .bind(port)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Some(port))
+}
+
 pub async fn mark_stale_apps_stopped(pool: &PgPool) -> Result<(), Error> {
     sqlx::query(
         "UPDATE apps SET status = 'STOPPED'::app_status, pid = NULL WHERE status != 'STOPPED'::app_status",
@@ -19,41 +126,212 @@ pub async fn mark_stale_apps_stopped(pool: &PgPool) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn is_port_in_use(pool: &PgPool, port: i32) -> Result<bool, Error> {
-    let row: (i64,) =
-        sqlx::query_as("SELECT COUNT(*) FROM apps WHERE port = $1 AND status != 'STOPPED'::app_status")
+/// Why an app can't have the port it asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortConflict {
+    /// Another app holds it, running or not
+    App,
+    /// Something else on this host listens on it
+    Host,
+}
+
+/// Take the allocation lock and check that no other app holds `port` and `is_free` accepts it.
+/// Ports of stopped apps count as held, like in `insert_application_with_free_port`.
+async fn check_port_locked(
+    conn: &mut sqlx::PgConnection,
+    port: i32,
+    app_id: Option<Uuid>,
+    is_free: impl Fn(i32) -> bool,
+) -> Result<Option<PortConflict>, Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(PORT_ALLOCATION_LOCK)
+        .execute(&mut *conn)
+        .await?;
+    let (holders,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM apps WHERE port = $1 AND id IS DISTINCT FROM $2 AND deleted_at IS NULL")
             .bind(port)
-            .fetch_one(pool)
+            .bind(app_id)
+            .fetch_one(&mut *conn)
+            .await?;
+    if holders > 0 {
+        return Ok(Some(PortConflict::App));
+    }
+    if !is_free(port) {
+        return Ok(Some(PortConflict::Host));
+    }
+    Ok(None)
+}
+
+/// Insert the app on the port it asked for, if no other app has it and `is_free` accepts it.
+/// Check and insert happen under the allocation lock.
+pub async fn insert_application_on_port(
+    pool: &PgPool,
+    app: &Application,
+    is_free: impl Fn(i32) -> bool,
+) -> Result<Result<Uuid, PortConflict>, Error> {
+    let mut tx = pool.begin().await?;
+    if let Some(conflict) = check_port_locked(&mut tx, app.port, None, is_free).await? {
+        return Ok(Err(conflict));
+    }
+    let app_id = insert_application_on(&mut tx, app).await?;
+    tx.commit().await?;
+    Ok(Ok(app_id))
+}
+
+/// Move the app to `port`, if no other app has it and `is_free` accepts it.
+/// Check and update happen under the allocation lock.
+pub async fn move_application_to_port(
+    pool: &PgPool,
+    app_id: Uuid,
+    port: i32,
+    is_free: impl Fn(i32) -> bool,
+) -> Result<Result<(), PortConflict>, Error> {
+    let mut tx = pool.begin().await?;
+    if let Some(conflict) = check_port_locked(&mut tx, port, Some(app_id), is_free).await? {
+        return Ok(Err(conflict));
+    }
+    sqlx::query("UPDATE apps SET port = $2 WHERE id = $1")
+        .bind(app_id)
+        .bind(port)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    Ok(Ok(()))
+}
+
+/// Ports in `range` held by apps that haven't been destroyed, running or not
+pub async fn get_taken_ports(
+    pool: &PgPool,
+    range: std::ops::RangeInclusive<i32>,
+) -> Result<std::collections::HashSet<i32>, Error> {
+    let taken: Vec<(i32,)> =
+        sqlx::query_as("SELECT port FROM apps WHERE port BETWEEN $1 AND $2 AND deleted_at IS NULL")
+            .bind(range.start())
+            .bind(range.end())
+            .fetch_all(pool)
             .await?;
-    Ok(row.0 > 0)
+    Ok(taken.into_iter().map(|(port,)| port).collect())
 }
 
-pub async fn insert_application(pool: &PgPool, app: &Application) -> Result<Uuid, Error> {
+/// Lock key serializing port allocation across concurrent deploys
+const PORT_ALLOCATION_LOCK: i64 = 0x7061_6173;
+
+/// Insert the app on the first port in `range` that no other app has and `is_free` accepts.
+/// Allocation and insert happen under one lock, so concurrent deploys never get the same port.
+/// Returns `None` if the range is exhausted.
+pub async fn insert_application_with_free_port(
+    pool: &PgPool,
+    app: &Application,
+    range: std::ops::RangeInclusive<i32>,
+    is_free: impl Fn(i32) -> bool,
+) -> Result<Option<(Uuid, i32)>, Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(PORT_ALLOCATION_LOCK)
+        .execute(&mut tx)
+        .await?;
+
+    let taken: Vec<(i32,)> =
+        sqlx::query_as("SELECT port FROM apps WHERE port BETWEEN $1 AND $2 AND deleted_at IS NULL")
+        .bind(range.start())
+        .bind(range.end())
+        .fetch_all(&mut tx)
+        .await?;
+    let taken: std::collections::HashSet<i32> = taken.into_iter().map(|(port,)| port).collect();
+
+    let Some(port) = range.filter(|p| !taken.contains(p)).find(|p| is_free(*p)) else {
+        return Ok(None);
+    };
+
+    let mut app = app.clone();
+    app.port = port;
+    let app_id = insert_application_on(&mut tx, &app).await?;
+    tx.commit().await?;
+    Ok(Some((app_id, port)))
+}
+
+async fn insert_application_on(conn: &mut sqlx::PgConnection, app: &Application) -> Result<Uuid, Error> {
     let query =
-        "INSERT INTO apps (name, command, status, port, working_dir, env_vars) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id";
+        "INSERT INTO apps (name, command, status, port, working_dir, env_vars, processes, build_command, build_timeout, links, on_link_change, labels) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id";
 
     let row = sqlx::query(query)
         .bind(&app.name)
         .bind(&app.command)
         .bind(&app.status)
-        .bind(&app.port)
+        .bind(app.port)
         .bind(&app.working_dir)
         .bind(serde_json::to_value(&app.env_vars).unwrap_or(serde_json::json!({})))
-        .fetch_one(pool)
+        .bind(app.processes.clone().unwrap_or(serde_json::json!({})))
+        .bind(&app.build_command)
+        .bind(app.build_timeout)
+        .bind(app.links.clone().unwrap_or(serde_json::json!([])))
+        .bind(app.on_link_change.as_deref().unwrap_or(shared::DEFAULT_LINK_CHANGE))
+        .bind(app.labels.clone().unwrap_or(serde_json::json!({})))
+        .fetch_one(conn)
         .await?;
 
     Ok(row.get("id"))
 }
 
 pub async fn get_applications(pool: &PgPool) -> Result<Vec<Application>, Error> {
-    let apps = sqlx::query_as(r#"SELECT id, name, command, status, port, working_dir, pid, env_vars FROM apps"#)
+    let apps = sqlx::query_as(&format!("SELECT {} FROM apps WHERE deleted_at IS NULL", APP_COLUMNS))
         .fetch_all(pool)
         .await?;
     Ok(apps)
 }
 
+/// Filters, order and page of `GET /apps`
+pub struct AppListQuery {
+    pub status: Option<AppStatus>,
+    /// Apps must have all of these labels
+    pub labels: serde_json::Map<String, serde_json::Value>,
+    /// List destroyed apps instead of live ones
+    pub deleted: bool,
+    /// An ORDER BY expression on `apps`; never user input
+    pub order_by: &'static str,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// One page of apps with their stats, and the number of apps matching the filters
+pub async fn list_applications(
+    pool: &PgPool,
+    list: &AppListQuery,
+) -> Result<(Vec<(Application, AppStats)>, i64), Error> {
+    let mut conditions = vec![
+        "labels @> $1".to_string(),
+        if list.deleted { "deleted_at IS NOT NULL" } else { "deleted_at IS NULL" }.to_string(),
+    ];
+    if list.status.is_some() {
+        conditions.push("status = $2".to_string());
+    }
+    let limit_param = if list.status.is_some() { 3 } else { 2 };
+    let query = format!(
+        "SELECT {}, started_at, deployed_at, restart_count, deleted_at, COUNT(*) OVER () AS total FROM apps
+         WHERE {} ORDER BY {}, name ASC LIMIT ${} OFFSET ${}",
+        APP_COLUMNS,
+        conditions.join(" AND "),
+        list.order_by,
+        limit_param,
+        limit_param + 1
+    );
+
+    let mut sql = sqlx::query(&query).bind(serde_json::Value::Object(list.labels.clone()));
+    if let Some(status) = &list.status {
+        sql = sql.bind(status);
+    }
+    let rows = sql.bind(list.limit).bind(list.offset).fetch_all(pool).await?;
+
+    let total = rows.first().map(|row| row.get("total")).unwrap_or(0);
+    let apps = rows
+        .iter()
+        .map(|row| Ok((Application::from_row(row)?, AppStats::from_row(row)?)))
+        .collect::<Result<_, Error>>()?;
+    Ok((apps, total))
+}
+
 pub async fn get_application(pool: &PgPool, app_id: Uuid) -> Result<Application, Error> {
-    let app = sqlx::query_as(r#"SELECT id, name, command, status, port, working_dir, pid, env_vars FROM apps where id = $1"#)
+    let app = sqlx::query_as(&format!("SELECT {} FROM apps WHERE id = $1 AND deleted_at IS NULL", APP_COLUMNS))
         .bind(app_id)
         .fetch_one(pool)
         .await?;
@@ -61,6 +339,71 @@ pub async fn get_application(pool: &PgPool, app_id: Uuid) -> Result<Application,
     Ok(app)
 }
 
+pub async fn get_application_by_name(pool: &PgPool, name: &str) -> Result<Application, Error> {
+    let app = sqlx::query_as(&format!("SELECT {} FROM apps WHERE name = $1 AND deleted_at IS NULL", APP_COLUMNS))
+        .bind(name)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(app)
+}
+
+/// Destroyed apps are only found with `include_deleted`, so they can be restored or purged by name
+pub async fn get_app_id_by_name(pool: &PgPool, name: &str, include_deleted: bool) -> Result<Option<Uuid>, Error> {
+    let row: Option<(Uuid,)> = sqlx::query_as("SELECT id FROM apps WHERE name = $1 AND ($2 OR deleted_at IS NULL)")
+        .bind(name)
+        .bind(include_deleted)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+/// Whether inserting or renaming an app failed because its name is taken
+pub fn is_name_taken(error: &Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.constraint())
+        .is_some_and(|constraint| constraint == "apps_name_key")
+}
+
+/// Apps that aren't stopped and list `name` in their `links`
+pub async fn get_linking_applications(pool: &PgPool, name: &str) -> Result<Vec<Application>, Error> {
+    let apps = sqlx::query_as(&format!(
+        "SELECT {} FROM apps WHERE links ? $1 AND status != 'STOPPED'::app_status AND deleted_at IS NULL",
+        APP_COLUMNS
+    ))
+    .bind(name)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(apps)
+}
+
+/// Record that the app's web process (re)started now, for its uptime
+pub async fn mark_started(pool: &PgPool, app_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE apps SET started_at = now() WHERE id = $1")
+        .bind(app_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn mark_deployed(pool: &PgPool, app_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE apps SET deployed_at = now() WHERE id = $1")
+        .bind(app_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn increment_restart_count(pool: &PgPool, app_id: Uuid) -> Result<(), Error> {
+    sqlx::query("UPDATE apps SET restart_count = restart_count + 1 WHERE id = $1")
+        .bind(app_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
 pub async fn clear_pid(pool: &PgPool, app_id: Uuid) -> Result<(), Error> {
     sqlx::query("UPDATE apps SET pid = NULL WHERE id = $1")
         .bind(app_id)
@@ -105,6 +448,34 @@ pub async fn patch_application(
         fields.push(format!("env_vars = ${}", fields.len() + 1));
     }
 
+    if app.processes.is_some() {
+        fields.push(format!("processes = ${}", fields.len() + 1));
+    }
+
+    if app.build_command.is_some() {
+        fields.push(format!("build_command = ${}", fields.len() + 1));
+    }
+
+    if app.build_timeout.is_some() {
+        fields.push(format!("build_timeout = ${}", fields.len() + 1));
+    }
+
+    if app.release_id.is_some() {
+        fields.push(format!("release_id = ${}", fields.len() + 1));
+    }
+
+    if app.links.is_some() {
+        fields.push(format!("links = ${}", fields.len() + 1));
+    }
+
+    if app.on_link_change.is_some() {
+        fields.push(format!("on_link_change = ${}", fields.len() + 1));
+    }
+
+    if app.labels.is_some() {
+        fields.push(format!("labels = ${}", fields.len() + 1));
+    }
+
     if fields.is_empty() {
         return Ok(());
     }
@@ -142,6 +513,34 @@ pub async fn patch_application(
         sql = sql.bind(env_vars);
     }
 
+    if let Some(processes) = &app.processes {
+        sql = sql.bind(processes);
+    }
+
+    if let Some(build_command) = &app.build_command {
+        sql = sql.bind(build_command);
+    }
+
+    if let Some(build_timeout) = &app.build_timeout {
+        sql = sql.bind(build_timeout);
+    }
+
+    if let Some(release_id) = &app.release_id {
+        sql = sql.bind(release_id);
+    }
+
+    if let Some(links) = &app.links {
+        sql = sql.bind(links);
+    }
+
+    if let Some(on_link_change) = &app.on_link_change {
+        sql = sql.bind(on_link_change);
+    }
+
+    if let Some(labels) = &app.labels {
+        sql = sql.bind(labels);
+    }
+
     sql = sql.bind(app_id);
 
     sql.execute(pool).await?;
@@ -4,35 +4,55 @@ use shared::{AppLog, NewAppLog};
 
 pub async fn insert_log(pool: &PgPool, log: &NewAppLog) -> Result<(), Error> {
     sqlx::query(
-        "INSERT INTO logs (app_id, stream, message) VALUES ($1, $2, $3)"
+        "INSERT INTO logs (app_id, stream, message, process_type, run_id) VALUES ($1, $2, $3, $4, $5)"
     )
     .bind(log.app_id)
     .bind(&log.stream)
     .bind(&log.message)
+    .bind(log.process_type.as_deref().unwrap_or(shared::WEB_PROCESS))
+    .bind(log.run_id)
     .execute(pool)
     .await?;
 
     Ok(())
 }
 
-pub async fn get_logs(pool: &PgPool, app_id: Uuid, limit: i64) -> Result<Vec<AppLog>, Error> {
+pub async fn get_logs(pool: &PgPool, app_id: Uuid, limit: i64, process_type: Option<&str>) -> Result<Vec<AppLog>, Error> {
     let logs = sqlx::query_as(
-        "SELECT id, app_id, stream, message, created_at FROM logs WHERE app_id = $1 ORDER BY created_at ASC LIMIT $2"
+        "SELECT id, app_id, stream, message, process_type, run_id, created_at FROM logs
+         WHERE app_id = $1 AND ($3::TEXT IS NULL OR process_type = $3) ORDER BY created_at ASC LIMIT $2"
     )
     .bind(app_id)
     .bind(limit)
+    .bind(process_type)
     .fetch_all(pool)
     .await?;
 
     Ok(logs)
 }
 
-pub async fn get_logs_since(pool: &PgPool, app_id: Uuid, since: chrono::DateTime<chrono::Utc>) -> Result<Vec<AppLog>, Error> {
+pub async fn get_logs_since(pool: &PgPool, app_id: Uuid, since: chrono::DateTime<chrono::Utc>, process_type: Option<&str>) -> Result<Vec<AppLog>, Error> {
     let logs = sqlx::query_as(
-        "SELECT id, app_id, stream, message, created_at FROM logs WHERE app_id = $1 AND created_at > $2 ORDER BY created_at ASC"
+        "SELECT id, app_id, stream, message, process_type, run_id, created_at FROM logs
+         WHERE app_id = $1 AND created_at > $2 AND ($3::TEXT IS NULL OR process_type = $3) ORDER BY created_at ASC"
     )
     .bind(app_id)
     .bind(since)
+    .bind(process_type)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(logs)
+}
+
+pub async fn get_run_logs(pool: &PgPool, app_id: Uuid, run_id: Uuid, after_id: i64) -> Result<Vec<AppLog>, Error> {
+    let logs = sqlx::query_as(
+        "SELECT id, app_id, stream, message, process_type, run_id, created_at FROM logs
+         WHERE app_id = $1 AND run_id = $2 AND id > $3 ORDER BY id ASC"
+    )
+    .bind(app_id)
+    .bind(run_id)
+    .bind(after_id)
     .fetch_all(pool)
     .await?;
 
@@ -1,2 +1,7 @@
 pub mod app_repo;
 pub mod log_repo;
+pub mod process_repo;
+pub mod release_repo;
+pub mod run_repo;
+pub mod schedule_repo;
+pub mod secret_repo;
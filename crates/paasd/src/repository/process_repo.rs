@@ -0,0 +1,144 @@
+use shared::{AppProcess, AppStatus, PatchProcess};
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+pub async fn insert_process(
+    pool: &PgPool,
+    app_id: Uuid,
+    process_type: &str,
+    instance: i32,
+    command: &str,
+) -> Result<AppProcess, Error> {
+    let process = sqlx::query_as(
+        "INSERT INTO processes (app_id, process_type, instance, command) VALUES ($1, $2, $3, $4)
+         RETURNING id, app_id, process_type, instance, command, status, pid",
+    )
+    .bind(app_id)
+    .bind(process_type)
+    .bind(instance)
+    .bind(command)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(process)
+}
+
+pub async fn get_processes(pool: &PgPool, app_id: Uuid) -> Result<Vec<AppProcess>, Error> {
+    let processes = sqlx::query_as(
+        "SELECT id, app_id, process_type, instance, command, status, pid FROM processes
+         WHERE app_id = $1 ORDER BY process_type ASC, instance ASC",
+    )
+    .bind(app_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(processes)
+}
+
+pub async fn get_processes_by_type(
+    pool: &PgPool,
+    app_id: Uuid,
+    process_type: &str,
+) -> Result<Vec<AppProcess>, Error> {
+    let processes = sqlx::query_as(
+        "SELECT id, app_id, process_type, instance, command, status, pid FROM processes
+         WHERE app_id = $1 AND process_type = $2 ORDER BY instance ASC",
+    )
+    .bind(app_id)
+    .bind(process_type)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(processes)
+}
+
+/// A process instance of the app. `RowNotFound` if it belongs to another app.
+pub async fn get_process(pool: &PgPool, app_id: Uuid, process_id: Uuid) -> Result<AppProcess, Error> {
+    let process = sqlx::query_as(
+        "SELECT id, app_id, process_type, instance, command, status, pid FROM processes WHERE app_id = $1 AND id = $2",
+    )
+    .bind(app_id)
+    .bind(process_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(process)
+}
+
+pub async fn patch_process(
+    pool: &PgPool,
+    app_id: Uuid,
+    process_id: Uuid,
+    patch: &PatchProcess,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE processes SET status = COALESCE($1, status), pid = COALESCE($2, pid), updated_at = now()
+         WHERE app_id = $3 AND id = $4",
+    )
+    .bind(&patch.status)
+    .bind(patch.pid)
+    .bind(app_id)
+    .bind(process_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Reset every instance of an app before a (re)start
+pub async fn reset_processes(pool: &PgPool, app_id: Uuid, status: AppStatus) -> Result<(), Error> {
+    sqlx::query("UPDATE processes SET status = $1, pid = NULL, updated_at = now() WHERE app_id = $2")
+        .bind(status)
+        .bind(app_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_process_type_status(
+    pool: &PgPool,
+    app_id: Uuid,
+    process_type: &str,
+    status: AppStatus,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE processes SET status = $1, pid = NULL, updated_at = now() WHERE app_id = $2 AND process_type = $3",
+    )
+    .bind(status)
+    .bind(app_id)
+    .bind(process_type)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn update_process_command(
+    pool: &PgPool,
+    app_id: Uuid,
+    process_type: &str,
+    command: &str,
+) -> Result<(), Error> {
+    sqlx::query("UPDATE processes SET command = $1, updated_at = now() WHERE app_id = $2 AND process_type = $3")
+        .bind(command)
+        .bind(app_id)
+        .bind(process_type)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete_process(pool: &PgPool, process_id: Uuid) -> Result<(), Error> {
+    sqlx::query("DELETE FROM processes WHERE id = $1")
+        .bind(process_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn mark_stale_processes_stopped(pool: &PgPool) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE processes SET status = 'STOPPED'::app_status, pid = NULL WHERE status != 'STOPPED'::app_status",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
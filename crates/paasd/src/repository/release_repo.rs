@@ -0,0 +1,91 @@
+use shared::{GitMetadata, Release};
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+const RELEASE_COLUMNS: &str = "id, app_id, version, source_sha256, source_size, git_commit, git_branch, git_dirty, \
+     source_release_id, description, env_vars, created_at";
+
+/// Store a source tarball as the app's next release version
+pub async fn insert_release(
+    pool: &PgPool,
+    app_id: Uuid,
+    source: &[u8],
+    sha256: &str,
+    git: &GitMetadata,
+) -> Result<Release, Error> {
+    let query = format!(
+        "INSERT INTO releases (app_id, version, source, source_sha256, source_size, git_commit, git_branch, git_dirty)
+         VALUES ($1, (SELECT COALESCE(MAX(version), 0) + 1 FROM releases WHERE app_id = $1), $2, $3, $4, $5, $6, $7)
+         RETURNING {}",
+        RELEASE_COLUMNS
+    );
+    sqlx::query_as(&query)
+        .bind(app_id)
+        .bind(source)
+        .bind(sha256)
+        .bind(source.len() as i64)
+        .bind(&git.commit)
+        .bind(&git.branch)
+        .bind(git.dirty)
+        .fetch_one(pool)
+        .await
+}
+
+/// Store the app's next release running the same source as release `from_release_id` of
+/// `from_app_id`, which is the app itself for config changes and another app for promotions.
+/// `env_vars` are the env vars of `app_id` the release runs with.
+pub async fn insert_derived_release(
+    pool: &PgPool,
+    app_id: Uuid,
+    from_app_id: Uuid,
+    from_release_id: Uuid,
+    description: &str,
+    env_vars: &serde_json::Value,
+) -> Result<Release, Error> {
+    let query = format!(
+        "INSERT INTO releases (app_id, version, source_release_id, source_sha256, source_size, git_commit, git_branch, git_dirty, description, env_vars)
+         SELECT $1, (SELECT COALESCE(MAX(version), 0) + 1 FROM releases WHERE app_id = $1),
+                COALESCE(source_release_id, id), source_sha256, source_size, git_commit, git_branch, git_dirty, $4, $5
+         FROM releases WHERE app_id = $2 AND id = $3
+         RETURNING {}",
+        RELEASE_COLUMNS
+    );
+    sqlx::query_as(&query)
+        .bind(app_id)
+        .bind(from_app_id)
+        .bind(from_release_id)
+        .bind(description)
+        .bind(env_vars)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn get_releases(pool: &PgPool, app_id: Uuid, limit: i64) -> Result<Vec<Release>, Error> {
+    let query = format!(
+        "SELECT {} FROM releases WHERE app_id = $1 ORDER BY version DESC LIMIT $2",
+        RELEASE_COLUMNS
+    );
+    sqlx::query_as(&query).bind(app_id).bind(limit).fetch_all(pool).await
+}
+
+pub async fn get_release(pool: &PgPool, app_id: Uuid, release_id: Uuid) -> Result<Release, Error> {
+    let query = format!(
+        "SELECT {} FROM releases WHERE app_id = $1 AND id = $2",
+        RELEASE_COLUMNS
+    );
+    sqlx::query_as(&query).bind(app_id).bind(release_id).fetch_one(pool).await
+}
+
+/// The release's source tarball (gzipped tar), following config releases to the upload they reuse
+pub async fn get_release_source(pool: &PgPool, app_id: Uuid, release_id: Uuid) -> Result<Vec<u8>, Error> {
+    let row: (Option<Vec<u8>>,) = sqlx::query_as(
+        "SELECT COALESCE(r.source, s.source) FROM releases r
+         LEFT JOIN releases s ON s.id = r.source_release_id
+         WHERE r.app_id = $1 AND r.id = $2",
+    )
+    .bind(app_id)
+    .bind(release_id)
+    .fetch_one(pool)
+    .await?;
+    row.0.ok_or(Error::RowNotFound)
+}
@@ -0,0 +1,107 @@
+use shared::{AppRun, FinishRun, RunStatus};
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+pub async fn insert_run(
+    pool: &PgPool,
+    app_id: Uuid,
+    command: &str,
+    schedule_id: Option<Uuid>,
+) -> Result<AppRun, Error> {
+    let run = sqlx::query_as(
+        "INSERT INTO runs (app_id, command, schedule_id) VALUES ($1, $2, $3)
+         RETURNING id, app_id, command, status, exit_code, duration_ms, created_at, finished_at, schedule_id",
+    )
+    .bind(app_id)
+    .bind(command)
+    .bind(schedule_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(run)
+}
+
+pub async fn get_runs(pool: &PgPool, app_id: Uuid, limit: i64) -> Result<Vec<AppRun>, Error> {
+    let runs = sqlx::query_as(
+        "SELECT id, app_id, command, status, exit_code, duration_ms, created_at, finished_at, schedule_id FROM runs
+         WHERE app_id = $1 ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(app_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs)
+}
+
+pub async fn get_schedule_runs(pool: &PgPool, schedule_id: Uuid, limit: i64) -> Result<Vec<AppRun>, Error> {
+    let runs = sqlx::query_as(
+        "SELECT id, app_id, command, status, exit_code, duration_ms, created_at, finished_at, schedule_id FROM runs
+         WHERE schedule_id = $1 ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(schedule_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(runs)
+}
+
+pub async fn get_run(pool: &PgPool, app_id: Uuid, run_id: Uuid) -> Result<AppRun, Error> {
+    let run = sqlx::query_as(
+        "SELECT id, app_id, command, status, exit_code, duration_ms, created_at, finished_at, schedule_id FROM runs
+         WHERE app_id = $1 AND id = $2",
+    )
+    .bind(app_id)
+    .bind(run_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(run)
+}
+
+/// Record how a run of the app ended. `RowNotFound` if the run belongs to another app.
+pub async fn finish_run(pool: &PgPool, app_id: Uuid, run_id: Uuid, result: &FinishRun) -> Result<(), Error> {
+    let status = if result.exit_code == 0 {
+        RunStatus::SUCCEEDED
+    } else {
+        RunStatus::FAILED
+    };
+    let updated = sqlx::query(
+        "UPDATE runs SET status = $1, exit_code = $2, duration_ms = $3, finished_at = now()
+         WHERE app_id = $4 AND id = $5",
+    )
+    .bind(status)
+    .bind(result.exit_code)
+    .bind(result.duration_ms)
+    .bind(app_id)
+    .bind(run_id)
+    .execute(pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Error::RowNotFound);
+    }
+    Ok(())
+}
+
+/// Give up on a run that never reported back, unless it has finished in the meantime
+pub async fn mark_run_failed(pool: &PgPool, run_id: Uuid) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE runs SET status = 'FAILED'::run_status, finished_at = now()
+         WHERE id = $1 AND status = 'RUNNING'::run_status",
+    )
+    .bind(run_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Runs still marked RUNNING when paasd starts lost their agent and will never report back
+pub async fn mark_stale_runs_failed(pool: &PgPool) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE runs SET status = 'FAILED'::run_status, finished_at = now() WHERE status = 'RUNNING'::run_status",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
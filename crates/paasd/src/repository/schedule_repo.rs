@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use shared::{NewSchedule, Schedule};
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+const SCHEDULE_COLUMNS: &str = "s.id, s.app_id, s.name, s.schedule, s.command, s.enabled, s.next_run_at, s.last_run_id,
+    r.created_at AS last_run_at, r.status AS last_status, r.exit_code AS last_exit_code";
+
+pub async fn get_schedules(pool: &PgPool, app_id: Uuid) -> Result<Vec<Schedule>, Error> {
+    let query = format!(
+        "SELECT {} FROM schedules s LEFT JOIN runs r ON r.id = s.last_run_id
+         WHERE s.app_id = $1 ORDER BY s.name ASC",
+        SCHEDULE_COLUMNS
+    );
+    sqlx::query_as(&query).bind(app_id).fetch_all(pool).await
+}
+
+pub async fn get_schedule(pool: &PgPool, app_id: Uuid, schedule_id: Uuid) -> Result<Schedule, Error> {
+    let query = format!(
+        "SELECT {} FROM schedules s LEFT JOIN runs r ON r.id = s.last_run_id
+         WHERE s.app_id = $1 AND s.id = $2",
+        SCHEDULE_COLUMNS
+    );
+    sqlx::query_as(&query)
+        .bind(app_id)
+        .bind(schedule_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Enabled schedules whose next run time has passed. Schedules of destroyed apps wait until
+/// the app is restored.
+pub async fn get_due_schedules(pool: &PgPool, now: DateTime<Utc>) -> Result<Vec<Schedule>, Error> {
+    let query = format!(
+        "SELECT {} FROM schedules s JOIN apps a ON a.id = s.app_id LEFT JOIN runs r ON r.id = s.last_run_id
+         WHERE s.enabled AND s.next_run_at <= $1 AND a.deleted_at IS NULL ORDER BY s.next_run_at ASC",
+        SCHEDULE_COLUMNS
+    );
+    sqlx::query_as(&query).bind(now).fetch_all(pool).await
+}
+
+/// Replace an app's schedules with the given set, keeping IDs (and history) of schedules whose name is unchanged
+pub async fn replace_schedules(
+    pool: &PgPool,
+    app_id: Uuid,
+    schedules: &[(NewSchedule, DateTime<Utc>)],
+) -> Result<(), Error> {
+    let mut tx = pool.begin().await?;
+
+    let names: Vec<String> = schedules.iter().map(|(s, _)| s.name.clone()).collect();
+    sqlx::query("DELETE FROM schedules WHERE app_id = $1 AND NOT (name = ANY($2))")
+        .bind(app_id)
+        .bind(&names)
+        .execute(&mut tx)
+        .await?;
+
+    for (schedule, next_run_at) in schedules {
+        sqlx::query(
+            "INSERT INTO schedules (app_id, name, schedule, command, next_run_at) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (app_id, name) DO UPDATE SET
+                schedule = EXCLUDED.schedule,
+                command = EXCLUDED.command,
+                next_run_at = EXCLUDED.next_run_at,
+                updated_at = now()",
+        )
+        .bind(app_id)
+        .bind(&schedule.name)
+        .bind(&schedule.schedule)
+        .bind(&schedule.command)
+        .bind(next_run_at)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Store the outcome of a trigger. A `None` next run time (schedule never fires again) parks the schedule.
+pub async fn record_schedule_run(
+    pool: &PgPool,
+    schedule_id: Uuid,
+    run_id: Option<Uuid>,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<(), Error> {
+    sqlx::query(
+        "UPDATE schedules SET last_run_id = COALESCE($1, last_run_id), next_run_at = $2, updated_at = now()
+         WHERE id = $3",
+    )
+    .bind(run_id)
+    .bind(next_run_at)
+    .bind(schedule_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use shared::SecretInfo;
+use sqlx::{Error, PgPool};
+use uuid::Uuid;
+
+pub async fn get_secret_names(pool: &PgPool, app_id: Uuid) -> Result<Vec<SecretInfo>, Error> {
+    sqlx::query_as("SELECT name, updated_at FROM secrets WHERE app_id = $1 ORDER BY name ASC")
+        .bind(app_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// (name, ciphertext, nonce) of every secret of the app
+pub async fn get_encrypted_secrets(pool: &PgPool, app_id: Uuid) -> Result<Vec<(String, Vec<u8>, Vec<u8>)>, Error> {
+    sqlx::query_as("SELECT name, ciphertext, nonce FROM secrets WHERE app_id = $1")
+        .bind(app_id)
+        .fetch_all(pool)
+        .await
+}
+
+pub async fn upsert_secret(
+    pool: &PgPool,
+    app_id: Uuid,
+    name: &str,
+    ciphertext: &[u8],
+    nonce: &[u8],
+) -> Result<SecretInfo, Error> {
+    sqlx::query_as(
+        "INSERT INTO secrets (app_id, name, ciphertext, nonce) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (app_id, name) DO UPDATE SET
+            ciphertext = EXCLUDED.ciphertext,
+            nonce = EXCLUDED.nonce,
+            updated_at = now()
+         RETURNING name, updated_at",
+    )
+    .bind(app_id)
+    .bind(name)
+    .bind(ciphertext)
+    .bind(nonce)
+    .fetch_one(pool)
+    .await
+}
+
+/// Returns whether a secret was deleted
+pub async fn delete_secret(pool: &PgPool, app_id: Uuid, name: &str) -> Result<bool, Error> {
+    let result = sqlx::query("DELETE FROM secrets WHERE app_id = $1 AND name = $2")
+        .bind(app_id)
+        .bind(name)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
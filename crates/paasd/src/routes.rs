@@ -0,0 +1,110 @@
+//! paasd's API routes, kept in one table so `openapi` can check each is documented
+
+use actix_web::dev::Handler;
+use actix_web::http::Method;
+use actix_web::{FromRequest, Responder, Route, guard, web};
+
+use crate::handlers::app_handlers::{
+    delete_program, get_live_status, get_program, get_programs, patch_program, post_program, redeploy_program,
+    restore_program,
+};
+use crate::handlers::discovery_handlers::get_discovery;
+use crate::handlers::env_handlers::{delete_app_env, get_app_env, patch_app_env, put_app_env};
+use crate::handlers::git_handlers::{git_deploy, post_app_git};
+use crate::handlers::log_handlers::{get_app_logs, post_log};
+use crate::handlers::process_handlers::{
+    get_app_process, get_app_processes, patch_app_process, restart_process_type, scale_process,
+};
+use crate::handlers::release_handlers::{
+    MAX_SOURCE_BYTES, get_app_release_source, get_app_releases, post_release, promote_release,
+};
+use crate::handlers::run_handlers::{get_app_run, get_app_run_logs, get_app_runs, patch_run, post_run};
+use crate::handlers::schedule_handlers::{
+    get_app_schedules, get_schedule_history, put_app_schedules, run_schedule_now,
+};
+use crate::handlers::secret_handlers::{delete_app_secret, get_app_secrets, put_app_secret};
+
+/// A route of the API and the method and path it's served on
+pub struct ApiRoute {
+    pub method: Method,
+    pub path: &'static str,
+    route: Route,
+    /// Largest raw body the route accepts, if not actix's default
+    payload_limit: Option<usize>,
+}
+
+impl ApiRoute {
+    fn with_payload_limit(mut self, limit: usize) -> Self {
+        self.payload_limit = Some(limit);
+        self
+    }
+}
+
+fn route<F, Args>(method: Method, path: &'static str, handler: F) -> ApiRoute
+where
+    F: Handler<Args>,
+    Args: FromRequest + 'static,
+    F::Output: Responder + 'static,
+{
+    ApiRoute {
+        method,
+        path,
+        route: web::route().to(handler),
+        payload_limit: None,
+    }
+}
+
+/// Every route paasd serves. Each one needs a `#[utoipa::path]` listed in `openapi::ApiDoc`.
+pub fn routes() -> Vec<ApiRoute> {
+    vec![
+        route(Method::POST, "/apps", post_program),
+        route(Method::GET, "/apps", get_programs),
+        route(Method::GET, "/apps/{app_id}", get_program),
+        route(Method::DELETE, "/apps/{app_id}", delete_program),
+        route(Method::GET, "/apps/{app_id}/status", get_live_status),
+        route(Method::PATCH, "/apps/{app_id}", patch_program),
+        route(Method::POST, "/apps/{app_id}/redeploy", redeploy_program),
+        route(Method::POST, "/apps/{app_id}/restore", restore_program),
+        route(Method::POST, "/apps/{app_id}/logs", post_log),
+        route(Method::GET, "/apps/{app_id}/logs", get_app_logs),
+        route(Method::GET, "/apps/{app_id}/processes", get_app_processes),
+        route(Method::GET, "/apps/{app_id}/processes/{process_id}", get_app_process),
+        route(Method::PATCH, "/apps/{app_id}/processes/{process_id}", patch_app_process),
+        route(Method::POST, "/apps/{app_id}/processes/{process_type}/scale", scale_process),
+        route(Method::POST, "/apps/{app_id}/processes/{process_type}/restart", restart_process_type),
+        route(Method::POST, "/apps/{app_id}/git", post_app_git),
+        route(Method::POST, "/apps/{app_id}/git/deploy", git_deploy),
+        route(Method::POST, "/apps/{app_id}/releases", post_release).with_payload_limit(MAX_SOURCE_BYTES),
+        route(Method::GET, "/apps/{app_id}/releases", get_app_releases),
+        route(Method::POST, "/apps/{app_id}/releases/promote", promote_release),
+        route(Method::GET, "/apps/{app_id}/releases/{release_id}/source", get_app_release_source),
+        route(Method::POST, "/apps/{app_id}/runs", post_run),
+        route(Method::GET, "/apps/{app_id}/runs", get_app_runs),
+        route(Method::GET, "/apps/{app_id}/runs/{run_id}", get_app_run),
+        route(Method::PATCH, "/apps/{app_id}/runs/{run_id}", patch_run),
+        route(Method::GET, "/apps/{app_id}/runs/{run_id}/logs", get_app_run_logs),
+        route(Method::GET, "/apps/{app_id}/env", get_app_env),
+        route(Method::PUT, "/apps/{app_id}/env", put_app_env),
+        route(Method::PATCH, "/apps/{app_id}/env", patch_app_env),
+        route(Method::DELETE, "/apps/{app_id}/env", delete_app_env),
+        route(Method::GET, "/apps/{app_id}/secrets", get_app_secrets),
+        route(Method::PUT, "/apps/{app_id}/secrets/{name}", put_app_secret),
+        route(Method::DELETE, "/apps/{app_id}/secrets/{name}", delete_app_secret),
+        route(Method::GET, "/apps/{app_id}/schedules", get_app_schedules),
+        route(Method::PUT, "/apps/{app_id}/schedules", put_app_schedules),
+        route(Method::POST, "/apps/{app_id}/schedules/{schedule_id}/run", run_schedule_now),
+        route(Method::GET, "/apps/{app_id}/schedules/{schedule_id}/runs", get_schedule_history),
+        route(Method::GET, "/discovery/{app_name}", get_discovery),
+    ]
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    for api_route in routes() {
+        // Routes sharing a path are separate resources, so the method guard goes on the resource
+        let mut resource = web::resource(api_route.path).guard(guard::Method(api_route.method));
+        if let Some(limit) = api_route.payload_limit {
+            resource = resource.app_data(web::PayloadConfig::new(limit));
+        }
+        cfg.service(resource.route(api_route.route));
+    }
+}
@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use shared::{AppStatus, RunStatus, Schedule};
+use sqlx::PgPool;
+
+use crate::handlers::run_handlers::launch_run;
+use crate::repository::app_repo::get_application;
+use crate::repository::run_repo::mark_run_failed;
+use crate::repository::schedule_repo::{get_due_schedules, record_schedule_run};
+
+/// How often the scheduler checks for due schedules
+const TICK_SECS: u64 = 15;
+
+/// A run still RUNNING after this long is assumed lost (e.g. the agent restarted) and no longer blocks its schedule
+const MAX_RUN_SECS: i64 = 24 * 60 * 60;
+
+/// Parse a cron expression. Standard 5-field crontab expressions are accepted
+/// and run at second 0; 6 and 7 field expressions (with seconds/years) are used as-is.
+pub fn parse_schedule(expr: &str) -> Result<cron::Schedule, String> {
+    let fields = expr.split_whitespace().count();
+    let normalized = if fields == 5 {
+        format!("0 {}", expr.trim())
+    } else {
+        expr.trim().to_string()
+    };
+    cron::Schedule::from_str(&normalized).map_err(|e| format!("Invalid schedule '{}': {}", expr, e))
+}
+
+/// The first time the schedule fires strictly after `after`
+pub fn next_run_after(expr: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    parse_schedule(expr).ok()?.after(&after).next()
+}
+
+/// Run due schedules forever. Each schedule starts a one-off run on the agent,
+/// unless its previous run is still going, in which case this occurrence is skipped.
+pub async fn run_scheduler(pool: PgPool) {
+    loop {
+        let now = Utc::now();
+        match get_due_schedules(&pool, now).await {
+            Ok(schedules) => {
+                for schedule in schedules {
+                    trigger_schedule(&pool, &schedule, now).await;
+                }
+            }
+            Err(e) => eprintln!("Scheduler: failed to load due schedules: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(TICK_SECS)).await;
+    }
+}
+
+async fn trigger_schedule(pool: &PgPool, schedule: &Schedule, now: DateTime<Utc>) {
+    let next = next_run_after(&schedule.schedule, now);
+
+    if matches!(schedule.last_status, Some(RunStatus::RUNNING))
+        && let Some(run_id) = schedule.last_run_id
+        && schedule
+            .last_run_at
+            .is_some_and(|started| now - started > chrono::Duration::seconds(MAX_RUN_SECS))
+    {
+        println!(
+            "Scheduler: run {} of '{}' has been running for over {}s, marking it failed",
+            run_id, schedule.name, MAX_RUN_SECS
+        );
+        if let Err(e) = mark_run_failed(pool, run_id).await {
+            eprintln!("Scheduler: failed to update run {}: {}", run_id, e);
+            if let Err(e) = record_schedule_run(pool, schedule.id, None, next).await {
+                eprintln!("Scheduler: failed to update schedule {}: {}", schedule.id, e);
+            }
+            return;
+        }
+    } else if matches!(schedule.last_status, Some(RunStatus::RUNNING)) {
+        println!(
+            "Scheduler: skipping '{}' for app {}, previous run is still running",
+            schedule.name, schedule.app_id
+        );
+        if let Err(e) = record_schedule_run(pool, schedule.id, None, next).await {
+            eprintln!("Scheduler: failed to update schedule {}: {}", schedule.id, e);
+        }
+        return;
+    }
+
+    let app = match get_application(pool, schedule.app_id).await {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Scheduler: failed to load app {}: {}", schedule.app_id, e);
+            // Skip this occurrence rather than failing again on every tick
+            if let Err(e) = record_schedule_run(pool, schedule.id, None, next).await {
+                eprintln!("Scheduler: failed to update schedule {}: {}", schedule.id, e);
+            }
+            return;
+        }
+    };
+
+    // Stopping an app with `paas stop` pauses its jobs as well
+    if matches!(app.status, AppStatus::STOPPED) {
+        if let Err(e) = record_schedule_run(pool, schedule.id, None, next).await {
+            eprintln!("Scheduler: failed to update schedule {}: {}", schedule.id, e);
+        }
+        return;
+    }
+
+    println!("Scheduler: running '{}' for app {}", schedule.name, app.name);
+    let run_id = match launch_run(pool, app, &schedule.command, Some(schedule.id)).await {
+        Ok(run) => Some(run.id),
+        Err(e) => {
+            eprintln!("Scheduler: failed to start '{}': {}", schedule.name, e);
+            None
+        }
+    };
+
+    if let Err(e) = record_schedule_run(pool, schedule.id, run_id, next).await {
+        eprintln!("Scheduler: failed to update schedule {}: {}", schedule.id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::{next_run_after, parse_schedule};
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    #[test]
+    fn five_field_expressions_run_at_second_zero() {
+        let next = next_run_after("*/15 * * * *", at(2026, 3, 1, 10, 7, 30));
+        assert_eq!(next, Some(at(2026, 3, 1, 10, 15, 0)));
+    }
+
+    #[test]
+    fn six_and_seven_field_expressions_are_used_as_is() {
+        assert_eq!(next_run_after("30 * * * * *", at(2026, 3, 1, 10, 0, 0)), Some(at(2026, 3, 1, 10, 0, 30)));
+        assert_eq!(next_run_after("0 0 0 1 1 * 2030", at(2026, 3, 1, 0, 0, 0)), Some(at(2030, 1, 1, 0, 0, 0)));
+    }
+
+    #[test]
+    fn next_run_is_strictly_after() {
+        let on_the_hour = at(2026, 3, 1, 10, 0, 0);
+        assert_eq!(next_run_after("0 * * * *", on_the_hour), Some(at(2026, 3, 1, 11, 0, 0)));
+    }
+
+    #[test]
+    fn edge_values_roll_over() {
+        // Last minute of the year
+        assert_eq!(next_run_after("59 23 31 12 *", at(2026, 6, 1, 0, 0, 0)), Some(at(2026, 12, 31, 23, 59, 0)));
+        assert_eq!(next_run_after("0 0 1 1 *", at(2026, 12, 31, 23, 59, 0)), Some(at(2027, 1, 1, 0, 0, 0)));
+        // Leap day only exists every four years
+        assert_eq!(next_run_after("0 12 29 2 *", at(2026, 3, 1, 0, 0, 0)), Some(at(2028, 2, 29, 12, 0, 0)));
+        // Months without a 31st are skipped
+        assert_eq!(next_run_after("0 0 31 * *", at(2026, 4, 1, 0, 0, 0)), Some(at(2026, 5, 31, 0, 0, 0)));
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_ignored() {
+        assert!(parse_schedule("  0 9 * * MON-FRI  ").is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expr in ["", "* * * *", "60 * * * *", "* 24 * * *", "* * 32 * *", "* * * 13 *", "every minute"] {
+            let error = parse_schedule(expr).unwrap_err();
+            assert!(error.starts_with("Invalid schedule"), "{}: {}", expr, error);
+        }
+        assert_eq!(next_run_after("nonsense", Utc::now()), None);
+    }
+
+    #[test]
+    fn schedules_that_never_fire_again_have_no_next_run() {
+        assert_eq!(next_run_after("0 0 0 1 1 * 2020", at(2026, 1, 1, 0, 0, 0)), None);
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::repository::secret_repo::get_encrypted_secrets;
+
+static CIPHER: OnceLock<Aes256Gcm> = OnceLock::new();
+
+fn key_file() -> PathBuf {
+    match std::env::var("PAAS_SECRETS_KEY_FILE") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => std::env::var("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir())
+            .join(".paas")
+            .join("secrets.key"),
+    }
+}
+
+/// Load the encryption key: `PAAS_SECRETS_KEY` (64 hex chars), or the key file at
+/// `PAAS_SECRETS_KEY_FILE` (default `~/.paas/secrets.key`), which is generated on first start.
+pub fn init_key() -> Result<(), String> {
+    let hex_key = match std::env::var("PAAS_SECRETS_KEY") {
+        Ok(key) => key,
+        Err(_) => {
+            let path = key_file();
+            if !path.exists() {
+                generate_key_file(&path)?;
+                println!("Generated secrets key at {}. Back it up: secrets can't be read without it.", path.display());
+            }
+            std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?
+        }
+    };
+
+    let bytes = hex::decode(hex_key.trim()).map_err(|_| "Secrets key must be hex encoded".to_string())?;
+    if bytes.len() != 32 {
+        return Err("Secrets key must be 32 bytes (64 hex characters)".to_string());
+    }
+    let _ = CIPHER.set(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)));
+    Ok(())
+}
+
+fn generate_key_file(path: &std::path::Path) -> Result<(), String> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let key = Aes256Gcm::generate_key(OsRng);
+    // Created owner-only from the start, and never over a key that appeared in the meantime
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    file.write_all(hex::encode(key).as_bytes())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn cipher() -> Result<&'static Aes256Gcm, String> {
+    CIPHER.get().ok_or_else(|| "Secrets key is not configured".to_string())
+}
+
+/// Encrypt a value, returning (ciphertext, nonce)
+pub fn encrypt(value: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher()?
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| "Failed to encrypt secret".to_string())?;
+    Ok((ciphertext, nonce.to_vec()))
+}
+
+fn decrypt(ciphertext: &[u8], nonce: &[u8]) -> Result<String, String> {
+    if nonce.len() != 12 {
+        return Err("Corrupt secret nonce".to_string());
+    }
+    let plaintext = cipher()?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt secret (wrong key?)".to_string())?;
+    String::from_utf8(plaintext).map_err(|_| "Secret is not valid UTF-8".to_string())
+}
+
+/// Decrypt all of an app's secrets, to hand to the agent when it starts a process
+pub async fn app_secrets(pool: &PgPool, app_id: Uuid) -> Result<BTreeMap<String, String>, String> {
+    let encrypted = get_encrypted_secrets(pool, app_id)
+        .await
+        .map_err(|e| format!("DB Error fetching secrets: {}", e))?;
+    encrypted
+        .into_iter()
+        .map(|(name, ciphertext, nonce)| {
+            let value = decrypt(&ciphertext, &nonce).map_err(|e| format!("Secret {}: {}", name, e))?;
+            Ok((name, value))
+        })
+        .collect()
+}
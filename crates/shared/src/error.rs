@@ -0,0 +1,237 @@
+//! The JSON body of every error response from paasd and the agent:
+//! `{"code": "port_in_use", "message": "...", "details": {...}, "request_id": "..."}`
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What went wrong, for clients to act on without parsing the message
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidRequest,
+    NotFound,
+    Conflict,
+    NameTaken,
+    /// `details.port`, and `details.free_ports` to pick from instead
+    PortInUse,
+    /// `details.range` of the exhausted allocation range
+    NoFreePort,
+    PayloadTooLarge,
+    /// paasd couldn't reach the agent, or the agent failed
+    AgentUnavailable,
+    NotImplemented,
+    Internal,
+    /// A code added by a newer server
+    #[serde(other)]
+    Unknown,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorCode::InvalidRequest => 400,
+            ErrorCode::NotFound => 404,
+            ErrorCode::Conflict | ErrorCode::NameTaken | ErrorCode::PortInUse | ErrorCode::NoFreePort => 409,
+            ErrorCode::PayloadTooLarge => 413,
+            ErrorCode::NotImplemented => 501,
+            ErrorCode::AgentUnavailable => 502,
+            ErrorCode::Internal | ErrorCode::Unknown => 500,
+        }
+    }
+
+    /// The code of an error response without an `ApiError` body
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => ErrorCode::InvalidRequest,
+            404 => ErrorCode::NotFound,
+            409 => ErrorCode::Conflict,
+            413 => ErrorCode::PayloadTooLarge,
+            501 => ErrorCode::NotImplemented,
+            502 | 503 => ErrorCode::AgentUnavailable,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub code: ErrorCode,
+    /// Human readable, safe to show to users as is
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+    /// Also sent as `X-Request-Id`; quote it when looking for the request in server logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+            request_id: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn agent_unavailable(message: impl Into<String>) -> Self {
+        ApiError::new(ErrorCode::AgentUnavailable, message)
+    }
+
+    /// The cause is only logged by the server, not sent to clients
+    pub fn internal() -> Self {
+        ApiError::new(ErrorCode::Internal, "Internal server error")
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(feature = "actix")]
+pub use server::*;
+
+/// Turning `ApiError`s into responses, and tagging every request with an id
+#[cfg(feature = "actix")]
+mod server {
+    use std::future::{Future, Ready, ready};
+    use std::pin::Pin;
+    use std::rc::Rc;
+
+    use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+    use actix_web::http::StatusCode;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+    use actix_web::{Error, HttpRequest, HttpResponse, ResponseError, web};
+
+    use super::{ApiError, ErrorCode};
+
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    tokio::task_local! {
+        static REQUEST_ID: String;
+    }
+
+    impl ApiError {
+        /// The error as a response, tagged with the id of the request being handled
+        pub fn response(self) -> HttpResponse {
+            self.error_response()
+        }
+    }
+
+    impl ResponseError for ApiError {
+        fn status_code(&self) -> StatusCode {
+            StatusCode::from_u16(self.code.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            let mut error = self.clone();
+            if error.request_id.is_none() {
+                error.request_id = REQUEST_ID.try_with(Clone::clone).ok();
+            }
+            HttpResponse::build(self.status_code()).json(error)
+        }
+    }
+
+    /// Bad JSON bodies, query strings and paths get `ApiError` bodies like everything else
+    pub fn json_config() -> web::JsonConfig {
+        web::JsonConfig::default()
+            .error_handler(|e, _| ApiError::invalid_request(format!("Invalid request body: {}", e)).into())
+    }
+
+    pub fn query_config() -> web::QueryConfig {
+        web::QueryConfig::default()
+            .error_handler(|e, _| ApiError::invalid_request(format!("Invalid query string: {}", e)).into())
+    }
+
+    pub fn path_config() -> web::PathConfig {
+        web::PathConfig::default().error_handler(|e, _| ApiError::not_found(format!("Not found: {}", e)).into())
+    }
+
+    /// Default service for paths no route matches
+    pub async fn no_route(req: HttpRequest) -> HttpResponse {
+        ApiError::new(ErrorCode::NotFound, format!("No route for {} {}", req.method(), req.path())).response()
+    }
+
+    /// Give every request an id, from its `X-Request-Id` header or a new one, that
+    /// error bodies carry and that is echoed in the response's `X-Request-Id`
+    pub struct RequestId;
+
+    impl<S, B> Transform<S, ServiceRequest> for RequestId
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+        B: 'static,
+    {
+        type Response = ServiceResponse<B>;
+        type Error = Error;
+        type Transform = RequestIdMiddleware<S>;
+        type InitError = ();
+        type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+        fn new_transform(&self, service: S) -> Self::Future {
+            ready(Ok(RequestIdMiddleware {
+                service: Rc::new(service),
+            }))
+        }
+    }
+
+    pub struct RequestIdMiddleware<S> {
+        service: Rc<S>,
+    }
+
+    impl<S, B> Service<ServiceRequest> for RequestIdMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+        B: 'static,
+    {
+        type Response = ServiceResponse<B>;
+        type Error = Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+        actix_web::dev::forward_ready!(service);
+
+        fn call(&self, req: ServiceRequest) -> Self::Future {
+            let service = Rc::clone(&self.service);
+            let request_id = req
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|id| id.to_str().ok())
+                .filter(|id| !id.is_empty() && id.len() <= 64)
+                .map(str::to_string)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+            Box::pin(REQUEST_ID.scope(request_id.clone(), async move {
+                let mut res = service.call(req).await?;
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+                }
+                Ok(res)
+            }))
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! `${VAR}` and `${app:<name>.<attribute>}` references in env var values.
+//! Write `$${` for a literal `${`.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::is_valid_env_name;
+
+/// Vars the platform sets on every process. They can be referenced and can't be overridden.
+pub const PLATFORM_VARS: &[&str] = &["PORT", "PAAS_APP_NAME", "PAAS_APP_ID", "PAAS_RELEASE"];
+
+/// Attributes of another app that `${app:<name>.<attribute>}` can reference
+pub const APP_ATTRIBUTES: &[&str] = &["url", "host", "port", "name", "id"];
+
+enum Segment<'a> {
+    Literal(&'a str),
+    Var(&'a str),
+    App { name: &'a str, attribute: &'a str },
+}
+
+fn parse(value: &str) -> Result<Vec<Segment<'_>>, String> {
+    let mut segments = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        let (before, after) = rest.split_at(start);
+        if let Some(escaped) = after.strip_prefix("$${") {
+            segments.push(Segment::Literal(before));
+            segments.push(Segment::Literal("${"));
+            rest = escaped;
+            continue;
+        }
+        let Some(body) = after.strip_prefix("${") else {
+            segments.push(Segment::Literal(&rest[..=start]));
+            rest = &after[1..];
+            continue;
+        };
+        let end = body.find('}').ok_or("unterminated `${`")?;
+        segments.push(Segment::Literal(before));
+        segments.push(parse_reference(&body[..end])?);
+        rest = &body[end + 1..];
+    }
+    segments.push(Segment::Literal(rest));
+    Ok(segments)
+}
+
+fn parse_reference(reference: &str) -> Result<Segment<'_>, String> {
+    if let Some(app) = reference.strip_prefix("app:") {
+        return match app.rsplit_once('.') {
+            Some((name, attribute)) if !name.is_empty() && !attribute.is_empty() => {
+                Ok(Segment::App { name, attribute })
+            }
+            _ => Err(format!("`${{{}}}` should be `${{app:<name>.<attribute>}}`", reference)),
+        };
+    }
+    if !is_valid_env_name(reference) {
+        return Err(format!("invalid reference `${{{}}}`", reference));
+    }
+    Ok(Segment::Var(reference))
+}
+
+/// Whether any value references another app, so its caller knows to look apps up
+pub fn references_apps(vars: &BTreeMap<String, String>) -> bool {
+    !referenced_apps(vars).is_empty()
+}
+
+/// Names of the apps referenced with `${app:<name>.<attribute>}`
+pub fn referenced_apps(vars: &BTreeMap<String, String>) -> BTreeSet<&str> {
+    vars.values()
+        .filter_map(|value| parse(value).ok())
+        .flatten()
+        .filter_map(|segment| match segment {
+            Segment::App { name, .. } => Some(name),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Resolver<'a> {
+    vars: &'a BTreeMap<String, String>,
+    secrets: &'a BTreeMap<String, String>,
+    platform: &'a BTreeMap<String, String>,
+    app_attribute: &'a dyn Fn(&str, &str) -> Option<String>,
+    resolved: BTreeMap<String, String>,
+    in_progress: Vec<String>,
+}
+
+impl Resolver<'_> {
+    /// The value of a var as the process will see it. Platform vars win over secrets,
+    /// which win over env vars, the same order they are applied in.
+    fn lookup(&mut self, referrer: &str, name: &str) -> Result<String, String> {
+        if let Some(value) = self.platform.get(name) {
+            return Ok(value.clone());
+        }
+        if PLATFORM_VARS.contains(&name) {
+            return Ok(String::new());
+        }
+        if let Some(value) = self.secrets.get(name) {
+            return Ok(value.clone());
+        }
+        if self.vars.contains_key(name) {
+            return self.resolve(name);
+        }
+        Err(format!("{} references ${{{}}}, which isn't set", referrer, name))
+    }
+
+    fn resolve(&mut self, name: &str) -> Result<String, String> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(value.clone());
+        }
+        if let Some(start) = self.in_progress.iter().position(|n| n == name) {
+            let mut cycle = self.in_progress[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(format!("Env vars reference each other in a cycle: {}", cycle.join(" -> ")));
+        }
+
+        let vars = self.vars;
+        let segments = parse(&vars[name]).map_err(|e| format!("Invalid value for {}: {}", name, e))?;
+        self.in_progress.push(name.to_string());
+        let mut value = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Literal(text) => value.push_str(text),
+                Segment::Var(var) => value.push_str(&self.lookup(name, var)?),
+                Segment::App { name: app, attribute } => {
+                    if !APP_ATTRIBUTES.contains(&attribute) {
+                        return Err(format!(
+                            "{} references ${{app:{}.{}}}: unknown attribute '{}', expected one of {}",
+                            name,
+                            app,
+                            attribute,
+                            attribute,
+                            APP_ATTRIBUTES.join(", ")
+                        ));
+                    }
+                    let resolved = (self.app_attribute)(app, attribute)
+                        .ok_or_else(|| format!("{} references unknown app '{}'", name, app))?;
+                    value.push_str(&resolved);
+                }
+            }
+        }
+        self.in_progress.pop();
+
+        self.resolved.insert(name.to_string(), value.clone());
+        Ok(value)
+    }
+}
+
+/// Expand the references in `vars`. Secrets and platform vars can be referenced but are
+/// taken literally. `app_attribute(name, attribute)` returns None for apps that don't exist.
+/// Fails on syntax errors, missing references and cycles.
+pub fn resolve_env(
+    vars: &BTreeMap<String, String>,
+    secrets: &BTreeMap<String, String>,
+    platform: &BTreeMap<String, String>,
+    app_attribute: &dyn Fn(&str, &str) -> Option<String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut resolver = Resolver {
+        vars,
+        secrets,
+        platform,
+        app_attribute,
+        resolved: BTreeMap::new(),
+        in_progress: Vec::new(),
+    };
+    for name in vars.keys() {
+        resolver.resolve(name)?;
+    }
+    Ok(resolver.resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{referenced_apps, references_apps, resolve_env};
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn apps(name: &str, attribute: &str) -> Option<String> {
+        match (name, attribute) {
+            ("api", "url") => Some("http://localhost:8001".to_string()),
+            ("api", "port") => Some("8001".to_string()),
+            _ => None,
+        }
+    }
+
+    fn resolve(vars: &[(&str, &str)]) -> Result<BTreeMap<String, String>, String> {
+        resolve_env(&map(vars), &map(&[("TOKEN", "s3cret")]), &map(&[("PORT", "8080")]), &apps)
+    }
+
+    #[test]
+    fn expands_vars_secrets_platform_vars_and_apps() {
+        let resolved = resolve(&[
+            ("BASE", "${API}/v1"),
+            ("API", "${app:api.url}"),
+            ("AUTH", "Bearer ${TOKEN}"),
+            ("LISTEN", "0.0.0.0:${PORT}"),
+        ])
+        .unwrap();
+        assert_eq!(resolved["BASE"], "http://localhost:8001/v1");
+        assert_eq!(resolved["AUTH"], "Bearer s3cret");
+        assert_eq!(resolved["LISTEN"], "0.0.0.0:8080");
+    }
+
+    #[test]
+    fn secrets_and_platform_values_are_not_expanded() {
+        let vars = map(&[("A", "${TOKEN}")]);
+        let secrets = map(&[("TOKEN", "${NOPE}")]);
+        let resolved = resolve_env(&vars, &secrets, &BTreeMap::new(), &apps).unwrap();
+        assert_eq!(resolved["A"], "${NOPE}");
+    }
+
+    #[test]
+    fn escapes_and_lone_dollars_are_literal() {
+        let resolved = resolve(&[("A", "$${NOT_A_REF} costs $5 and $$"), ("B", "$")]).unwrap();
+        assert_eq!(resolved["A"], "${NOT_A_REF} costs $5 and $$");
+        assert_eq!(resolved["B"], "$");
+    }
+
+    #[test]
+    fn unset_platform_vars_resolve_empty() {
+        assert_eq!(resolve(&[("A", "[${PAAS_RELEASE}]")]).unwrap()["A"], "[]");
+    }
+
+    #[test]
+    fn rejects_malformed_references() {
+        assert!(resolve(&[("A", "${OPEN")]).unwrap_err().contains("unterminated"));
+        assert!(resolve(&[("A", "${1BAD}")]).unwrap_err().contains("invalid reference"));
+        assert!(resolve(&[("A", "${app:api}")]).unwrap_err().contains("app:<name>.<attribute>"));
+        assert!(resolve(&[("A", "${app:api.color}")]).unwrap_err().contains("unknown attribute"));
+        assert!(resolve(&[("A", "${app:web.url}")]).unwrap_err().contains("unknown app 'web'"));
+        assert!(resolve(&[("A", "${MISSING}")]).unwrap_err().contains("isn't set"));
+    }
+
+    #[test]
+    fn detects_reference_cycles() {
+        let error = resolve(&[("A", "${B}"), ("B", "${C}"), ("C", "${A}")]).unwrap_err();
+        assert!(error.contains("A -> B -> C -> A"), "{}", error);
+        let error = resolve(&[("SELF", "x${SELF}")]).unwrap_err();
+        assert!(error.contains("SELF -> SELF"), "{}", error);
+    }
+
+    #[test]
+    fn detects_app_references() {
+        assert!(references_apps(&map(&[("A", "x"), ("B", "${app:api.url}")])));
+        assert!(!references_apps(&map(&[("A", "${B}"), ("B", "$${app:api.url}"), ("C", "${OPEN")])));
+    }
+
+    #[test]
+    fn lists_referenced_apps() {
+        let vars = map(&[("A", "${app:api.url}"), ("B", "${app:db.host}:${app:api.port}"), ("C", "${OPEN")]);
+        assert_eq!(referenced_apps(&vars).into_iter().collect::<Vec<_>>(), vec!["api", "db"]);
+    }
+}
@@ -1,8 +1,16 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 use uuid::Uuid;
 
+pub mod error;
+pub mod interpolate;
+
+pub use error::{ApiError, ErrorCode};
+
 #[derive(Deserialize, Serialize, sqlx::Type, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[sqlx(type_name = "app_status", rename_all = "UPPERCASE")]
 pub enum AppStatus {
     PENDING,
@@ -10,21 +18,79 @@ pub enum AppStatus {
     STOPPED,
     FAILED,
     CRASHED,
+    BUILDING,
 }
 
+/// Process type that serves HTTP traffic and owns the app's port.
+pub const WEB_PROCESS: &str = "web";
+
+/// Process type used to tag build output in the logs
+pub const BUILD_PROCESS: &str = "build";
+
+/// `on_link_change` values: restart the app, send its processes SIGHUP, or do nothing
+pub const LINK_CHANGE_ACTIONS: &[&str] = &["restart", "reload", "none"];
+
+/// `on_link_change` used when paas.toml doesn't set it
+pub const DEFAULT_LINK_CHANGE: &str = "restart";
+
+/// Build timeout used when paas.toml doesn't set `build_timeout`
+pub const DEFAULT_BUILD_TIMEOUT_SECS: i32 = 600;
+
 #[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct Application {
     pub id: Option<Uuid>,
     pub name: String,
     pub command: String,
     pub status: AppStatus,
+    /// 0 (or omitted) when creating an app asks paasd to allocate a free port
+    #[serde(default)]
     pub port: i32,
     pub working_dir: String,
     pub pid: Option<i32>,
     pub env_vars: Option<serde_json::Value>,
+    /// Process types mapped to their commands, e.g. `{"web": "...", "worker": "..."}`
+    pub processes: Option<serde_json::Value>,
+    /// Runs in the working directory before any process is started
+    pub build_command: Option<String>,
+    /// Seconds before a running build is killed
+    pub build_timeout: Option<i32>,
+    /// Uploaded source the app runs from. Apps without one run from `working_dir`.
+    pub release_id: Option<Uuid>,
+    /// Names of apps whose address is injected as `<NAME>_URL`, e.g. `["api"]`
+    pub links: Option<serde_json::Value>,
+    /// What happens when a linked app moves to another port, one of `LINK_CHANGE_ACTIONS`
+    pub on_link_change: Option<String>,
+    /// Free-form `key = value` tags for filtering, e.g. `{"team": "payments"}`
+    pub labels: Option<serde_json::Value>,
 }
 
-#[derive(Deserialize, Serialize, Debug, FromRow)]
+impl Application {
+    /// Names of the apps this app links to
+    pub fn link_names(&self) -> Vec<String> {
+        self.links
+            .as_ref()
+            .and_then(|l| l.as_array())
+            .map(|names| names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+            .unwrap_or_default()
+    }
+
+    /// The app's env vars as a name to value map. Non-string values are ignored.
+    pub fn env_map(&self) -> BTreeMap<String, String> {
+        self.env_vars
+            .as_ref()
+            .and_then(|e| e.as_object())
+            .map(|map| {
+                map.iter()
+                    .filter_map(|(key, val)| val.as_str().map(|v| (key.clone(), v.to_string())))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct PatchApplication {
     pub name: Option<String>,
     pub command: Option<String>,
@@ -33,20 +99,422 @@ pub struct PatchApplication {
     pub working_dir: Option<String>,
     pub pid: Option<i32>,
     pub env_vars: Option<serde_json::Value>,
+    pub processes: Option<serde_json::Value>,
+    pub build_command: Option<String>,
+    pub build_timeout: Option<i32>,
+    pub release_id: Option<Uuid>,
+    pub links: Option<serde_json::Value>,
+    pub on_link_change: Option<String>,
+    pub labels: Option<serde_json::Value>,
+    /// Set by the agent when `port` is the port it found the app's web process listening on
+    #[serde(default)]
+    pub port_detected: bool,
+}
+
+/// Lifecycle timestamps and counters paasd keeps for each app
+#[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppStats {
+    /// When the web process last started, including restarts after a crash
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub deployed_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Crash restarts by the agent since the app was created
+    pub restart_count: i32,
+    /// When the app was destroyed, for apps still in their restore window
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// An app as listed by `GET /apps`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppSummary {
+    #[serde(flatten)]
+    pub app: Application,
+    #[serde(flatten)]
+    pub stats: AppStats,
+    /// Status of the web process according to the agent, e.g. `RUNNING` or `STOPPED`
+    pub live_status: String,
+}
+
+/// Answer to `POST /apps`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatedApp {
+    pub id: Uuid,
+    /// The allocated port when the app asked for one
+    pub port: i32,
+    pub building: bool,
+}
+
+/// An app as returned by `GET /apps/{app_id}`, with the release it runs from
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppDetails {
+    #[serde(flatten)]
+    pub app: Application,
+    pub release: Option<Release>,
+}
+
+/// Answer to `GET /apps/{app_id}/status`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppLiveStatus {
+    pub id: Uuid,
+    pub name: String,
+    /// Status of the web process according to the agent, e.g. `RUNNING`, `STOPPED` or `BUILDING`
+    pub status: String,
+    pub pid: Option<i32>,
+    pub port: i32,
+    pub command: String,
+    pub release: Option<Release>,
+}
+
+/// Answer to `DELETE /apps/{app_id}`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeletedApp {
+    pub id: Uuid,
+    pub name: String,
+    pub purged: bool,
+    /// When a destroyed app that wasn't purged is deleted for good
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_after: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Body of `POST /apps/{app_id}/redeploy`. Fields left out keep the app's current config.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RedeployApp {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<i32>,
+    /// Process types mapped to their commands, replacing the app's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processes: Option<BTreeMap<String, String>>,
+    /// An empty build command removes the build step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_timeout: Option<i32>,
+    /// Merged into the app's env vars; vars only set on the server are kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_vars: Option<BTreeMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_link_change: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<BTreeMap<String, String>>,
+    /// Switch to an uploaded release
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_id: Option<Uuid>,
+}
+
+/// Answer to `POST /apps/{app_id}/redeploy`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Redeployed {
+    pub name: String,
+    pub port: i32,
+    /// Whether a build was started; the previous version keeps running until it succeeds
+    pub building: bool,
+}
+
+/// A single running instance of one of an app's process types (e.g. `worker.2`)
+#[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppProcess {
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub process_type: String,
+    pub instance: i32,
+    pub command: String,
+    pub status: AppStatus,
+    pub pid: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PatchProcess {
+    pub status: Option<AppStatus>,
+    pub pid: Option<i32>,
+    /// Set by the agent when it respawned the process after a crash
+    #[serde(default)]
+    pub restarted: bool,
+}
+
+/// Payload sent from paasd to the agent to start one process instance
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RunProcess {
+    pub app: Application,
+    pub process: AppProcess,
+    /// Decrypted secrets, only ever sent to the agent
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// Payload sent from paasd to the agent to build an app
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RunBuild {
+    pub app: Application,
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// Outcome of a build, returned by the agent's `/build` endpoint
+#[derive(Deserialize, Serialize, Debug)]
+pub struct BuildResult {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub duration_ms: i64,
+}
+
+/// An uploaded source snapshot of an app. The tarball itself is only served to the agent.
+#[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Release {
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub version: i32,
+    pub source_sha256: String,
+    pub source_size: i64,
+    pub git_commit: Option<String>,
+    pub git_branch: Option<String>,
+    /// Whether the checkout had uncommitted changes when it was deployed
+    pub git_dirty: Option<bool>,
+    /// Set on releases created by a config change, which reuse this release's source
+    pub source_release_id: Option<Uuid>,
+    /// What changed, e.g. `Set DATABASE_URL`. None for source uploads.
+    pub description: Option<String>,
+    /// The app's env vars as of this release. Redeploying the release restores them. None for source uploads.
+    pub env_vars: Option<serde_json::Value>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Copy another app's release, e.g. from staging to production
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PromoteRelease {
+    pub from_app_id: Uuid,
+    /// Defaults to the release the other app currently runs
+    pub release_id: Option<Uuid>,
+}
+
+/// Git state sent along with a source upload
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema, utoipa::IntoParams))]
+pub struct GitMetadata {
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    pub dirty: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScaleProcess {
+    pub scale: i32,
+}
+
+/// Answer to `POST /apps/{app_id}/processes/{process_type}/scale`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScaledProcess {
+    pub process_type: String,
+    pub scale: i32,
+}
+
+/// The app's git repository on the paasd host, as returned by `POST /apps/{app_id}/git`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GitRepo {
+    pub path: String,
+    /// Branches whose pushes are deployed
+    pub branches: Vec<String>,
+}
+
+/// Sent by the post-receive hook to `POST /apps/{app_id}/git/deploy`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GitPush {
+    pub commit: String,
+    pub branch: String,
+}
+
+#[derive(Deserialize, Serialize, sqlx::Type, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[sqlx(type_name = "run_status", rename_all = "UPPERCASE")]
+pub enum RunStatus {
+    RUNNING,
+    SUCCEEDED,
+    FAILED,
+}
+
+/// Process type used to tag the output of one-off runs in the logs
+pub const RUN_PROCESS: &str = "run";
+
+/// A one-off command executed in an app's environment
+#[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AppRun {
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub command: String,
+    pub status: RunStatus,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<i64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Set when the run was triggered by a cron schedule
+    pub schedule_id: Option<Uuid>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewRun {
+    pub command: String,
+}
+
+/// Reported by the agent once a one-off run has exited
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct FinishRun {
+    pub exit_code: i32,
+    pub duration_ms: i64,
+}
+
+/// Payload sent from paasd to the agent to execute a one-off run
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct StartRun {
+    pub app: Application,
+    pub run: AppRun,
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+}
+
+/// Whether `name` can be used as an env var or secret name: letters, digits and `_`,
+/// not starting with a digit
+pub fn is_valid_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `name` can name an app. Names appear in URLs in place of ids, so they are
+/// limited to letters, digits, `-`, `_` and `.`, and can't look like an id.
+pub fn is_valid_app_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        && Uuid::parse_str(name).is_err()
+}
+
+/// The env var a linked app's address is injected as: `api` becomes `API_URL`
+pub fn link_env_name(app_name: &str) -> String {
+    let name: String = app_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}_URL", name)
+    } else {
+        format!("{}_URL", name)
+    }
+}
+
+/// A running web instance of an app, as returned by `/discovery/{app_name}`
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ServiceInstance {
+    pub process_id: Uuid,
+    pub instance: i32,
+    pub host: String,
+    pub port: i32,
+    pub url: String,
+}
+
+/// Where to reach an app. `url` is its address even while no instance is healthy.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ServiceDiscovery {
+    pub app_id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub instances: Vec<ServiceInstance>,
+}
+
+/// The app's env vars after a change through `/apps/{app_id}/env`
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct EnvUpdate {
+    pub env_vars: BTreeMap<String, String>,
+    /// The release recording the change. None when nothing changed or the app runs from `working_dir`.
+    pub release: Option<Release>,
+    pub restarted: bool,
+    pub building: bool,
+}
+
+/// A secret as listed by the API. Values are write-only.
+#[derive(Deserialize, Serialize, Debug, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SecretInfo {
+    pub name: String,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SetSecret {
+    pub value: String,
+}
+
+/// A `[[cron]]` entry from paas.toml as sent by the CLI
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct NewSchedule {
+    pub name: String,
+    /// Cron expression in UTC, either 5 fields (`0 3 * * *`) or 6 with seconds
+    pub schedule: String,
+    pub command: String,
+}
+
+/// A cron schedule together with the outcome of its most recent run
+#[derive(Deserialize, Serialize, Debug, Clone, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Schedule {
+    pub id: Uuid,
+    pub app_id: Uuid,
+    pub name: String,
+    pub schedule: String,
+    pub command: String,
+    pub enabled: bool,
+    pub next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_run_id: Option<Uuid>,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_status: Option<RunStatus>,
+    pub last_exit_code: Option<i32>,
 }
 
 #[derive(Deserialize, Serialize, Debug, FromRow)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct AppLog {
     pub id: i64,
     pub app_id: Uuid,
     pub stream: String,
     pub message: String,
+    pub process_type: String,
+    pub run_id: Option<Uuid>,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct NewAppLog {
     pub app_id: Uuid,
     pub stream: String,
     pub message: String,
+    #[serde(default)]
+    pub process_type: Option<String>,
+    #[serde(default)]
+    pub run_id: Option<Uuid>,
 }
@@ -0,0 +1,24 @@
+ALTER TABLE apps ADD COLUMN processes JSONB NOT NULL DEFAULT '{}';
+
+CREATE TABLE processes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    app_id UUID NOT NULL REFERENCES apps(id) ON DELETE CASCADE,
+    process_type TEXT NOT NULL,
+    instance INTEGER NOT NULL DEFAULT 1,
+    command TEXT NOT NULL,
+    status app_status NOT NULL DEFAULT 'PENDING',
+    pid INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (app_id, process_type, instance)
+);
+
+CREATE INDEX idx_processes_app_id ON processes(app_id);
+
+-- Every existing app gets a single web process running its command
+INSERT INTO processes (app_id, process_type, instance, command, status, pid)
+SELECT id, 'web', 1, command, status, pid FROM apps;
+
+UPDATE apps SET processes = jsonb_build_object('web', command);
+
+ALTER TABLE logs ADD COLUMN process_type TEXT NOT NULL DEFAULT 'web';
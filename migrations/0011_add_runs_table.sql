@@ -0,0 +1,18 @@
+CREATE TYPE run_status AS ENUM('RUNNING', 'SUCCEEDED', 'FAILED');
+
+CREATE TABLE runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    app_id UUID NOT NULL REFERENCES apps(id) ON DELETE CASCADE,
+    command TEXT NOT NULL,
+    status run_status NOT NULL DEFAULT 'RUNNING',
+    exit_code INTEGER,
+    duration_ms BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_runs_app_id ON runs(app_id);
+
+-- Output of one-off runs is stored alongside app logs, tagged with the run
+ALTER TABLE logs ADD COLUMN run_id UUID REFERENCES runs(id) ON DELETE CASCADE;
+CREATE INDEX idx_logs_run_id ON logs(run_id);
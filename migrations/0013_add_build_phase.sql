@@ -0,0 +1,5 @@
+ALTER TYPE app_status ADD VALUE IF NOT EXISTS 'BUILDING';
+
+ALTER TABLE apps
+    ADD COLUMN build_command TEXT,
+    ADD COLUMN build_timeout INTEGER;
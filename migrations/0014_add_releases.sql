@@ -0,0 +1,14 @@
+-- Uploaded source snapshots. A release is never modified once stored.
+CREATE TABLE releases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    app_id UUID NOT NULL REFERENCES apps(id) ON DELETE CASCADE,
+    version INTEGER NOT NULL,
+    source BYTEA NOT NULL,
+    source_sha256 TEXT NOT NULL,
+    source_size BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (app_id, version)
+);
+
+-- The release the app's processes run from. NULL for apps that run from working_dir.
+ALTER TABLE apps ADD COLUMN release_id UUID REFERENCES releases(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- Git state of the project when a release was uploaded. NULL when not deployed from a git checkout.
+ALTER TABLE releases ADD COLUMN git_commit TEXT;
+ALTER TABLE releases ADD COLUMN git_branch TEXT;
+ALTER TABLE releases ADD COLUMN git_dirty BOOLEAN;
@@ -0,0 +1,4 @@
+-- Config changes create a release that reuses the source of an earlier one instead of copying it
+ALTER TABLE releases ALTER COLUMN source DROP NOT NULL;
+ALTER TABLE releases ADD COLUMN source_release_id UUID REFERENCES releases(id);
+ALTER TABLE releases ADD COLUMN description TEXT;
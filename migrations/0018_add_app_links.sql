@@ -0,0 +1,6 @@
+-- Apps an app links to get their address injected as <NAME>_URL
+ALTER TABLE apps
+    ADD COLUMN links JSONB NOT NULL DEFAULT '[]',
+    ADD COLUMN on_link_change TEXT NOT NULL DEFAULT 'restart';
+
+CREATE INDEX idx_apps_links ON apps USING GIN (links);
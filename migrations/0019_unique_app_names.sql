@@ -0,0 +1,14 @@
+-- App names identify apps in the API and CLI, so they must be unique.
+-- Of apps sharing a name, a running one (else the oldest) keeps it; the others get their id's prefix appended.
+WITH ranked AS (
+    SELECT id, row_number() OVER (
+        PARTITION BY name
+        ORDER BY status = 'STOPPED'::app_status ASC, created_at ASC, id ASC
+    ) AS n
+    FROM apps
+)
+UPDATE apps SET name = apps.name || '-' || left(apps.id::text, 8)
+FROM ranked
+WHERE apps.id = ranked.id AND ranked.n > 1;
+
+ALTER TABLE apps ADD CONSTRAINT apps_name_key UNIQUE (name);
@@ -0,0 +1,7 @@
+ALTER TABLE apps
+    ADD COLUMN labels JSONB NOT NULL DEFAULT '{}',
+    ADD COLUMN started_at TIMESTAMPTZ,
+    ADD COLUMN deployed_at TIMESTAMPTZ,
+    ADD COLUMN restart_count INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX idx_apps_labels ON apps USING GIN (labels);
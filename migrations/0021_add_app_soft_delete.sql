@@ -0,0 +1,4 @@
+-- Destroyed apps are kept for a restore window before being purged
+ALTER TABLE apps ADD COLUMN deleted_at TIMESTAMPTZ;
+
+CREATE INDEX idx_apps_deleted_at ON apps (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Config releases keep the env vars they were created with, so redeploying one restores them
+ALTER TABLE releases ADD COLUMN env_vars JSONB;